@@ -0,0 +1,50 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use clap::{App, Arg};
+
+use tapyrus_signer::blockdata::Block;
+use tapyrus_signer::sign::SighashVariant;
+
+const OPTION_NAME_BLOCK: &str = "block";
+const OPTION_NAME_SIGHASH_VARIANT: &str = "sighash_variant";
+
+/// Print the message which signers sign for the candidate block.
+///
+/// command example:
+/// ./target/debug/blocksighash 00000020...
+fn main() {
+    let matches = App::new("blocksighash")
+        .about("Compute the sighash which signers sign for the candidate block.")
+        .after_help("EXAMPLE:\n    blocksighash $(tapyrus-cli -regtest getnewblock <address>)")
+        .arg(
+            Arg::with_name(OPTION_NAME_BLOCK)
+                .required(true)
+                .value_name("BLOCK_HEX")
+                .help("Candidate block hex string. (e.g. result of getnewblock RPC)"),
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_SIGHASH_VARIANT)
+                .long("sighash-variant")
+                .takes_value(true)
+                .value_name("VARIANT")
+                .default_value("header")
+                .help("Variant of the message which signers sign. It must be same as the node."),
+        )
+        .get_matches();
+
+    let block_hex = matches.value_of(OPTION_NAME_BLOCK).unwrap();
+    let variant = matches.value_of(OPTION_NAME_SIGHASH_VARIANT).unwrap();
+    let result = variant
+        .parse::<SighashVariant>()
+        .and_then(|variant| Block::from_hex(block_hex).and_then(|block| variant.sighash(&block)));
+    match result {
+        Ok(message) => println!("{}", hex::encode(&message[..])),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
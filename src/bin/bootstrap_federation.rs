@@ -0,0 +1,423 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use std::io::Write;
+use std::path::Path;
+
+use bitcoin::{Network, PrivateKey, PublicKey};
+use clap::{App, Arg};
+
+use tapyrus_signer::errors::Error;
+use tapyrus_signer::sign::generate_private_key;
+use tapyrus_signer::signer_node::canonical_order;
+use tapyrus_signer::token::FederationToken;
+
+const OPTION_NAME_SIGNERS: &str = "signers";
+const OPTION_NAME_THRESHOLD: &str = "threshold";
+const OPTION_NAME_OUT: &str = "out";
+const OPTION_NAME_ARCHIVE: &str = "archive";
+/// Size of a header and data block of tar archive.
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// Generate keys and config files of a federation for local development.
+/// It writes `signer_<i>.toml` for each signer and `federation.toml` which has the public keys,
+/// threshold and the options for Tapyrus Core. The signer 0 is the master of the first round.
+/// The private keys are for regtest, so don't use them for production.
+/// With `--archive`, the files are written in one tar archive instead of a directory.
+///
+/// command example:
+/// ./target/debug/bootstrap_federation --signers 5 --threshold 3 --out ./federation
+/// ./target/debug/bootstrap_federation --signers 5 --threshold 3 --archive ./federation.tar
+fn main() {
+    let matches = App::new("bootstrap_federation")
+        .about("Generate keys and config files of a federation for local development.")
+        .after_help("EXAMPLES:\n    bootstrap_federation --signers 5 --threshold 3 --out ./federation\n    bootstrap_federation --signers 5 --threshold 3 --archive ./federation.tar")
+        .arg(
+            Arg::with_name(OPTION_NAME_SIGNERS)
+                .long("signers")
+                .takes_value(true)
+                .required(true)
+                .value_name("NUM"),
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_THRESHOLD)
+                .long("threshold")
+                .takes_value(true)
+                .required(true)
+                .value_name("NUM"),
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_OUT)
+                .long("out")
+                .takes_value(true)
+                .required_unless(OPTION_NAME_ARCHIVE)
+                .conflicts_with(OPTION_NAME_ARCHIVE)
+                .value_name("DIR")
+                .help("Directory which config files are written to."),
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_ARCHIVE)
+                .long("archive")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Tar archive which config files are written to. Config files of the signers are readable only by the owner."),
+        )
+        .get_matches();
+
+    let signers: usize = matches
+        .value_of(OPTION_NAME_SIGNERS)
+        .unwrap()
+        .parse()
+        .expect("signers must be number.");
+    let threshold: u8 = matches
+        .value_of(OPTION_NAME_THRESHOLD)
+        .unwrap()
+        .parse()
+        .expect("threshold must be number from 1 to 255.");
+
+    if let Err(e) = validate(signers, threshold) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let files = federation_files(&generate_keys(signers), threshold).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Some(archive) = matches.value_of(OPTION_NAME_ARCHIVE) {
+        // it has the private keys.
+        write_file(Path::new(archive), &tar_archive(&files), 0o600).expect("Can't write archive.");
+        for file in &files {
+            println!("{}:{}", archive, file.name);
+        }
+        return;
+    }
+    let out = Path::new(matches.value_of(OPTION_NAME_OUT).unwrap());
+    std::fs::create_dir_all(out).expect("Can't create output directory.");
+    for file in &files {
+        let path = out.join(&file.name);
+        write_file(&path, file.contents.as_bytes(), file.mode).expect("Can't write config file.");
+        println!("{}", path.display());
+    }
+}
+
+/// Config file of the federation.
+struct FederationFile {
+    name: String,
+    contents: String,
+    /// Unix permission of the file.
+    mode: u32,
+}
+
+/// Write `contents` to `path` with the unix permission `mode`. The file is created with the
+/// permission, so that the private key is never readable by others.
+fn write_file(path: &Path, contents: &[u8], mode: u32) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        // the mode is applied only when the file is created, so change the existing file too.
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    file.write_all(contents)
+}
+
+/// Config files of each signer and the federation descriptor.
+fn federation_files(keys: &[PrivateKey], threshold: u8) -> Result<Vec<FederationFile>, Error> {
+    let pubkeys = public_keys(keys);
+    let mut files: Vec<FederationFile> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| FederationFile {
+            name: format!("signer_{}.toml", i),
+            contents: signer_config(&pubkeys, key, threshold, i == 0),
+            // it has the private key.
+            mode: 0o600,
+        })
+        .collect();
+    files.push(FederationFile {
+        name: "federation.toml".to_string(),
+        contents: federation_descriptor(&pubkeys, threshold)?,
+        mode: 0o644,
+    });
+    Ok(files)
+}
+
+/// Tar archive(ustar) of the files.
+fn tar_archive(files: &[FederationFile]) -> Vec<u8> {
+    fn octal(field: &mut [u8], value: u64) {
+        // zero padded octal number terminated by NUL.
+        let s = format!("{:0width$o}\0", value, width = field.len() - 1);
+        field.copy_from_slice(s.as_bytes());
+    }
+
+    let mtime = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut archive = Vec::new();
+    for file in files {
+        let mut header = [0u8; TAR_BLOCK_SIZE];
+        header[..file.name.len()].copy_from_slice(file.name.as_bytes());
+        octal(&mut header[100..108], u64::from(file.mode));
+        octal(&mut header[108..116], 0);
+        octal(&mut header[116..124], 0);
+        octal(&mut header[124..136], file.contents.len() as u64);
+        octal(&mut header[136..148], mtime);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        // checksum is calculated with its field filled with spaces.
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(file.contents.as_bytes());
+        let padding = (TAR_BLOCK_SIZE - file.contents.len() % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+        archive.resize(archive.len() + padding, 0);
+    }
+    // end of archive is two zero blocks.
+    archive.resize(archive.len() + TAR_BLOCK_SIZE * 2, 0);
+    archive
+}
+
+fn validate(signers: usize, threshold: u8) -> Result<(), String> {
+    if threshold == 0 || threshold as usize > signers {
+        return Err(format!(
+            "threshold must be 1 to the number of signers. signers: {}, threshold: {}",
+            signers, threshold
+        ));
+    }
+    if signers > 255 {
+        return Err(format!("signers must be 255 or less. signers: {}", signers));
+    }
+    Ok(())
+}
+
+fn generate_keys(signers: usize) -> Vec<PrivateKey> {
+    (0..signers)
+        .map(|_| generate_private_key(Network::Testnet).expect("Can't generate private key."))
+        .collect()
+}
+
+/// Public keys in the canonical order of the signer network.
+fn public_keys(keys: &[PrivateKey]) -> Vec<PublicKey> {
+    let secp = secp256k1::Secp256k1::signing_only();
+    let mut pubkeys: Vec<PublicKey> = keys.iter().map(|k| k.public_key(&secp)).collect();
+    canonical_order(&mut pubkeys);
+    pubkeys
+}
+
+fn publickeys_toml(pubkeys: &[PublicKey]) -> String {
+    pubkeys.iter().map(|pk| format!("\"{}\",\n", pk)).collect()
+}
+
+fn signer_config(
+    pubkeys: &[PublicKey],
+    private_key: &PrivateKey,
+    threshold: u8,
+    master: bool,
+) -> String {
+    format!(
+        r#"[signer]
+publickeys = [
+{}]
+privatekey = "{}"
+threshold = {}
+
+[rpc]
+rpc_endpoint_host = "127.0.0.1"
+rpc_endpoint_port = 12381
+rpc_endpoint_user = "user"
+rpc_endpoint_pass = "pass"
+
+[redis]
+redis_host = "127.0.0.1"
+redis_port = 6379
+
+[general]
+round_duration = 5
+log_level = "info"
+master = {}
+network = "regtest"
+"#,
+        publickeys_toml(pubkeys),
+        private_key.to_wif(),
+        threshold,
+        master
+    )
+}
+
+/// Public information of the federation, shared by all signers.
+fn federation_descriptor(pubkeys: &[PublicKey], threshold: u8) -> Result<String, Error> {
+    let concatenated: String = pubkeys.iter().map(|pk| pk.to_string()).collect();
+    let token = FederationToken::new(pubkeys.to_vec(), threshold)?;
+    Ok(format!(
+        r#"publickeys = [
+{}]
+threshold = {}
+
+# public keys and threshold in one string. `decode_token` expands it.
+token = "{}"
+
+# options for tapyrus-core of this federation.
+tapyrus_core_args = ["-signblockpubkeys={}", "-signblockthreshold={}"]
+"#,
+        publickeys_toml(pubkeys),
+        threshold,
+        token,
+        concatenated,
+        threshold
+    ))
+}
+
+#[test]
+fn test_validate() {
+    assert!(validate(5, 3).is_ok());
+    assert!(validate(5, 5).is_ok());
+    assert!(validate(3, 5).is_err());
+    assert!(validate(3, 0).is_err());
+    assert!(validate(256, 3).is_err());
+}
+
+#[test]
+fn test_signer_config() {
+    use tapyrus_signer::command_args::validate_config;
+
+    let keys = generate_keys(5);
+    let pubkeys = public_keys(&keys);
+    for (i, key) in keys.iter().enumerate() {
+        let config = signer_config(&pubkeys, key, 3, i == 0);
+        assert_eq!(validate_config(&config), vec![]);
+
+        // private key of each signer is paired with one of the public keys.
+        let selected = tapyrus_signer::signer_node::select_private_key(&[*key], &pubkeys);
+        assert!(selected.is_ok());
+    }
+}
+
+#[test]
+fn test_tar_archive() {
+    use tapyrus_signer::command_args::validate_config;
+
+    fn octal(field: &[u8]) -> usize {
+        let s = String::from_utf8_lossy(field);
+        usize::from_str_radix(s.trim_matches(|c| c == '\0' || c == ' '), 8).unwrap()
+    }
+
+    let archive = tar_archive(&federation_files(&generate_keys(3), 2).unwrap());
+    assert_eq!(archive.len() % TAR_BLOCK_SIZE, 0);
+
+    // extract entries.
+    let mut entries = vec![];
+    let mut offset = 0;
+    while archive[offset..offset + TAR_BLOCK_SIZE]
+        .iter()
+        .any(|b| *b != 0)
+    {
+        let header = &archive[offset..offset + TAR_BLOCK_SIZE];
+        let name_len = header[..100].iter().position(|b| *b == 0).unwrap();
+        let name = String::from_utf8(header[..name_len].to_vec()).unwrap();
+        let mode = octal(&header[100..108]);
+        let size = octal(&header[124..136]);
+        let mut checksum_header = header.to_vec();
+        checksum_header[148..156].copy_from_slice(b"        ");
+        let checksum: usize = checksum_header.iter().map(|b| *b as usize).sum();
+        assert_eq!(octal(&header[148..156]), checksum);
+        assert_eq!(&header[257..263], b"ustar\0");
+
+        let start = offset + TAR_BLOCK_SIZE;
+        let contents = String::from_utf8(archive[start..start + size].to_vec()).unwrap();
+        entries.push((name, mode, contents));
+        offset = start + size.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE;
+    }
+
+    let names: Vec<&str> = entries.iter().map(|(name, _, _)| name as &str).collect();
+    assert_eq!(
+        names,
+        vec![
+            "signer_0.toml",
+            "signer_1.toml",
+            "signer_2.toml",
+            "federation.toml"
+        ]
+    );
+    for (name, mode, contents) in &entries[..3] {
+        assert_eq!(*mode, 0o600, "{}", name);
+        assert_eq!(validate_config(contents), vec![], "{}", name);
+    }
+    assert_eq!(entries[3].1, 0o644);
+    assert!(toml::from_str::<toml::Value>(&entries[3].2).is_ok());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_write_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!(
+        "tapyrus-signer-test-bootstrap-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mode_of = |name: &str| {
+        std::fs::metadata(dir.join(name))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777
+    };
+
+    for file in &federation_files(&generate_keys(2), 2).unwrap() {
+        write_file(&dir.join(&file.name), file.contents.as_bytes(), file.mode).unwrap();
+    }
+    assert_eq!(mode_of("signer_0.toml"), 0o600);
+    assert_eq!(mode_of("signer_1.toml"), 0o600);
+    assert_eq!(mode_of("federation.toml"), 0o644);
+
+    // the existing file which others can read.
+    std::fs::set_permissions(
+        dir.join("signer_0.toml"),
+        std::fs::Permissions::from_mode(0o644),
+    )
+    .unwrap();
+    write_file(&dir.join("signer_0.toml"), b"contents", 0o600).unwrap();
+    assert_eq!(mode_of("signer_0.toml"), 0o600);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("signer_0.toml")).unwrap(),
+        "contents"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_federation_descriptor() {
+    let keys = generate_keys(3);
+    let pubkeys = public_keys(&keys);
+    let descriptor: toml::Value =
+        toml::from_str(&federation_descriptor(&pubkeys, 2).unwrap()).unwrap();
+    assert_eq!(descriptor["threshold"].as_integer(), Some(2));
+    assert_eq!(descriptor["publickeys"].as_array().unwrap().len(), 3);
+    let token: FederationToken = descriptor["token"].as_str().unwrap().parse().unwrap();
+    assert_eq!(token, FederationToken::new(pubkeys.clone(), 2).unwrap());
+    let args = descriptor["tapyrus_core_args"].as_array().unwrap();
+    assert_eq!(
+        args[0].as_str().unwrap().len(),
+        "-signblockpubkeys=".len() + 66 * 3
+    );
+
+    // threshold is more than the number of signers.
+    assert!(federation_descriptor(&pubkeys, 4).is_err());
+}
@@ -0,0 +1,46 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use clap::{App, Arg};
+
+use tapyrus_signer::token::FederationToken;
+
+const OPTION_NAME_TOKEN: &str = "token";
+
+/// Print the public keys and threshold of the federation token.
+///
+/// command example:
+/// ./target/debug/decode_token <token in federation.toml>
+fn main() {
+    let matches = App::new("decode_token")
+        .about("Expand the federation token to the public keys and threshold.")
+        .after_help("EXAMPLE:\n    decode_token <token in federation.toml>")
+        .arg(
+            Arg::with_name(OPTION_NAME_TOKEN)
+                .required(true)
+                .value_name("TOKEN")
+                .help("Federation token. (e.g. token in federation.toml of bootstrap_federation)"),
+        )
+        .get_matches();
+
+    match matches
+        .value_of(OPTION_NAME_TOKEN)
+        .unwrap()
+        .parse::<FederationToken>()
+    {
+        Ok(token) => {
+            println!("threshold = {}", token.threshold);
+            println!("publickeys = [");
+            for pk in &token.pubkey_list {
+                println!("\"{}\",", pk);
+            }
+            println!("]");
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
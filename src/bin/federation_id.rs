@@ -0,0 +1,30 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use tapyrus_signer::command_args::{get_options, CommandArgs};
+use tapyrus_signer::token::FederationToken;
+
+/// Print the identifier of the federation, derived from the public keys and threshold, for
+/// indexing federations. Public keys and threshold are same options as `node`.
+///
+/// command example:
+/// ./target/debug/federation_id -c signer_config.toml
+fn main() {
+    let matches = get_options()
+        .name("federation_id")
+        .about("Print the identifier of the federation.")
+        .after_help("EXAMPLE:\n    federation_id -c signer_config.toml")
+        .get_matches();
+    let configs = CommandArgs::load(matches).unwrap();
+    let signer_config = configs.signer_config();
+
+    match FederationToken::new(signer_config.public_keys(), signer_config.threshold()) {
+        Ok(token) => println!("{}", token.federation_id()),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use bitcoin::blockdata::opcodes::all::OP_CHECKMULTISIG;
+use bitcoin::blockdata::script::{Builder, Script};
+use bitcoin::{Address, Network, PublicKey};
+
+use tapyrus_signer::command_args::{get_options, CommandArgs};
+use tapyrus_signer::errors::Error;
+use tapyrus_signer::signer_node::canonical_order;
+
+/// Maximum number of public keys in a standard P2SH multisig script.
+const MAX_P2SH_MULTISIG_KEYS: usize = 15;
+
+/// Print the threshold multisig script of the federation public keys, and its P2SH form for
+/// funding transactions. Public keys, threshold and network are same options as `node`.
+///
+/// command example:
+/// ./target/debug/federation_script -c signer_config.toml --network regtest
+fn main() {
+    let matches = get_options()
+        .name("federation_script")
+        .about("Print the multisig script of the federation.")
+        .after_help("EXAMPLE:\n    federation_script -c signer_config.toml --network regtest")
+        .get_matches();
+    let configs = CommandArgs::load(matches).unwrap();
+    let signer_config = configs.signer_config();
+    let network = configs
+        .general_config()
+        .network()
+        .unwrap_or(Network::Testnet);
+
+    let redeem_script = match redeem_script(signer_config.public_keys(), signer_config.threshold())
+    {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let script_pubkey = redeem_script.to_p2sh();
+    println!("redeem_script: {:x}", redeem_script);
+    println!("redeem_script_asm: {}", asm(&redeem_script));
+    println!("script_pubkey: {:x}", script_pubkey);
+    println!("script_pubkey_asm: {}", asm(&script_pubkey));
+    println!("address: {}", Address::p2sh(&redeem_script, network));
+}
+
+/// `<threshold> <public keys in the canonical order> <count of keys> OP_CHECKMULTISIG`
+fn redeem_script(mut pubkey_list: Vec<PublicKey>, threshold: u8) -> Result<Script, Error> {
+    if threshold == 0 || pubkey_list.len() < threshold as usize {
+        return Err(Error::InvalidThreshold {
+            threshold,
+            signers: pubkey_list.len(),
+        });
+    }
+    if pubkey_list.len() > MAX_P2SH_MULTISIG_KEYS {
+        return Err(Error::InvalidArgs(format!(
+            "P2SH multisig script can have {} public keys at most. publickeys: {}",
+            MAX_P2SH_MULTISIG_KEYS,
+            pubkey_list.len()
+        )));
+    }
+    if let Some(pk) = pubkey_list.iter().find(|pk| !pk.compressed) {
+        return Err(Error::InvalidPublicKeyFormat(pk.to_string()));
+    }
+    canonical_order(&mut pubkey_list);
+    let builder = pubkey_list.iter().fold(
+        Builder::new().push_int(i64::from(threshold)),
+        |builder, pk| builder.push_key(pk),
+    );
+    Ok(builder
+        .push_int(pubkey_list.len() as i64)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script())
+}
+
+/// Script in asm, without `Script()` of the debug format.
+fn asm(script: &Script) -> String {
+    let s = script.to_string();
+    s.trim_start_matches("Script(")
+        .trim_end_matches(')')
+        .to_string()
+}
+
+#[test]
+fn test_redeem_script() {
+    use tapyrus_signer::test_helper::TestKeys;
+
+    let mut pubkeys = TestKeys::new().pubkeys();
+    let script = redeem_script(pubkeys.clone(), 3).unwrap();
+    let bytes = script.as_bytes();
+    // OP_3 <33 bytes key> * 5 OP_5 OP_CHECKMULTISIG
+    assert_eq!(bytes.len(), 1 + 34 * 5 + 2);
+    assert_eq!(bytes[0], 0x53);
+    assert_eq!(bytes[bytes.len() - 2], 0x55);
+    assert_eq!(bytes[bytes.len() - 1], 0xae);
+    assert!(asm(&script).starts_with("OP_PUSHNUM_3 OP_PUSHBYTES_33 "));
+
+    // the script doesn't depend on the order of the keys.
+    pubkeys.reverse();
+    assert_eq!(redeem_script(pubkeys.clone(), 3).unwrap(), script);
+
+    assert!(script.to_p2sh().is_p2sh());
+    let address = Address::p2sh(&script, Network::Bitcoin).to_string();
+    assert!(address.starts_with('3'));
+}
+
+#[test]
+fn test_redeem_script_invalid() {
+    use tapyrus_signer::test_helper::TestKeys;
+
+    let pubkeys = TestKeys::new().pubkeys();
+    assert!(matches!(
+        redeem_script(pubkeys.clone(), 6),
+        Err(Error::InvalidThreshold {
+            threshold: 6,
+            signers: 5
+        })
+    ));
+    assert!(matches!(
+        redeem_script(pubkeys.clone(), 0),
+        Err(Error::InvalidThreshold { .. })
+    ));
+    let many: Vec<PublicKey> = pubkeys.iter().cycle().take(16).cloned().collect();
+    assert!(matches!(redeem_script(many, 3), Err(Error::InvalidArgs(_))));
+}
@@ -0,0 +1,270 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant};
+
+use bitcoin::PublicKey;
+
+use tapyrus_signer::command_args::{get_options, CommandArgs};
+use tapyrus_signer::net::{MessageAuth, RedisManager, SignerID};
+use tapyrus_signer::observer::{Observer, RoundOutcome};
+use tapyrus_signer::rpc::{Rpc, TapyrusApi};
+
+/// Interval of updating the screen.
+const RENDER_INTERVAL_MILLIS: u64 = 1000;
+/// Signer is shown as online if any message from it is received in this number of round time
+/// limits. Members send messages only in rounds, so it must be longer than a round.
+const ONLINE_ROUNDS: u32 = 2;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Status of a signer on the screen.
+struct SignerStatus {
+    pubkey: PublicKey,
+    online: bool,
+    latency: Option<Duration>,
+}
+
+/// Status of the federation on the screen.
+struct Status {
+    height: Option<u64>,
+    threshold: u8,
+    master: Option<usize>,
+    signatures: usize,
+    signers: Vec<SignerStatus>,
+    recent_rounds: Vec<String>,
+}
+
+impl Status {
+    fn new<T: TapyrusApi>(observer: &Observer<T>, now: Instant) -> Status {
+        let pubkey_list = observer.pubkey_list();
+        let index = |id: &SignerID| pubkey_list.iter().position(|p| *p == id.pubkey);
+        let online_limit = observer.round_timelimit() * ONLINE_ROUNDS;
+        let signers = pubkey_list
+            .iter()
+            .map(|pubkey| {
+                let id = SignerID::new(*pubkey);
+                SignerStatus {
+                    pubkey: *pubkey,
+                    online: match observer.last_seen(&id) {
+                        Some(seen) => now.duration_since(seen) < online_limit,
+                        None => false,
+                    },
+                    latency: observer.signature_latency(&id),
+                }
+            })
+            .collect();
+        let recent_rounds = observer
+            .recent_rounds()
+            .iter()
+            .map(|outcome| match outcome {
+                RoundOutcome::Completed { master, latency } => format!(
+                    "completed  master: {}, latency: {}ms",
+                    index(master).unwrap_or_default(),
+                    latency.as_millis()
+                ),
+                RoundOutcome::TimedOut {
+                    master: Some(master),
+                } => format!("timed out  master: {}", index(master).unwrap_or_default()),
+                RoundOutcome::TimedOut { master: None } => {
+                    "timed out  no candidate block".to_string()
+                }
+            })
+            .collect();
+        Status {
+            // the round is for the next block of the tip.
+            height: observer
+                .rpc()
+                .getblockchaininfo()
+                .ok()
+                .map(|info| info.blocks + 1),
+            threshold: observer.threshold(),
+            master: observer.master().and_then(|m| index(&m)),
+            signatures: observer.signatures(),
+            signers,
+            recent_rounds,
+        }
+    }
+
+    /// Lines of the screen. They change only when the status changes, so that plain output
+    /// prints them only then.
+    fn render(&self) -> Vec<String> {
+        let or_none = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+        let mut lines = vec![
+            "tapyrus-signer monitor (Ctrl-C to exit)".to_string(),
+            format!(
+                "height: {}  master: {}  signatures: {}/{}",
+                or_none(self.height.map(|h| h.to_string())),
+                or_none(self.master.map(|m| m.to_string())),
+                self.signatures,
+                self.threshold
+            ),
+            String::new(),
+            "index  status   latency  public key".to_string(),
+        ];
+        for (index, signer) in self.signers.iter().enumerate() {
+            lines.push(format!(
+                "{:<5}  {:<7}  {:>7}  {}",
+                index,
+                if signer.online { "online" } else { "offline" },
+                or_none(signer.latency.map(|l| format!("{}ms", l.as_millis()))),
+                signer.pubkey
+            ));
+        }
+        lines.push(String::new());
+        lines.push("recent rounds:".to_string());
+        lines.extend(self.recent_rounds.iter().map(|r| format!("  {}", r)));
+        lines
+    }
+}
+
+/// Show the status of the federation, following the rounds as an observer. It redraws the
+/// screen on the terminal, otherwise prints the status whenever it changes.
+/// Public keys, threshold, RPC and redis connection are same options as `node`.
+///
+/// command example:
+/// ./target/debug/monitor -c signer_config.toml
+fn main() {
+    let matches = get_options()
+        .name("monitor")
+        .about("Show the status of the federation in the terminal.")
+        .after_help("EXAMPLE:\n    monitor -c signer_config.toml")
+        .get_matches();
+    let configs = CommandArgs::load(matches).unwrap();
+    let general_config = configs.general_config();
+    let signer_config = configs.signer_config();
+    let tty = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+    // logs would break the screen.
+    if !tty && !general_config.log_quiet() {
+        let log_level = general_config.log_level();
+        std::env::set_var("RUST_LOG", format!("tapyrus_signer={}", log_level));
+        env_logger::init();
+    }
+
+    let public_keys = signer_config.public_keys();
+    let redis_config = configs.redis_config();
+    let mut redis_manager = RedisManager::new(
+        redis_config.host().to_string(),
+        redis_config.port().to_string(),
+    );
+    redis_manager.auth = Some(MessageAuth {
+        private_key: None,
+        pubkey_list: public_keys.clone(),
+    });
+    redis_manager
+        .test_connection()
+        .expect("Failed to connect redis. Please confirm redis connection info");
+    let rpc_config = configs.rpc_config();
+    let mut rpc = Rpc::new(
+        format!("http://{}:{}", rpc_config.host(), rpc_config.port()),
+        rpc_config.user_name().map(str::to_string),
+        rpc_config.password().map(str::to_string),
+    );
+    rpc.set_timeout(Duration::from_secs(rpc_config.timeout()));
+    rpc.set_connect_timeout(Duration::from_secs(rpc_config.connect_timeout()));
+    rpc.test_connection()
+        .expect("RPC connect failed. Please confirm RPC connection info.");
+
+    let mut observer = Observer::new(
+        public_keys,
+        signer_config.threshold(),
+        rpc,
+        general_config.round_duration(),
+    );
+    observer.sighash_variant = general_config.sighash_variant().unwrap();
+
+    let (stop_sender, stop_receiver) = channel();
+    observer.stop_handler(stop_receiver);
+    let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+    std::thread::spawn(move || {
+        while !INTERRUPTED.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let _ = stop_sender.send(1);
+    });
+
+    let stdout = std::io::stdout();
+    if tty {
+        // alternate screen without cursor.
+        print!("\x1b[?1049h\x1b[?25l");
+    }
+    let mut last_render: Option<Instant> = None;
+    let mut last_lines = vec![];
+    observer.run_with(redis_manager, |observer| {
+        let now = Instant::now();
+        if let Some(last) = last_render {
+            if now.duration_since(last) < Duration::from_millis(RENDER_INTERVAL_MILLIS) {
+                return;
+            }
+        }
+        last_render = Some(now);
+        let lines = Status::new(observer, now).render();
+        let mut out = stdout.lock();
+        if tty {
+            let _ = writeln!(out, "\x1b[H\x1b[2J{}", lines.join("\n"));
+        } else if lines != last_lines {
+            let _ = writeln!(out, "{}\n", lines.join("\n"));
+        }
+        let _ = out.flush();
+        last_lines = lines;
+    });
+    if tty {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+#[test]
+fn test_render() {
+    use std::str::FromStr;
+
+    let pubkey =
+        PublicKey::from_str("03831a69b8009833ab5b0326012eaf489bfea35a7321b1ca15b11d88131423fafc")
+            .unwrap();
+    let status = Status {
+        height: Some(10),
+        threshold: 2,
+        master: Some(1),
+        signatures: 1,
+        signers: vec![
+            SignerStatus {
+                pubkey,
+                online: true,
+                latency: Some(Duration::from_millis(120)),
+            },
+            SignerStatus {
+                pubkey,
+                online: false,
+                latency: None,
+            },
+        ],
+        recent_rounds: vec!["timed out  no candidate block".to_string()],
+    };
+    assert_eq!(
+        status.render(),
+        vec![
+            "tapyrus-signer monitor (Ctrl-C to exit)",
+            "height: 10  master: 1  signatures: 1/2",
+            "",
+            "index  status   latency  public key",
+            "0      online     120ms  03831a69b8009833ab5b0326012eaf489bfea35a7321b1ca15b11d88131423fafc",
+            "1      offline        -  03831a69b8009833ab5b0326012eaf489bfea35a7321b1ca15b11d88131423fafc",
+            "",
+            "recent rounds:",
+            "  timed out  no candidate block",
+        ]
+    );
+}
@@ -9,12 +9,29 @@ extern crate log;
 extern crate redis;
 extern crate tapyrus_signer;
 
-use bitcoin::{PrivateKey, PublicKey};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bitcoin::{Network, PrivateKey, PublicKey};
+use redis::ControlFlow;
 
 use tapyrus_signer::command_args::{CommandArgs, RedisConfig, RpcConfig};
-use tapyrus_signer::net::{ConnectionManager, RedisManager};
-use tapyrus_signer::rpc::Rpc;
-use tapyrus_signer::signer_node::{NodeParameters, SignerNode};
+use tapyrus_signer::key_store::{EphemeralKeyStore, FileKeyStore, KeyStore};
+use tapyrus_signer::net::{ConnectionManager, Message, MessageAuth, RedisManager};
+use tapyrus_signer::observer::Observer;
+use tapyrus_signer::rpc::{GetBlockchainInfoResult, Rpc, TapyrusApi, TlsConfig};
+use tapyrus_signer::signer_node::{
+    BlockToSubmit, NodeOutput, NodeParameters, SignerNode, POLL_INTERVAL_MIN_MILLIS,
+    ROUND_TIMELIMIT_DELTA,
+};
+
+/// Max time the node waits for a message before it checks the other inputs.
+const LOOP_INTERVAL: Duration = Duration::from_millis(300);
+/// Interval of checking whether Tapyrus Core finished IBD.
+const IBD_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
 /// This command is for launch tapyrus-signer-node.
 /// command example:
@@ -34,28 +51,383 @@ fn main() {
         env_logger::init();
     }
 
+    let startup_timed_out = Arc::new(AtomicBool::new(false));
+    let startup = match general_config.startup_timeout() {
+        0 => None,
+        secs => {
+            let timed_out = startup_timed_out.clone();
+            Some(startup_watchdog(
+                std::time::Duration::from_secs(secs),
+                move || {
+                    log::error!(
+                        "Startup didn't finish in {} secs. Please confirm RPC and redis connection info.",
+                        secs
+                    );
+                    timed_out.store(true, Ordering::SeqCst);
+                },
+            ))
+        }
+    };
+
     let signer_config = configs.signer_config();
-    validate_options(
-        &signer_config.public_keys(),
-        &signer_config.private_key(),
-        &signer_config.threshold(),
-    )
-    .unwrap();
+    if general_config.observer() {
+        let public_keys = signer_config.public_keys();
+        validate_threshold(&public_keys, &signer_config.threshold()).unwrap();
+        let auth = MessageAuth {
+            private_key: None,
+            pubkey_list: public_keys.clone(),
+        };
+        let con = connect_signer_network(configs.redis_config(), auth, round_duration);
+        check_startup(&startup_timed_out);
+        let rpc = connect_rpc(configs.rpc_config());
+        let mut observer =
+            Observer::new(public_keys, signer_config.threshold(), rpc, round_duration);
+        observer.sighash_variant = general_config.sighash_variant().unwrap();
+        finish_startup(startup, &startup_timed_out);
+        observer.run(con);
+        return;
+    }
+
+    let public_keys = signer_config.public_keys();
+    let key_store: Box<dyn KeyStore> = if general_config.ephemeral_key() {
+        let private_key =
+            ephemeral_key(general_config.network().unwrap_or(Network::Testnet)).unwrap();
+        println!(
+            "ephemeral public key: {}",
+            private_key.public_key(&secp256k1::Secp256k1::new())
+        );
+        Box::new(EphemeralKeyStore::new(private_key))
+    } else {
+        Box::new(FileKeyStore::new(signer_config.private_keys()))
+    };
+    let private_key = key_store.private_key(&public_keys).unwrap();
+    validate_options(&public_keys, &private_key, &signer_config.threshold()).unwrap();
+    if let Some(warning) = fault_tolerance_warning(&public_keys, signer_config.threshold()) {
+        if !general_config.allow_no_fault_tolerance() {
+            log::warn!("{}", warning);
+        }
+    }
+    match general_config.network() {
+        Some(network) => validate_network(&private_key, network).unwrap(),
+        None => {
+            log::warn!("--network is not specified. Network of the private key is not checked.")
+        }
+    }
 
-    let con = connect_signer_network(configs.redis_config());
+    let auth = MessageAuth {
+        private_key: Some(private_key),
+        pubkey_list: public_keys.clone(),
+    };
+    let con = connect_signer_network(configs.redis_config(), auth, round_duration);
+    check_startup(&startup_timed_out);
     let rpc = connect_rpc(configs.rpc_config());
 
-    let params = NodeParameters::new(
-        signer_config.public_keys(),
-        signer_config.private_key(),
+    let mut params = NodeParameters::new(
+        public_keys.clone(),
+        private_key,
         signer_config.threshold(),
-        rpc,
         is_master,
         round_duration,
-        general_config.skip_waiting_ibd(),
     );
-    let node = &mut SignerNode::new(con, params);
-    node.start();
+    params.max_round_attempts = general_config.max_round_attempts();
+    params.max_missed_rounds = general_config.max_missed_rounds();
+    params.watchdog_rounds = general_config.watchdog_rounds();
+    validate_fixed_master(general_config.fixed_master(), &public_keys).unwrap();
+    params.fixed_master = general_config.fixed_master();
+    params.max_concurrent_rounds = general_config.max_concurrent_rounds();
+    if let Some(quorum) = general_config.collection_quorum() {
+        validate_collection_quorum(quorum, signer_config.threshold(), &public_keys).unwrap();
+        params.collection_quorum = quorum;
+    }
+    params.late_signature_grace =
+        std::time::Duration::from_millis(general_config.late_signature_grace());
+    params.readiness_window = std::time::Duration::from_millis(general_config.readiness_window());
+    params.detect_equivocation = general_config.detect_equivocation();
+    params.policy = general_config.block_policy();
+    params.timeout_jitter = std::time::Duration::from_millis(general_config.timeout_jitter());
+    validate_poll_interval(general_config.poll_interval()).unwrap();
+    params.poll_interval = std::time::Duration::from_millis(general_config.poll_interval());
+    params.submit_confirmation_timeout =
+        std::time::Duration::from_millis(general_config.submit_confirmation_timeout());
+    params.metrics_dump_path = general_config.dump_metrics();
+    params.events_path = general_config.events_file();
+    params.last_signed_height_path = general_config.last_signed_height_file();
+    params.sighash_variant = general_config.sighash_variant().unwrap();
+    params.election = general_config.leader_election().unwrap();
+    params.sign_once = general_config.sign_once();
+    let node = &mut SignerNode::new(params);
+    if let Some(path) = general_config.admin_socket() {
+        if let Err(e) = node.open_admin_socket(&path) {
+            log::error!("Failed to open admin socket {:?}: {:?}", path, e);
+        }
+    }
+    let block_hashes = match general_config.zmq_endpoint() {
+        Some(endpoint) => {
+            let (sender, receiver) = channel();
+            subscribe_block_hashes(endpoint, sender).unwrap();
+            Some(receiver)
+        }
+        None => None,
+    };
+    if let Some(addr) = general_config.statsd_addr() {
+        tapyrus_signer::statsd::StatsdExporter::new(addr, node.metrics())
+            .unwrap()
+            .spawn(std::time::Duration::from_millis(
+                general_config.statsd_interval(),
+            ));
+    }
+    if let Some(addr) = general_config.metrics_addr() {
+        tapyrus_signer::prometheus::MetricsListener::bind(addr, node.metrics())
+            .unwrap()
+            .spawn();
+    }
+    finish_startup(startup, &startup_timed_out);
+    let chain = if general_config.skip_waiting_ibd() {
+        rpc.getblockchaininfo()
+    } else {
+        Ok(wait_for_ibd_finish(&rpc, IBD_CHECK_INTERVAL))
+    };
+    run_node(
+        node,
+        con,
+        &rpc,
+        chain,
+        block_hashes,
+        general_config.sign_once(),
+    );
+    if general_config.sign_once() && !node.contributed() {
+        std::process::exit(1);
+    }
+}
+
+/// Call `on_timeout` unless the startup is finished by `finish_startup` within `timeout`.
+/// It bounds the startup when a dependency doesn't respond.
+fn startup_watchdog<F>(timeout: std::time::Duration, on_timeout: F) -> std::sync::mpsc::Sender<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        if let Err(std::sync::mpsc::RecvTimeoutError::Timeout) = receiver.recv_timeout(timeout) {
+            on_timeout();
+        }
+    });
+    sender
+}
+
+/// Exit with an error code if the startup watchdog has timed out. Every startup step is bounded
+/// by its own connection timeout, so the node exits at the next check after the watchdog fires.
+fn check_startup(timed_out: &AtomicBool) {
+    if timed_out.load(Ordering::SeqCst) {
+        std::process::exit(1);
+    }
+}
+
+fn finish_startup(startup: Option<std::sync::mpsc::Sender<()>>, timed_out: &AtomicBool) {
+    check_startup(timed_out);
+    if let Some(sender) = startup {
+        let _ = sender.send(());
+    }
+}
+
+/// Signer Node waits for connected Tapyrus Core Node complete IBD(Initial Block Download).
+fn wait_for_ibd_finish<T: TapyrusApi>(rpc: &T, interval: Duration) -> GetBlockchainInfoResult {
+    log::info!("Waiting finish Initial Block Download ...");
+    log::info!("If you start right away, you can set `--skip-waiting-ibd` option. ");
+
+    loop {
+        match rpc.getblockchaininfo().expect("RPC connection failed") {
+            info @ GetBlockchainInfoResult {
+                initialblockdownload: false,
+                ..
+            } => {
+                return info;
+            }
+            GetBlockchainInfoResult {
+                initialblockdownload: true,
+                blocks: height,
+                bestblockhash: hash,
+                ..
+            } => {
+                log::info!("Waiting for finish Initial Block Download. Current block height: {}, current best hash: {}", height, hash);
+            }
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+/// Drive the node with the messages from the signer network, the blocks notified by Tapyrus
+/// Core and the clock. It returns only when the node finished with `--sign-once`.
+fn run_node<C: ConnectionManager, T: TapyrusApi>(
+    node: &mut SignerNode,
+    mut con: C,
+    rpc: &T,
+    chain: Result<GetBlockchainInfoResult, tapyrus_signer::errors::Error>,
+    block_hashes: Option<Receiver<String>>,
+    sign_once: bool,
+) {
+    let (sender, receiver) = channel();
+    let closure = move |message: Message| match sender.send(message) {
+        Ok(_) => ControlFlow::Continue,
+        Err(error) => {
+            log::warn!("Happened error!: {:?}", error);
+            ControlFlow::Break(())
+        }
+    };
+
+    // redisとの通信を行うthreadを開始
+    let _handler = con.start(closure);
+    let output = node.chain_info(chain, Instant::now());
+    execute(node, &con, rpc, output);
+    let output = node.start(Instant::now());
+    let mut next_deadline = execute(node, &con, rpc, output);
+
+    // get error_handler that is for catch error within connection_manager.
+    let connection_manager_error_handler = con.error_handler();
+    loop {
+        if sign_once && node.sign_once_finished(Instant::now()) {
+            if node.contributed() {
+                log::info!("Signed the candidate block. Exit.");
+            } else {
+                log::error!("No valid candidate block arrived in the round. Exit.");
+            }
+            break;
+        }
+        node.serve_admin_requests();
+        // Should be panic, if happened error in connection_manager.
+        match connection_manager_error_handler {
+            Some(ref receiver) => match receiver.try_recv() {
+                Ok(e) => {
+                    panic!("{}", e.to_string());
+                }
+                Err(_e) => {}
+            },
+            None => {
+                log::warn!("Failed to get error_handler of connection_manager!");
+            }
+        }
+        if let Some(ref block_hashes) = block_hashes {
+            for block_hash in block_hashes.try_iter() {
+                let output = node.block_notified(block_hash, Instant::now());
+                next_deadline = execute(node, &con, rpc, output);
+            }
+        }
+
+        // Receiving message until the node has something to do on tick.
+        let timeout = match next_deadline {
+            Some(deadline) => std::cmp::min(
+                deadline.saturating_duration_since(Instant::now()),
+                LOOP_INTERVAL,
+            ),
+            None => LOOP_INTERVAL,
+        };
+        match receiver.recv_timeout(timeout) {
+            Ok(message) => {
+                let output = node.process_message(message, Instant::now());
+                next_deadline = execute(node, &con, rpc, output);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // the connection manager stopped, so no message arrives anymore.
+            Err(RecvTimeoutError::Disconnected) => {
+                log::error!("Connection to Redis is closed. Exit.");
+                std::process::exit(1);
+            }
+        }
+
+        // Process for exceed time limit of Round.
+        let now = Instant::now();
+        let due = match next_deadline {
+            Some(deadline) => now >= deadline,
+            None => true,
+        };
+        if due {
+            let output = node.tick(now);
+            next_deadline = execute(node, &con, rpc, output);
+        }
+    }
+}
+
+/// Broadcast the messages in `output` and do its requests to Tapyrus Core. Results of the
+/// requests are passed to the node, and their outputs are executed too. Returns the deadline of
+/// the next tick.
+fn execute<C: ConnectionManager, T: TapyrusApi>(
+    node: &mut SignerNode,
+    con: &C,
+    rpc: &T,
+    output: NodeOutput,
+) -> Option<Instant> {
+    let mut outputs = VecDeque::new();
+    outputs.push_back(output);
+    let mut next_deadline = None;
+    while let Some(output) = outputs.pop_front() {
+        for message in output.messages {
+            con.broadcast_message(message);
+        }
+        if output.fetch_chain_info {
+            let info = rpc.getblockchaininfo();
+            outputs.push_back(node.chain_info(info, Instant::now()));
+        }
+        if let Some(address) = output.fetch_candidate_block {
+            let block = rpc.getnewblock(&address);
+            outputs.push_back(node.candidate_block(block, Instant::now()));
+        }
+        if let Some(block) = output.block_to_test {
+            let result = rpc.testproposedblock(&block);
+            outputs.push_back(node.block_tested(result, Instant::now()));
+        }
+        if let Some(BlockToSubmit { block, signatures }) = output.block_to_submit {
+            // call combineblocksigs and submitblock
+            let result = rpc
+                .combineblocksigs(&block, &signatures)
+                .and_then(|completed_block| {
+                    rpc.submitblock(&completed_block)?;
+                    Ok(completed_block)
+                });
+            outputs.push_back(node.block_submitted(result, Instant::now()));
+        }
+        next_deadline = output.next_deadline;
+    }
+    next_deadline
+}
+
+/// Generate a private key which is used only while the node is running.
+#[cfg(not(feature = "production"))]
+fn ephemeral_key(network: Network) -> Result<PrivateKey, tapyrus_signer::errors::Error> {
+    tapyrus_signer::sign::generate_private_key(network)
+}
+
+/// A federation must not run with a key which is thrown away when the node stops.
+#[cfg(feature = "production")]
+fn ephemeral_key(_network: Network) -> Result<PrivateKey, tapyrus_signer::errors::Error> {
+    Err(tapyrus_signer::errors::Error::InvalidArgs(
+        "--ephemeral-key is not available in the build with production feature.".to_string(),
+    ))
+}
+
+fn validate_threshold(
+    public_keys: &[PublicKey],
+    threshold: &u8,
+) -> Result<(), tapyrus_signer::errors::Error> {
+    if *threshold == 0 || public_keys.len() < *threshold as usize {
+        return Err(tapyrus_signer::errors::Error::InvalidThreshold {
+            threshold: *threshold,
+            signers: public_keys.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Warning for the federation which can't produce blocks when any one signer stops.
+fn fault_tolerance_warning(public_keys: &[PublicKey], threshold: u8) -> Option<String> {
+    if public_keys.len() == threshold as usize {
+        Some(format!(
+            "Threshold equals the number of signers. Losing any one signer halts block production. Set --allow-no-fault-tolerance if it is intended. threshold: {}, number of public keys: {}",
+            threshold,
+            public_keys.len()
+        ))
+    } else {
+        None
+    }
 }
 
 fn validate_options(
@@ -63,14 +435,7 @@ fn validate_options(
     private_key: &PrivateKey,
     threshold: &u8,
 ) -> Result<(), tapyrus_signer::errors::Error> {
-    if public_keys.len() < *threshold as usize {
-        let error_msg = format!(
-            "Not enough number of public keys. publicKeys.len: {}, threshold: {}",
-            public_keys.len(),
-            threshold
-        );
-        return Err(tapyrus_signer::errors::Error::InvalidArgs(error_msg));
-    }
+    validate_threshold(public_keys, threshold)?;
     let pubkey_from_private = private_key.public_key(&secp256k1::Secp256k1::new());
     match public_keys.iter().find(|&&p| p == pubkey_from_private) {
         Some(_) => (),
@@ -83,21 +448,161 @@ fn validate_options(
     Ok(())
 }
 
+fn validate_fixed_master(
+    fixed_master: Option<usize>,
+    public_keys: &[PublicKey],
+) -> Result<(), tapyrus_signer::errors::Error> {
+    match fixed_master {
+        Some(index) if index >= public_keys.len() => {
+            let error_msg = format!(
+                "Fixed master is not signer index. fixed_master: {}, number of public keys: {}",
+                index,
+                public_keys.len()
+            );
+            Err(tapyrus_signer::errors::Error::InvalidArgs(error_msg))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_collection_quorum(
+    quorum: u8,
+    threshold: u8,
+    public_keys: &[PublicKey],
+) -> Result<(), tapyrus_signer::errors::Error> {
+    if quorum < threshold || quorum as usize > public_keys.len() {
+        let error_msg = format!(
+            "Collection quorum must be threshold to the number of public keys. collection_quorum: {}, threshold: {}, number of public keys: {}",
+            quorum,
+            threshold,
+            public_keys.len()
+        );
+        return Err(tapyrus_signer::errors::Error::InvalidArgs(error_msg));
+    }
+    Ok(())
+}
+
+fn validate_poll_interval(poll_interval: u64) -> Result<(), tapyrus_signer::errors::Error> {
+    if poll_interval < POLL_INTERVAL_MIN_MILLIS {
+        let error_msg = format!(
+            "Poll interval is too short. poll_interval: {}, minimum: {}",
+            poll_interval, POLL_INTERVAL_MIN_MILLIS
+        );
+        return Err(tapyrus_signer::errors::Error::InvalidArgs(error_msg));
+    }
+    Ok(())
+}
+
+fn validate_network(
+    private_key: &PrivateKey,
+    expected: Network,
+) -> Result<(), tapyrus_signer::errors::Error> {
+    if private_key.network != expected {
+        return Err(tapyrus_signer::errors::Error::NetworkMismatch {
+            expected,
+            found: private_key.network,
+        });
+    }
+    Ok(())
+}
+
 fn connect_rpc(rpc_config: RpcConfig) -> Rpc {
-    let url = format!("http://{}:{}", rpc_config.host(), rpc_config.port());
+    let scheme = if rpc_config.tls() { "https" } else { "http" };
+    let url = format!("{}://{}:{}", scheme, rpc_config.host(), rpc_config.port());
     let user = rpc_config.user_name().map(str::to_string);
     let pass = rpc_config.password().map(str::to_string);
-    let rpc = tapyrus_signer::rpc::Rpc::new(url.clone(), user.clone(), pass);
+    let mut rpc = if rpc_config.tls() {
+        new_tls_rpc(url.clone(), user.clone(), pass, &rpc_config.tls_config()).unwrap()
+    } else {
+        tapyrus_signer::rpc::Rpc::new(url.clone(), user.clone(), pass)
+    };
+    rpc.set_timeout(std::time::Duration::from_secs(rpc_config.timeout()));
+    rpc.set_connect_timeout(std::time::Duration::from_secs(rpc_config.connect_timeout()));
+    rpc.set_max_response_size(rpc_config.max_response_size());
+    rpc.set_retry(
+        rpc_config.retries(),
+        std::time::Duration::from_millis(rpc_config.retry_backoff()),
+    );
     rpc.test_connection().expect(&format!(
         "RPC connect failed. Please confirm RPC connection info. url: {}, user: '{}' ,",
         url,
         user.unwrap_or("".to_string())
     ));
+    match rpc.verify_core_version(rpc_config.min_core_version()) {
+        Err(tapyrus_signer::errors::Error::UnsupportedCoreVersion { version, minimum }) => panic!(
+            "Tapyrus Core version {} is not supported. Minimum version is {}.",
+            version, minimum
+        ),
+        r => r.expect("Failed to get Tapyrus Core version."),
+    }
+    if rpc_config.verify_permissions() {
+        rpc.verify_permissions()
+            .expect("RPC user doesn't have permission which the signer needs.");
+    }
     rpc
 }
 
-fn connect_signer_network(rc: RedisConfig) -> impl ConnectionManager {
-    let redis_manager = RedisManager::new(rc.host().to_string(), rc.port().to_string());
+/// Messages are for the round which they were sent in, so they are stale after the length of a
+/// round unless the TTL is specified.
+fn message_ttl(message_ttl: Option<u64>, round_duration: u64) -> Option<std::time::Duration> {
+    match message_ttl {
+        Some(0) => None,
+        Some(millis) => Some(std::time::Duration::from_millis(millis)),
+        None => Some(std::time::Duration::from_secs(
+            round_duration + ROUND_TIMELIMIT_DELTA,
+        )),
+    }
+}
+
+#[cfg(feature = "zmq")]
+fn subscribe_block_hashes(
+    endpoint: &str,
+    sender: std::sync::mpsc::Sender<String>,
+) -> Result<(), tapyrus_signer::errors::Error> {
+    tapyrus_signer::zmq::subscribe_block_hashes(endpoint, sender).map(|_| ())
+}
+
+#[cfg(not(feature = "zmq"))]
+fn subscribe_block_hashes(
+    _endpoint: &str,
+    _sender: std::sync::mpsc::Sender<String>,
+) -> Result<(), tapyrus_signer::errors::Error> {
+    Err(tapyrus_signer::errors::Error::InvalidArgs(
+        "--zmq-endpoint is not available in the build without `zmq` feature.".to_string(),
+    ))
+}
+
+#[cfg(feature = "tls")]
+fn new_tls_rpc(
+    url: String,
+    user: Option<String>,
+    pass: Option<String>,
+    tls: &TlsConfig,
+) -> Result<Rpc, tapyrus_signer::errors::Error> {
+    Rpc::new_with_tls(url, user, pass, tls)
+}
+
+#[cfg(not(feature = "tls"))]
+fn new_tls_rpc(
+    _url: String,
+    _user: Option<String>,
+    _pass: Option<String>,
+    _tls: &TlsConfig,
+) -> Result<Rpc, tapyrus_signer::errors::Error> {
+    Err(tapyrus_signer::errors::Error::InvalidArgs(
+        "TLS for RPC connection is not supported in the build without `tls` feature.".to_string(),
+    ))
+}
+
+fn connect_signer_network(
+    rc: RedisConfig,
+    auth: MessageAuth,
+    round_duration: u64,
+) -> impl ConnectionManager {
+    let mut redis_manager = RedisManager::new(rc.host().to_string(), rc.port().to_string());
+    redis_manager.auth = Some(auth);
+    redis_manager.message_ttl = message_ttl(rc.message_ttl(), round_duration);
+    redis_manager.accept_untimed_messages = rc.accept_untimed_messages();
     redis_manager
         .test_connection()
         .expect("Failed to connect redis. Please confirm redis connection info");
@@ -105,7 +610,7 @@ fn connect_signer_network(rc: RedisConfig) -> impl ConnectionManager {
 }
 
 #[test]
-#[should_panic(expected = "Not enough number of public keys. publicKeys.len:")]
+#[should_panic(expected = "InvalidThreshold { threshold: 2, signers: 1 }")]
 fn test_validate_options_less_threshold() {
     use std::str::FromStr;
 
@@ -120,6 +625,41 @@ fn test_validate_options_less_threshold() {
     validate_options(&pubkey_list, &private_key, &threshold).unwrap();
 }
 
+#[test]
+fn test_fault_tolerance_warning() {
+    use tapyrus_signer::test_helper::TestKeys;
+
+    let pubkey_list = TestKeys::new().pubkeys();
+    assert!(fault_tolerance_warning(&pubkey_list, 5).is_some());
+    assert!(fault_tolerance_warning(&pubkey_list[..3], 3).is_some());
+    assert!(fault_tolerance_warning(&pubkey_list, 4).is_none());
+    assert!(fault_tolerance_warning(&pubkey_list, 3).is_none());
+    assert!(fault_tolerance_warning(&pubkey_list, 1).is_none());
+}
+
+#[test]
+fn test_validate_threshold() {
+    use tapyrus_signer::test_helper::TestKeys;
+
+    let pubkey_list = TestKeys::new().pubkeys();
+    assert!(validate_threshold(&pubkey_list, &1).is_ok());
+    assert!(validate_threshold(&pubkey_list, &5).is_ok());
+    assert!(matches!(
+        validate_threshold(&pubkey_list, &0),
+        Err(tapyrus_signer::errors::Error::InvalidThreshold {
+            threshold: 0,
+            signers: 5
+        })
+    ));
+    assert!(matches!(
+        validate_threshold(&pubkey_list, &6),
+        Err(tapyrus_signer::errors::Error::InvalidThreshold {
+            threshold: 6,
+            signers: 5
+        })
+    ));
+}
+
 #[test]
 #[should_panic(expected = "Private key is not pair of any one of Public key list.")]
 fn test_validate_options_no_pair() {
@@ -138,6 +678,56 @@ fn test_validate_options_no_pair() {
     validate_options(&pubkey_list, &private_key, &threshold).unwrap();
 }
 
+#[test]
+#[should_panic(expected = "NetworkMismatch { expected: testnet, found: bitcoin }")]
+fn test_validate_network_mainnet_key_for_testnet() {
+    let private_key =
+        PrivateKey::from_wif("L4Bw5GTJXL7Nd5wjprXim2sMpNgTSieZ14FCaHax7zzRnHbx19sc").unwrap();
+
+    validate_network(&private_key, Network::Testnet).unwrap();
+}
+
+#[test]
+#[should_panic(
+    expected = "Fixed master is not signer index. fixed_master: 5, number of public keys: 5"
+)]
+fn test_validate_fixed_master() {
+    let public_keys = tapyrus_signer::test_helper::TestKeys::new().pubkeys();
+    assert!(validate_fixed_master(None, &public_keys).is_ok());
+    assert!(validate_fixed_master(Some(4), &public_keys).is_ok());
+    validate_fixed_master(Some(5), &public_keys).unwrap();
+}
+
+#[test]
+#[should_panic(
+    expected = "Collection quorum must be threshold to the number of public keys. collection_quorum: 2, threshold: 3, number of public keys: 5"
+)]
+fn test_validate_collection_quorum() {
+    let public_keys = tapyrus_signer::test_helper::TestKeys::new().pubkeys();
+    assert!(validate_collection_quorum(3, 3, &public_keys).is_ok());
+    assert!(validate_collection_quorum(5, 3, &public_keys).is_ok());
+    assert!(validate_collection_quorum(6, 3, &public_keys).is_err());
+    validate_collection_quorum(2, 3, &public_keys).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "Poll interval is too short. poll_interval: 10, minimum: 100")]
+fn test_validate_poll_interval() {
+    assert!(validate_poll_interval(100).is_ok());
+    validate_poll_interval(10).unwrap();
+}
+
+#[test]
+fn test_validate_network() {
+    let private_key =
+        PrivateKey::from_wif("cUwpWhH9CbYwjUWzfz1UVaSjSQm9ALXWRqeFFiZKnn8cV6wqNXQA").unwrap();
+    assert!(validate_network(&private_key, Network::Testnet).is_ok());
+
+    let private_key =
+        PrivateKey::from_wif("L4Bw5GTJXL7Nd5wjprXim2sMpNgTSieZ14FCaHax7zzRnHbx19sc").unwrap();
+    assert!(validate_network(&private_key, Network::Bitcoin).is_ok());
+}
+
 #[test]
 #[should_panic(
     expected = "RPC connect failed. Please confirm RPC connection info. url: http://127.0.0.1:9999, user: '' "
@@ -150,6 +740,17 @@ fn test_connect_rpc() {
             port: Some("9999"),
             username: None,
             password: None,
+            timeout: None,
+            connect_timeout: None,
+            max_response_size: None,
+            retries: None,
+            retry_backoff: None,
+            tls: false,
+            ca_cert: None,
+            client_cert: None,
+            client_cert_pass: None,
+            verify_permissions: false,
+            min_core_version: None,
         },
         toml_config: None,
     };
@@ -157,6 +758,45 @@ fn test_connect_rpc() {
     connect_rpc(config);
 }
 
+#[test]
+fn test_startup_watchdog() {
+    use std::time::{Duration, Instant};
+
+    // RPC server which accepts connections but never responds.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (timeout_sender, timeout_receiver) = std::sync::mpsc::channel();
+    let started = Instant::now();
+    let startup = startup_watchdog(Duration::from_secs(1), move || {
+        timeout_sender.send(()).unwrap();
+    });
+    std::thread::spawn(move || {
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_timeout(Duration::from_secs(60));
+        let _ = rpc.test_connection();
+        finish_startup(Some(startup), &AtomicBool::new(false));
+    });
+    timeout_receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("Startup should time out.");
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    drop(listener);
+}
+
+#[test]
+fn test_startup_watchdog_finished() {
+    use std::time::Duration;
+
+    let (timeout_sender, timeout_receiver) = std::sync::mpsc::channel();
+    let startup = startup_watchdog(Duration::from_millis(200), move || {
+        timeout_sender.send(()).unwrap();
+    });
+    finish_startup(Some(startup), &AtomicBool::new(false));
+    assert!(timeout_receiver
+        .recv_timeout(Duration::from_millis(500))
+        .is_err());
+}
+
 #[test]
 #[should_panic(expected = "Failed to connect redis. Please confirm redis connection info")]
 fn test_connect_signer_network() {
@@ -166,9 +806,110 @@ fn test_connect_signer_network() {
         command_args: RedisCommandArgs {
             host: Some("127.0.0.1"),
             port: Some("9999"),
+            message_ttl: None,
+            accept_untimed_messages: false,
         },
         toml_config: None,
     };
 
-    connect_signer_network(config);
+    let auth = MessageAuth {
+        private_key: None,
+        pubkey_list: vec![],
+    };
+    connect_signer_network(config, auth, 60);
+}
+
+#[test]
+fn test_message_ttl() {
+    use std::time::Duration;
+
+    assert_eq!(message_ttl(None, 60), Some(Duration::from_secs(65)));
+    assert_eq!(
+        message_ttl(Some(3000), 60),
+        Some(Duration::from_millis(3000))
+    );
+    assert_eq!(message_ttl(Some(0), 60), None);
+}
+
+#[test]
+#[cfg(not(feature = "production"))]
+fn test_ephemeral_key() {
+    let private_key = ephemeral_key(Network::Regtest).unwrap();
+    assert_eq!(private_key.network, Network::Regtest);
+    assert!(private_key.compressed);
+    assert_ne!(private_key, ephemeral_key(Network::Regtest).unwrap());
+}
+
+#[test]
+#[cfg(feature = "production")]
+#[should_panic(expected = "--ephemeral-key is not available in the build with production feature.")]
+fn test_ephemeral_key_in_production() {
+    ephemeral_key(Network::Regtest).unwrap();
+}
+
+#[test]
+fn test_wait_for_ibd_finish() {
+    use bitcoin::Address;
+    use std::cell::Cell;
+    use tapyrus_signer::blockdata::Block;
+    use tapyrus_signer::errors::Error;
+
+    struct MockRpc {
+        pub results: [GetBlockchainInfoResult; 2],
+        pub call_count: Cell<usize>,
+    }
+
+    impl TapyrusApi for MockRpc {
+        fn getnewblock(&self, _address: &Address) -> Result<Block, Error> {
+            unimplemented!()
+        }
+        fn testproposedblock(&self, _block: &Block) -> Result<(), Error> {
+            unimplemented!()
+        }
+        fn combineblocksigs(
+            &self,
+            _block: &Block,
+            _signatures: &Vec<secp256k1::Signature>,
+        ) -> Result<Block, Error> {
+            unimplemented!()
+        }
+        fn submitblock(&self, _block: &Block) -> Result<(), Error> {
+            unimplemented!()
+        }
+
+        fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error> {
+            let result = self.results[self.call_count.get()].clone();
+
+            self.call_count.set(self.call_count.get() + 1);
+
+            Ok(result)
+        }
+    }
+
+    let json = serde_json::from_str("{\"chain\": \"test\", \"blocks\": 26826, \"headers\": 26826, \"bestblockhash\": \"7303687fb5d80781bd9fece466e76d97a94613d409d127030ff7f34081a899f7\", \"mediantime\": 1568103315, \"verificationprogress\": 1, \"initialblockdownload\": false, \"size_on_disk\": 11669126,  \"pruned\": false,  \"bip9_softforks\": {    \"csv\": {      \"status\": \"failed\",      \"startTime\": 1456790400, \"timeout\": 1493596800, \"since\": 2016 }, \"segwit\": { \"status\": \"failed\", \"startTime\": 1462060800, \"timeout\": 1493596800, \"since\": 2016 }},  \"warnings\": \"\"}").unwrap();
+    let mut result1 = serde_json::from_value::<GetBlockchainInfoResult>(json).unwrap();
+    result1.initialblockdownload = true;
+    let mut result2 = result1.clone();
+    result2.initialblockdownload = false;
+
+    let rpc = MockRpc {
+        results: [result1, result2.clone()],
+        call_count: Cell::new(0),
+    };
+
+    let info = wait_for_ibd_finish(&rpc, Duration::from_millis(1));
+
+    assert_eq!(rpc.call_count.get(), 2);
+    assert_eq!(info.bestblockhash, result2.bestblockhash);
+}
+
+#[test]
+#[cfg(not(feature = "tls"))]
+fn test_new_tls_rpc_without_tls_feature() {
+    let url = "https://localhost:2377".to_string();
+    match new_tls_rpc(url, None, None, &TlsConfig::default()) {
+        Err(tapyrus_signer::errors::Error::InvalidArgs(_)) => {}
+        Err(e) => panic!("Should be InvalidArgs, but {:?}", e),
+        Ok(_) => panic!("Should be InvalidArgs, but the client is created."),
+    }
 }
@@ -0,0 +1,171 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use bitcoin::{Network, PrivateKey};
+use clap::{App, Arg};
+use rand::Rng;
+use redis::ControlFlow;
+
+use tapyrus_signer::errors::Error;
+use tapyrus_signer::net::{ConnectionManager, Message, MessageType, RedisManager, SignerID};
+
+const OPTION_NAME_COUNT: &str = "count";
+const OPTION_NAME_BROKER_URL: &str = "broker_url";
+/// Channel for ping messages, not to disturb the signer network.
+const PING_CHANNEL: &str = "tapyrus-signer-ping";
+/// Time limit for receiving each ping message.
+const PING_TIMEOUT_SECS: u64 = 5;
+
+/// Measure round-trip latency of redis with the same connection manager as the node. Each
+/// latency includes the wait before publishing which the node also has, so it can be compared
+/// with the round time limit directly.
+///
+/// command example:
+/// ./target/debug/ping_broker --broker-url redis://127.0.0.1:6379 --count 10
+fn main() {
+    let matches = App::new("ping_broker")
+        .about("Measure round-trip latency of the redis server of the signer network.")
+        .after_help("EXAMPLE:\n    ping_broker --broker-url redis://127.0.0.1:6379 --count 10")
+        .arg(
+            Arg::with_name(OPTION_NAME_COUNT)
+                .long("count")
+                .takes_value(true)
+                .value_name("NUM")
+                .default_value("10")
+                .help("Number of messages to publish."),
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_BROKER_URL)
+                .long("broker-url")
+                .takes_value(true)
+                .value_name("URL")
+                .default_value("redis://127.0.0.1:6379")
+                .help("Redis url, same server as the node connects to."),
+        )
+        .get_matches();
+
+    let count: usize = matches
+        .value_of(OPTION_NAME_COUNT)
+        .unwrap()
+        .parse()
+        .expect("count must be number.");
+    let url = matches.value_of(OPTION_NAME_BROKER_URL).unwrap();
+
+    match ping(url, count) {
+        Ok(latencies) => {
+            println!("received {} of {} messages.", latencies.len(), count);
+            if let Some((min, median, max)) = summary(latencies) {
+                println!(
+                    "latency(msec) min: {}, median: {}, max: {}",
+                    min.as_millis(),
+                    median.as_millis(),
+                    max.as_millis()
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to the broker {}: {}", url, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Publish `count` messages and returns the latencies of the received messages.
+fn ping(url: &str, count: usize) -> Result<Vec<Duration>, Error> {
+    let mut manager = RedisManager::open(url)?;
+    manager.test_connection()?;
+    manager.channel = PING_CHANNEL.to_string();
+    let sender_id = random_signer_id();
+
+    let (sender, receiver) = channel();
+    manager.start(move |message: Message| {
+        if message.sender_id != sender_id {
+            return ControlFlow::Continue;
+        }
+        match sender.send(Instant::now()) {
+            Ok(_) => ControlFlow::Continue,
+            Err(_) => ControlFlow::Break(()),
+        }
+    });
+
+    let mut latencies = Vec::with_capacity(count);
+    for _ in 0..count {
+        let sent_at = Instant::now();
+        manager.broadcast_message(Message {
+            message_type: MessageType::Roundfailure,
+            sender_id,
+            // signers drop it, because it isn't for any round.
+            height: 0,
+            round: 0,
+        });
+        match receiver.recv_timeout(Duration::from_secs(PING_TIMEOUT_SECS)) {
+            Ok(received_at) => latencies.push(received_at - sent_at),
+            Err(RecvTimeoutError::Timeout) => println!("message is lost."),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(latencies)
+}
+
+/// Sender of ping messages. It isn't any signer of the signer network.
+fn random_signer_id() -> SignerID {
+    let secp = secp256k1::Secp256k1::signing_only();
+    loop {
+        let bytes: [u8; 32] = rand::thread_rng().gen();
+        if let Ok(key) = secp256k1::SecretKey::from_slice(&bytes) {
+            let private_key = PrivateKey {
+                compressed: true,
+                network: Network::Testnet,
+                key,
+            };
+            return SignerID::new(private_key.public_key(&secp));
+        }
+    }
+}
+
+/// Min, median and max of the latencies.
+fn summary(mut latencies: Vec<Duration>) -> Option<(Duration, Duration, Duration)> {
+    if latencies.is_empty() {
+        return None;
+    }
+    latencies.sort();
+    Some((
+        latencies[0],
+        latencies[latencies.len() / 2],
+        latencies[latencies.len() - 1],
+    ))
+}
+
+#[test]
+fn test_summary() {
+    let latencies = vec![30, 10, 20, 50, 40]
+        .into_iter()
+        .map(Duration::from_millis)
+        .collect();
+    assert_eq!(
+        summary(latencies),
+        Some((
+            Duration::from_millis(10),
+            Duration::from_millis(30),
+            Duration::from_millis(50)
+        ))
+    );
+    assert_eq!(summary(vec![]), None);
+}
+
+#[test]
+fn test_ping_unreachable_broker() {
+    match ping("redis://127.0.0.1:1", 1) {
+        Err(Error::RedisError(_)) => {}
+        r => panic!("Should be RedisError, but {:?}", r),
+    }
+    match ping("http://127.0.0.1:6379", 1) {
+        Err(Error::RedisError(_)) => {}
+        r => panic!("Should be RedisError, but {:?}", r),
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use clap::Arg;
+
+use tapyrus_signer::command_args::{get_options, CommandArgs};
+use tapyrus_signer::rpc::{Rpc, TapyrusApi};
+use tapyrus_signer::sign::verify_proof;
+
+const OPTION_NAME_SINCE_HEIGHT: &str = "since_height";
+const OPTION_NAME_UNTIL_HEIGHT: &str = "until_height";
+
+/// Verify signatures in the proof of the blocks in the chain with the sighash of this crate.
+/// It reports the heights where signatures of the signers are less than threshold.
+/// Public keys, threshold and RPC connection are same options as `node`.
+///
+/// command example:
+/// ./target/debug/replay_blocks -c signer_config.toml --since-height 1 --until-height 100
+fn main() {
+    let options = get_options()
+        .name("replay_blocks")
+        .about("Verify proof of the blocks in the chain with the sighash of the signer.")
+        .after_help(
+            "EXAMPLE:\n    replay_blocks -c signer_config.toml --since-height 1 --until-height 100",
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_SINCE_HEIGHT)
+                .long("since-height")
+                .takes_value(true)
+                .required(true)
+                .value_name("HEIGHT"),
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_UNTIL_HEIGHT)
+                .long("until-height")
+                .takes_value(true)
+                .value_name("HEIGHT")
+                .help("Last height to verify. default is the tip of the chain."),
+        );
+    let matches = options.get_matches();
+    let since: u64 = matches
+        .value_of(OPTION_NAME_SINCE_HEIGHT)
+        .unwrap()
+        .parse()
+        .expect("since-height must be number.");
+    let until: Option<u64> = matches
+        .value_of(OPTION_NAME_UNTIL_HEIGHT)
+        .map(|h| h.parse().expect("until-height must be number."));
+
+    let configs = CommandArgs::load(matches).unwrap();
+    let signer_config = configs.signer_config();
+    let pubkey_list = signer_config.public_keys();
+    let threshold = signer_config.threshold() as usize;
+    let variant = configs.general_config().sighash_variant().unwrap();
+    let rpc_config = configs.rpc_config();
+    let rpc = Rpc::new(
+        format!("http://{}:{}", rpc_config.host(), rpc_config.port()),
+        rpc_config.user_name().map(str::to_string),
+        rpc_config.password().map(str::to_string),
+    );
+
+    let until = until.unwrap_or_else(|| rpc.getblockchaininfo().unwrap().blocks);
+    let mut failed = vec![];
+    for height in since..=until {
+        let result = rpc
+            .getblockhash(height)
+            .and_then(|hash| rpc.getblock(&hash))
+            .and_then(|block| verify_proof(&block, variant, &pubkey_list));
+        match result {
+            Ok(signers) if signers >= threshold => {}
+            Ok(signers) => {
+                println!(
+                    "height {}: only {} of threshold {} signatures are valid.",
+                    height, signers, threshold
+                );
+                failed.push(height);
+            }
+            Err(e) => {
+                println!("height {}: {:?}", height, e);
+                failed.push(height);
+            }
+        }
+    }
+    println!(
+        "verified {} blocks. {} blocks failed.",
+        (since..=until).count(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        std::process::exit(1);
+    }
+}
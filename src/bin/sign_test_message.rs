@@ -0,0 +1,54 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use clap::Arg;
+
+use tapyrus_signer::command_args::{get_options, CommandArgs};
+use tapyrus_signer::sign::{count_signers, sign_hash};
+
+const OPTION_NAME_MESSAGE: &str = "message";
+
+/// Sign arbitrary 32 bytes hash by the signers with the same primitive as signing blocks, and
+/// verify the signatures against the public keys. It is for checking interop with test vectors
+/// of other implementations. Public keys, threshold and private keys are same options as `node`.
+/// All private keys of the signers are specified by `--privatekey` multiple times.
+///
+/// command example:
+/// ./target/debug/sign_test_message -c signer_config.toml --message 1111111111111111111111111111111111111111111111111111111111111111
+fn main() {
+    let options = get_options()
+        .name("sign_test_message")
+        .about("Sign arbitrary hash with the signers' keys. This is not for producing blocks.")
+        .after_help("EXAMPLE:\n    Specify --privatekey for each signer.\n    sign_test_message -c signer_config.toml --privatekey=<WIF private key 1> --privatekey=<WIF private key 2> \\\n        --message <32 bytes hash in hex>")
+        .arg(
+            Arg::with_name(OPTION_NAME_MESSAGE)
+                .long("message")
+                .takes_value(true)
+                .required(true)
+                .value_name("HEX")
+                .help("32 bytes hash to sign in hex."),
+        );
+    let matches = options.get_matches();
+    let hash =
+        hex::decode(matches.value_of(OPTION_NAME_MESSAGE).unwrap()).expect("message must be hex.");
+
+    let configs = CommandArgs::load(matches).unwrap();
+    let signer_config = configs.signer_config();
+    let pubkey_list = signer_config.public_keys();
+    let threshold = signer_config.threshold() as usize;
+
+    let (message, signatures) = sign_hash(&signer_config.private_keys(), &hash).unwrap();
+    for signature in &signatures {
+        println!("{}", hex::encode(signature.serialize_der()));
+    }
+    let signers = count_signers(&message, &signatures, &pubkey_list);
+    println!(
+        "{} of threshold {} signatures are valid.",
+        signers, threshold
+    );
+    if signers < threshold {
+        std::process::exit(1);
+    }
+}
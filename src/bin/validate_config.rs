@@ -0,0 +1,60 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+use clap::{App, Arg};
+
+use tapyrus_signer::command_args::{validate_config, ConfigProblem};
+
+const OPTION_NAME_CONFIG: &str = "config";
+const OPTION_NAME_JSON: &str = "json";
+
+/// Check the config file of the signer node without connecting to Tapyrus Core or Redis.
+/// Exit code is 1 if any problem is found.
+///
+/// command example:
+/// ./target/debug/validate_config signer_config.toml --json
+fn main() {
+    let matches = App::new("validate_config")
+        .about("Check the config file of the signer node.")
+        .after_help("EXAMPLE:\n    validate_config signer_config.toml --json")
+        .arg(
+            Arg::with_name(OPTION_NAME_CONFIG)
+                .required(true)
+                .value_name("CONFIG_FILE_PATH")
+                .help("Config file to check."),
+        )
+        .arg(
+            Arg::with_name(OPTION_NAME_JSON)
+                .long("json")
+                .help("Print problems as JSON array."),
+        )
+        .get_matches();
+
+    let path = matches.value_of(OPTION_NAME_CONFIG).unwrap();
+    let problems = match std::fs::read_to_string(path) {
+        Ok(contents) => validate_config(&contents),
+        Err(e) => vec![ConfigProblem {
+            key: String::new(),
+            message: format!("Can't read {}: {}", path, e),
+        }],
+    };
+
+    if matches.is_present(OPTION_NAME_JSON) {
+        println!("{}", serde_json::to_string(&problems).unwrap());
+    } else if problems.is_empty() {
+        println!("{} is valid.", path);
+    } else {
+        for problem in &problems {
+            if problem.key.is_empty() {
+                println!("{}", problem.message);
+            } else {
+                println!("{}: {}", problem.key, problem.message);
+            }
+        }
+    }
+    if !problems.is_empty() {
+        std::process::exit(1);
+    }
+}
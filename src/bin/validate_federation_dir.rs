@@ -0,0 +1,398 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+extern crate tapyrus_signer;
+
+use std::path::Path;
+use std::str::FromStr;
+
+use bitcoin::{PrivateKey, PublicKey};
+use clap::{App, Arg};
+use tapyrus_signer::command_args::validate_config;
+use tapyrus_signer::signer_node::{canonical_order, select_private_key};
+use tapyrus_signer::token::FederationToken;
+
+const OPTION_NAME_DIR: &str = "dir";
+
+/// Check the directory which `bootstrap_federation` wrote, or the config files of a federation
+/// collected in a directory. Each `signer_*.toml` must be valid, and all of them and
+/// `federation.toml` must have the same public keys and threshold, and the signers must have the
+/// same limits of the block size and weight. The private key of each signer must be one of the
+/// public keys, and enough signers to produce blocks must be there.
+/// Exit code is 1 if any problem is found.
+///
+/// command example:
+/// ./target/debug/validate_federation_dir ./federation
+fn main() {
+    let matches = App::new("validate_federation_dir")
+        .about("Check that the config files of a federation in the directory are consistent.")
+        .after_help("EXAMPLE:\n    validate_federation_dir ./federation")
+        .arg(
+            Arg::with_name(OPTION_NAME_DIR)
+                .required(true)
+                .value_name("DIR")
+                .help("Directory which has signer_*.toml and federation.toml."),
+        )
+        .get_matches();
+
+    let dir = matches.value_of(OPTION_NAME_DIR).unwrap();
+    let problems = validate_federation_dir(Path::new(dir));
+    if problems.is_empty() {
+        println!("PASS: {} is consistent.", dir);
+        return;
+    }
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    println!("FAIL: {} problems in {}.", problems.len(), dir);
+    std::process::exit(1);
+}
+
+/// Public keys and threshold which a file says the federation has.
+#[derive(Debug, PartialEq)]
+struct Federation {
+    pubkeys: Vec<PublicKey>,
+    threshold: u64,
+}
+
+/// `max_block_size` and `max_block_weight` of the block policy. Members refuse blocks over the
+/// limits, so all signers must have the same.
+type BlockLimits = (Option<i64>, Option<i64>);
+
+fn validate_federation_dir(dir: &Path) -> Vec<String> {
+    let mut problems = vec![];
+    let mut names: Vec<String> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("signer_") && name.ends_with(".toml"))
+            .collect(),
+        Err(e) => return vec![format!("Can't read {}: {}", dir.display(), e)],
+    };
+    names.sort();
+    if names.is_empty() {
+        problems.push("No signer_*.toml is found.".to_string());
+    }
+
+    // the federation which the first valid file says, and the file name.
+    let mut expected: Option<(Federation, String)> = None;
+    let mut expected_limits: Option<(BlockLimits, String)> = None;
+    let mut members: Vec<PublicKey> = vec![];
+    let mut masters: Vec<&str> = vec![];
+    for name in &names {
+        let contents = match std::fs::read_to_string(dir.join(name)) {
+            Ok(contents) => contents,
+            Err(e) => {
+                problems.push(format!("{}: Can't read: {}", name, e));
+                continue;
+            }
+        };
+        let config_problems = validate_config(&contents);
+        if !config_problems.is_empty() {
+            for problem in config_problems {
+                problems.push(format!("{}: {}: {}", name, problem.key, problem.message));
+            }
+            continue;
+        }
+        let value: toml::Value = toml::from_str(&contents).unwrap();
+        let signer = match value.get("signer") {
+            Some(signer) => signer,
+            None => {
+                problems.push(format!("{}: signer section is not specified.", name));
+                continue;
+            }
+        };
+        let federation = match federation(signer) {
+            Ok(federation) => federation,
+            Err(message) => {
+                problems.push(format!("{}: {}", name, message));
+                continue;
+            }
+        };
+        match select_private_key(&private_keys(signer), &federation.pubkeys) {
+            Ok(key) => {
+                let pubkey = key.public_key(&secp256k1::Secp256k1::signing_only());
+                if members.contains(&pubkey) {
+                    problems.push(format!(
+                        "{}: private key of {} is also in another file.",
+                        name, pubkey
+                    ));
+                } else {
+                    members.push(pubkey);
+                }
+            }
+            Err(e) => problems.push(format!("{}: {:?}", name, e)),
+        }
+        if value
+            .get("general")
+            .and_then(|general| general.get("master"))
+            .and_then(|master| master.as_bool())
+            .unwrap_or_default()
+        {
+            masters.push(name);
+        }
+        match expected {
+            Some((ref expected, ref expected_name)) if *expected != federation => {
+                problems.push(format!(
+                    "{}: public keys or threshold are different from {}.",
+                    name, expected_name
+                ));
+            }
+            Some(_) => {}
+            None => expected = Some((federation, name.clone())),
+        }
+        let limits = block_limits(&value);
+        match expected_limits {
+            Some((ref expected, ref expected_name)) if *expected != limits => {
+                problems.push(format!(
+                    "{}: max_block_size or max_block_weight are different from {}.",
+                    name, expected_name
+                ));
+            }
+            Some(_) => {}
+            None => expected_limits = Some((limits, name.clone())),
+        }
+    }
+    if masters.len() > 1 {
+        problems.push(format!(
+            "Multiple signers are launched as master: {}.",
+            masters.join(", ")
+        ));
+    }
+
+    let expected = match expected {
+        Some((expected, _)) => expected,
+        None => return problems,
+    };
+    if (members.len() as u64) < expected.threshold {
+        problems.push(format!(
+            "Only {} signers are in the directory. Threshold {} signers are needed to produce blocks.",
+            members.len(),
+            expected.threshold
+        ));
+    }
+    problems.extend(validate_descriptor(dir, &expected));
+    problems
+}
+
+/// Check `federation.toml`, which `bootstrap_federation` writes for Tapyrus Core.
+fn validate_descriptor(dir: &Path, expected: &Federation) -> Vec<String> {
+    let name = "federation.toml";
+    let contents = match std::fs::read_to_string(dir.join(name)) {
+        Ok(contents) => contents,
+        Err(e) => return vec![format!("{}: Can't read: {}", name, e)],
+    };
+    let value: toml::Value = match toml::from_str(&contents) {
+        Ok(value) => value,
+        Err(e) => return vec![format!("{}: {}", name, e)],
+    };
+    let mut problems = vec![];
+    match federation(&value) {
+        Ok(ref federation) if federation == expected => {}
+        Ok(_) => problems.push(format!(
+            "{}: public keys or threshold are different from signer configs.",
+            name
+        )),
+        Err(message) => problems.push(format!("{}: {}", name, message)),
+    }
+    if let Some(token) = value.get("token").and_then(|token| token.as_str()) {
+        match FederationToken::from_str(token) {
+            Ok(token)
+                if token.pubkey_list == expected.pubkeys
+                    && u64::from(token.threshold) == expected.threshold => {}
+            Ok(_) => problems.push(format!("{}: token is different from signer configs.", name)),
+            Err(e) => problems.push(format!("{}: token: {:?}", name, e)),
+        }
+    }
+    if let Some(args) = value.get("tapyrus_core_args").and_then(|a| a.as_array()) {
+        let concatenated: String = expected.pubkeys.iter().map(|pk| pk.to_string()).collect();
+        let expected_args = [
+            format!("-signblockpubkeys={}", concatenated),
+            format!("-signblockthreshold={}", expected.threshold),
+        ];
+        for arg in expected_args.iter() {
+            if !args.iter().any(|a| a.as_str() == Some(arg)) {
+                problems.push(format!("{}: tapyrus_core_args doesn't have {}", name, arg));
+            }
+        }
+    }
+    problems
+}
+
+/// Public keys in signer index order and threshold in the table.
+fn federation(table: &toml::Value) -> Result<Federation, String> {
+    let mut pubkeys = table
+        .get("publickeys")
+        .and_then(|keys| keys.as_array())
+        .ok_or("publickeys is not specified.")?
+        .iter()
+        .map(|key| {
+            key.as_str()
+                .and_then(|key| PublicKey::from_str(key).ok())
+                .ok_or(format!("{} is invalid public key.", key))
+        })
+        .collect::<Result<Vec<PublicKey>, String>>()?;
+    canonical_order(&mut pubkeys);
+    let threshold = table
+        .get("threshold")
+        .and_then(|threshold| threshold.as_integer())
+        .ok_or("threshold is not specified.")?;
+    Ok(Federation {
+        pubkeys,
+        threshold: threshold as u64,
+    })
+}
+
+fn block_limits(config: &toml::Value) -> BlockLimits {
+    let limit = |key: &str| {
+        config
+            .get("general")
+            .and_then(|general| general.get(key))
+            .and_then(|limit| limit.as_integer())
+    };
+    (limit("max_block_size"), limit("max_block_weight"))
+}
+
+fn private_keys(signer: &toml::Value) -> Vec<PrivateKey> {
+    let single = signer.get("privatekey").into_iter();
+    let multiple = signer
+        .get("privatekeys")
+        .and_then(|keys| keys.as_array())
+        .into_iter()
+        .flatten();
+    single
+        .chain(multiple)
+        .filter_map(|key| key.as_str())
+        .filter_map(|key| PrivateKey::from_wif(key).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tapyrus_signer::test_helper::TestKeys;
+
+    /// Write config files of 3 of 5 federation to a new directory, like `bootstrap_federation`.
+    fn write_federation(name: &str, signers: usize) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "tapyrus-signer-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let testkeys = TestKeys::new();
+        let mut pubkeys = testkeys.pubkeys();
+        canonical_order(&mut pubkeys);
+        let pubkeys_toml: String = pubkeys.iter().map(|pk| format!("\"{}\",", pk)).collect();
+        for (i, key) in testkeys.key[..signers].iter().enumerate() {
+            let config = format!(
+                "[signer]\npublickeys = [{}]\nprivatekey = \"{}\"\nthreshold = 3\n\n[general]\nmaster = {}\n",
+                pubkeys_toml,
+                key.to_wif(),
+                i == 0
+            );
+            std::fs::write(dir.join(format!("signer_{}.toml", i)), config).unwrap();
+        }
+        let concatenated: String = pubkeys.iter().map(|pk| pk.to_string()).collect();
+        let token = FederationToken::new(pubkeys.clone(), 3).unwrap();
+        let descriptor = format!(
+            "publickeys = [{}]\nthreshold = 3\ntoken = \"{}\"\ntapyrus_core_args = [\"-signblockpubkeys={}\", \"-signblockthreshold=3\"]\n",
+            pubkeys_toml, token, concatenated
+        );
+        std::fs::write(dir.join("federation.toml"), descriptor).unwrap();
+        dir
+    }
+
+    fn replace(path: &Path, from: &str, to: &str) {
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(contents.contains(from));
+        std::fs::write(path, contents.replace(from, to)).unwrap();
+    }
+
+    #[test]
+    fn test_consistent_federation() {
+        let dir = write_federation("consistent", 5);
+        assert_eq!(validate_federation_dir(&dir), Vec::<String>::new());
+
+        // enough signers to produce blocks.
+        std::fs::remove_file(dir.join("signer_4.toml")).unwrap();
+        std::fs::remove_file(dir.join("signer_3.toml")).unwrap();
+        assert_eq!(validate_federation_dir(&dir), Vec::<String>::new());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_inconsistent_federation() {
+        let dir = write_federation("inconsistent", 5);
+        replace(&dir.join("signer_1.toml"), "threshold = 3", "threshold = 2");
+        replace(
+            &dir.join("signer_2.toml"),
+            "master = false",
+            "master = true",
+        );
+        std::fs::copy(dir.join("signer_3.toml"), dir.join("signer_4.toml")).unwrap();
+        replace(
+            &dir.join("federation.toml"),
+            "-signblockthreshold=3",
+            "-signblockthreshold=4",
+        );
+        let pubkey = TestKeys::new().pubkeys()[3];
+        assert_eq!(
+            validate_federation_dir(&dir),
+            vec![
+                "signer_1.toml: public keys or threshold are different from signer_0.toml."
+                    .to_string(),
+                format!(
+                    "signer_4.toml: private key of {} is also in another file.",
+                    pubkey
+                ),
+                "Multiple signers are launched as master: signer_0.toml, signer_2.toml."
+                    .to_string(),
+                "federation.toml: tapyrus_core_args doesn't have -signblockthreshold=3".to_string(),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_inconsistent_block_limits() {
+        let dir = write_federation("block-limits", 3);
+        for i in 0..3 {
+            let size = if i == 2 { 2000 } else { 1000 };
+            replace(
+                &dir.join(format!("signer_{}.toml", i)),
+                "[general]\n",
+                &format!("[general]\nmax_block_size = {}\n", size),
+            );
+        }
+        assert_eq!(
+            validate_federation_dir(&dir),
+            vec![
+                "signer_2.toml: max_block_size or max_block_weight are different from signer_0.toml."
+                    .to_string()
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_not_enough_signers() {
+        let dir = write_federation("not-enough", 2);
+        std::fs::write(dir.join("signer_2.toml"), "[signer]\nthreshold = \"3\"\n").unwrap();
+        let problems = validate_federation_dir(&dir);
+        assert!(problems[0].starts_with("signer_2.toml: "), "{:?}", problems);
+        assert_eq!(
+            problems.last().unwrap(),
+            "Only 2 signers are in the directory. Threshold 3 signers are needed to produce blocks."
+        );
+
+        std::fs::remove_file(dir.join("federation.toml")).unwrap();
+        assert!(validate_federation_dir(&dir)
+            .last()
+            .unwrap()
+            .starts_with("federation.toml: Can't read: "));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
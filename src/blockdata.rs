@@ -3,9 +3,12 @@
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
 use crate::errors::Error;
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::Transaction;
 use bitcoin_hashes::{sha256d, Hash};
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
 pub struct BlockHash([u8; 32]);
 
 impl BlockHash {
@@ -27,18 +30,57 @@ impl BlockHash {
     pub fn borrow_inner(&self) -> &[u8; 32] {
         &self.0
     }
+    /// Hex string in the byte order which RPCs of Tapyrus Core use, that is reversed.
+    pub fn to_rpc_hex(&self) -> String {
+        let mut bytes = self.0;
+        bytes.reverse();
+        hex::encode(bytes)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Block(Vec<u8>);
 
 impl Block {
+    /// Length of block header without proof is 104 bytes.
+    const HEADER_LEN: usize = 104;
+
     pub fn new(data: Vec<u8>) -> Block {
         Block(data)
     }
-    /// Length of block header without proof is 104 bytes.
+
+    /// Parse hex string of the block. Returns `Error::InvalidBlock` if the data isn't a block
+    /// which consists of header, proof and transactions.
+    pub fn from_hex(hex: &str) -> Result<Block, Error> {
+        let data = hex::decode(hex.trim()).map_err(|_| Error::InvalidBlock)?;
+        let block = Block(data);
+        block.transactions()?;
+        Ok(block)
+    }
+
+    /// Transactions in the block.
+    pub fn transactions(&self) -> Result<Vec<Transaction>, Error> {
+        let (_proof, txs) = self.body()?;
+        Ok(txs)
+    }
+
+    /// Signatures of the signers in the proof of the block. Candidate block has no signatures.
+    pub fn proof(&self) -> Result<Vec<Vec<u8>>, Error> {
+        let (proof, _txs) = self.body()?;
+        Ok(proof)
+    }
+
+    fn body(&self) -> Result<(Vec<Vec<u8>>, Vec<Transaction>), Error> {
+        if self.0.len() < Self::HEADER_LEN {
+            return Err(Error::InvalidBlock);
+        }
+        // proof and transactions follow after the header.
+        deserialize::<(Vec<Vec<u8>>, Vec<Transaction>)>(&self.0[Self::HEADER_LEN..])
+            .map_err(|_| Error::InvalidBlock)
+    }
+
     pub fn get_header_without_proof(&self) -> &[u8] {
-        &self.0[..104]
+        &self.0[..Self::HEADER_LEN]
     }
 
     pub fn hex(&self) -> String {
@@ -46,6 +88,9 @@ impl Block {
     }
 
     pub fn hash(&self) -> Result<BlockHash, Error> {
+        if self.0.len() < Self::HEADER_LEN {
+            return Err(Error::InvalidBlock);
+        }
         let header = self.get_header_without_proof();
         let hash = sha256d::Hash::hash(header).into_inner();
         Ok(BlockHash::from_slice(&hash)?)
@@ -55,3 +100,42 @@ impl Block {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helper::get_block;
+
+    #[test]
+    fn test_from_hex() {
+        let block = get_block(0);
+        assert_eq!(Block::from_hex(&block.hex()).unwrap(), block);
+    }
+
+    #[test]
+    fn test_to_rpc_hex() {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 0x01;
+        bytes[31] = 0xff;
+        let hex = BlockHash::from_slice(&bytes).unwrap().to_rpc_hex();
+        assert!(hex.starts_with("ff00"));
+        assert!(hex.ends_with("0001"));
+    }
+
+    #[test]
+    fn test_from_hex_invalid_block() {
+        let hex = get_block(0).hex();
+        match Block::from_hex("zz") {
+            Err(Error::InvalidBlock) => {}
+            r => panic!("Should be InvalidBlock, but {:?}", r),
+        }
+        match Block::from_hex(&hex[..200]) {
+            Err(Error::InvalidBlock) => {}
+            r => panic!("Should be InvalidBlock, but {:?}", r),
+        }
+        match Block::from_hex(&hex[..hex.len() - 2]) {
+            Err(Error::InvalidBlock) => {}
+            r => panic!("Should be InvalidBlock, but {:?}", r),
+        }
+    }
+}
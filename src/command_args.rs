@@ -2,13 +2,26 @@
 // Distributed under the MIT software license, see the accompanying
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
+use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
-use crate::signer_node::ROUND_INTERVAL_DEFAULT_SECS;
-use bitcoin::{PrivateKey, PublicKey};
+use crate::election::ElectionStrategy;
+use crate::policy::{BlockPolicy, ColorId};
+use crate::rpc::{
+    TlsConfig, DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_MAX_RESPONSE_SIZE, DEFAULT_RETRIES,
+    DEFAULT_RETRY_BACKOFF_MILLIS, DEFAULT_TIMEOUT_SECS, MIN_CORE_VERSION,
+};
+use crate::sign::SighashVariant;
+use crate::signer_node::{
+    MAX_ROUND_ATTEMPTS_DEFAULT, POLL_INTERVAL_DEFAULT_MILLIS, ROUND_INTERVAL_DEFAULT_SECS,
+    WATCHDOG_ROUNDS_DEFAULT,
+};
+use crate::statsd::STATSD_INTERVAL_DEFAULT_MILLIS;
+use bitcoin::{Network, PrivateKey, PublicKey};
 use clap::{App, Arg};
 use log;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 
 pub const OPTION_NAME_CONFIG: &str = "config";
@@ -20,18 +33,65 @@ pub const OPTION_NAME_RPC_ENDPOINT_HOST: &str = "rpc_endpoint_host";
 pub const OPTION_NAME_RPC_ENDPOINT_PORT: &str = "rpc_endpoint_port";
 pub const OPTION_NAME_RPC_ENDPOINT_USER: &str = "rpc_endpoint_user";
 pub const OPTION_NAME_RPC_ENDPOINT_PASS: &str = "rpc_endpoint_pass";
+pub const OPTION_NAME_RPC_TIMEOUT: &str = "rpc_timeout";
+pub const OPTION_NAME_RPC_CONNECT_TIMEOUT: &str = "rpc_connect_timeout";
+pub const OPTION_NAME_RPC_MAX_RESPONSE_SIZE: &str = "rpc_max_response_size";
+pub const OPTION_NAME_RPC_RETRIES: &str = "rpc_retries";
+pub const OPTION_NAME_RPC_RETRY_BACKOFF: &str = "rpc_retry_backoff";
+pub const OPTION_NAME_RPC_TLS: &str = "rpc_tls";
+pub const OPTION_NAME_RPC_VERIFY_PERMISSIONS: &str = "rpc_verify_permissions";
+pub const OPTION_NAME_RPC_MIN_CORE_VERSION: &str = "rpc_min_core_version";
+pub const OPTION_NAME_RPC_CA_CERT: &str = "rpc_ca_cert";
+pub const OPTION_NAME_RPC_CLIENT_CERT: &str = "rpc_client_cert";
+pub const OPTION_NAME_RPC_CLIENT_CERT_PASS: &str = "rpc_client_cert_pass";
 
 pub const OPTION_NAME_REDIS_HOST: &str = "redis_host";
 pub const OPTION_NAME_REDIS_PORT: &str = "redis_port";
+pub const OPTION_NAME_REDIS_MESSAGE_TTL: &str = "redis_message_ttl";
+pub const OPTION_NAME_REDIS_ACCEPT_UNTIMED_MESSAGES: &str = "redis_accept_untimed_messages";
 
 /// round category params.
 pub const OPTION_NAME_ROUND_DURATION: &str = "round_duration";
+pub const OPTION_NAME_MAX_ROUND_ATTEMPTS: &str = "max_round_attempts";
+pub const OPTION_NAME_TIMEOUT_JITTER: &str = "timeout_jitter";
+pub const OPTION_NAME_MAX_MISSED_ROUNDS: &str = "max_missed_rounds";
+pub const OPTION_NAME_WATCHDOG_ROUNDS: &str = "watchdog_rounds";
+pub const OPTION_NAME_FIXED_MASTER: &str = "fixed_master";
+pub const OPTION_NAME_MAX_CONCURRENT_ROUNDS: &str = "max_concurrent_rounds";
+pub const OPTION_NAME_COLLECTION_QUORUM: &str = "collection_quorum";
+pub const OPTION_NAME_LATE_SIGNATURE_GRACE: &str = "late_signature_grace";
+pub const OPTION_NAME_READINESS_WINDOW: &str = "readiness_window";
+pub const OPTION_NAME_DETECT_EQUIVOCATION: &str = "detect_equivocation";
+pub const OPTION_NAME_POLL_INTERVAL: &str = "poll_interval";
+pub const OPTION_NAME_SUBMIT_CONFIRMATION_TIMEOUT: &str = "submit_confirmation_timeout";
+pub const OPTION_NAME_STARTUP_TIMEOUT: &str = "startup_timeout";
+pub const OPTION_NAME_DUMP_METRICS: &str = "dump_metrics";
+pub const OPTION_NAME_EVENTS_FILE: &str = "events_file";
+pub const OPTION_NAME_LAST_SIGNED_HEIGHT_FILE: &str = "last_signed_height_file";
+pub const OPTION_NAME_ZMQ_ENDPOINT: &str = "zmq_endpoint";
+pub const OPTION_NAME_ADMIN_SOCKET: &str = "admin_socket";
+pub const OPTION_NAME_STATSD_ADDR: &str = "statsd_addr";
+pub const OPTION_NAME_METRICS_ADDR: &str = "metrics_addr";
+pub const OPTION_NAME_STATSD_INTERVAL: &str = "statsd_interval";
+pub const OPTION_NAME_SIGHASH_VARIANT: &str = "sighash_variant";
+pub const OPTION_NAME_LEADER_ELECTION: &str = "leader_election";
 
 /// log category params.
 pub const OPTION_NAME_LOG_QUIET: &str = "log_quiet";
 pub const OPTION_NAME_LOG_LEVEL: &str = "log_level";
 
 pub const OPTION_NAME_SKIP_WAITING_IBD: &str = "skip_waiting_ibd";
+pub const OPTION_NAME_NETWORK: &str = "network";
+pub const OPTION_NAME_OBSERVER: &str = "observer";
+pub const OPTION_NAME_SIGN_ONCE: &str = "sign_once";
+pub const OPTION_NAME_ALLOW_NO_FAULT_TOLERANCE: &str = "allow_no_fault_tolerance";
+pub const OPTION_NAME_EPHEMERAL_KEY: &str = "ephemeral_key";
+
+/// block policy params.
+pub const OPTION_NAME_ALLOWED_COLOR_IDS: &str = "allowed_color_ids";
+pub const OPTION_NAME_DENIED_COLOR_IDS: &str = "denied_color_ids";
+pub const OPTION_NAME_MAX_BLOCK_SIZE: &str = "max_block_size";
+pub const OPTION_NAME_MAX_BLOCK_WEIGHT: &str = "max_block_weight";
 
 pub const DEFAULT_RPC_HOST: &str = "127.0.0.1";
 pub const DEFAULT_RPC_PORT: &str = "2377";
@@ -43,34 +103,126 @@ pub const DEFAULT_LOG_LEVEL: &str = "info";
 /// default config file name
 pub const DEFAULT_CONFIG_FILENAME: &str = "signer_config.toml";
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Deserialize, Default)]
 struct SignerToml {
     publickeys: Option<Vec<String>>,
     privatekey: Option<String>,
+    privatekeys: Option<Vec<String>>,
     threshold: Option<u8>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
 pub struct RpcToml {
     rpc_endpoint_host: Option<String>,
     rpc_endpoint_port: Option<u32>,
     rpc_endpoint_user: Option<String>,
     rpc_endpoint_pass: Option<String>,
+    rpc_timeout: Option<u64>,
+    rpc_connect_timeout: Option<u64>,
+    rpc_max_response_size: Option<u64>,
+    rpc_retries: Option<u32>,
+    rpc_retry_backoff: Option<u64>,
+    rpc_tls: Option<bool>,
+    rpc_ca_cert: Option<String>,
+    rpc_client_cert: Option<String>,
+    rpc_client_cert_pass: Option<String>,
+    rpc_verify_permissions: Option<bool>,
+    rpc_min_core_version: Option<u64>,
+}
+
+/// Private keys are redacted so that the config can be logged safely.
+impl fmt::Debug for SignerToml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SignerToml")
+            .field("publickeys", &self.publickeys)
+            .field("privatekey", &self.privatekey.as_ref().map(|_| "***"))
+            .field(
+                "privatekeys",
+                &self
+                    .privatekeys
+                    .as_ref()
+                    .map(|keys| vec!["***"; keys.len()]),
+            )
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+/// Passwords are redacted so that the config can be logged safely.
+impl fmt::Debug for RpcToml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RpcToml")
+            .field("rpc_endpoint_host", &self.rpc_endpoint_host)
+            .field("rpc_endpoint_port", &self.rpc_endpoint_port)
+            .field("rpc_endpoint_user", &self.rpc_endpoint_user)
+            .field(
+                "rpc_endpoint_pass",
+                &self.rpc_endpoint_pass.as_ref().map(|_| "***"),
+            )
+            .field("rpc_timeout", &self.rpc_timeout)
+            .field("rpc_connect_timeout", &self.rpc_connect_timeout)
+            .field("rpc_max_response_size", &self.rpc_max_response_size)
+            .field("rpc_retries", &self.rpc_retries)
+            .field("rpc_retry_backoff", &self.rpc_retry_backoff)
+            .field("rpc_tls", &self.rpc_tls)
+            .field("rpc_ca_cert", &self.rpc_ca_cert)
+            .field("rpc_client_cert", &self.rpc_client_cert)
+            .field(
+                "rpc_client_cert_pass",
+                &self.rpc_client_cert_pass.as_ref().map(|_| "***"),
+            )
+            .field("rpc_verify_permissions", &self.rpc_verify_permissions)
+            .field("rpc_min_core_version", &self.rpc_min_core_version)
+            .finish()
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RedisToml {
     redis_host: Option<String>,
     redis_port: Option<u32>,
+    redis_message_ttl: Option<u64>,
+    redis_accept_untimed_messages: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GeneralToml {
     round_duration: Option<u64>,
+    max_round_attempts: Option<u32>,
+    timeout_jitter: Option<u64>,
+    max_missed_rounds: Option<u32>,
+    watchdog_rounds: Option<u32>,
+    fixed_master: Option<usize>,
+    max_concurrent_rounds: Option<u32>,
+    collection_quorum: Option<u8>,
+    late_signature_grace: Option<u64>,
+    readiness_window: Option<u64>,
+    detect_equivocation: Option<bool>,
+    poll_interval: Option<u64>,
+    submit_confirmation_timeout: Option<u64>,
+    startup_timeout: Option<u64>,
+    dump_metrics: Option<String>,
+    events_file: Option<String>,
+    last_signed_height_file: Option<String>,
+    zmq_endpoint: Option<String>,
+    admin_socket: Option<String>,
+    statsd_addr: Option<String>,
+    metrics_addr: Option<String>,
+    statsd_interval: Option<u64>,
+    sighash_variant: Option<String>,
+    leader_election: Option<String>,
     log_level: Option<String>,
     log_quiet: Option<bool>,
     skip_waiting_ibd: Option<bool>,
     master: Option<bool>,
+    observer: Option<bool>,
+    sign_once: Option<bool>,
+    allow_no_fault_tolerance: Option<bool>,
+    network: Option<String>,
+    allowed_color_ids: Option<Vec<String>>,
+    denied_color_ids: Option<Vec<String>>,
+    max_block_size: Option<u64>,
+    max_block_weight: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -87,7 +239,7 @@ pub struct CommandArgs<'a> {
 }
 
 pub struct SignerCommandArgs<'a> {
-    private_key: Option<&'a str>,
+    private_keys: Option<Vec<&'a str>>,
     public_keys: Option<Vec<&'a str>>,
     threshold: Option<u8>,
 }
@@ -141,23 +293,35 @@ impl<'a> SignerConfig<'a> {
             .expect("Must be specified threshold.")
     }
 
-    pub fn private_key(&self) -> PrivateKey {
-        let private_key_within_config: Option<&str> = self
-            .toml_config
-            .and_then(|config| config.privatekey.as_ref())
-            .map(|p| p as &str);
-        self.command_args
-            .private_key
-            .or(private_key_within_config)
-            .and_then(|s| match PrivateKey::from_str(s) {
-                Ok(p) => Some(p),
+    /// Private keys of this signer node. If multiple keys are specified, the key which is paired
+    /// with one of the public keys is used. See `key_store::FileKeyStore`.
+    pub fn private_keys(&self) -> Vec<PrivateKey> {
+        let private_keys_within_config: Option<Vec<&str>> = self.toml_config.map(|config| {
+            config
+                .privatekey
+                .iter()
+                .chain(config.privatekeys.iter().flatten())
+                .map(|p| p as &str)
+                .collect()
+        });
+        let specified = self
+            .command_args
+            .private_keys
+            .clone()
+            .or(private_keys_within_config)
+            .filter(|keys| !keys.is_empty())
+            .expect("Must be specified private_key.");
+        specified
+            .iter()
+            .map(|s| match PrivateKey::from_str(s) {
+                Ok(p) => p,
                 Err(e) => panic!(format!(
                     "'{}' is invalid WIF format!. error msg: {:?}",
                     s,
                     e.description()
                 )),
             })
-            .expect("Must be specified private_key.")
+            .collect()
     }
 }
 
@@ -166,6 +330,17 @@ pub struct RpcCommandArgs<'a> {
     pub port: Option<&'a str>,
     pub username: Option<&'a str>,
     pub password: Option<&'a str>,
+    pub timeout: Option<&'a str>,
+    pub connect_timeout: Option<&'a str>,
+    pub max_response_size: Option<&'a str>,
+    pub retries: Option<&'a str>,
+    pub retry_backoff: Option<&'a str>,
+    pub tls: bool,
+    pub ca_cert: Option<&'a str>,
+    pub client_cert: Option<&'a str>,
+    pub client_cert_pass: Option<&'a str>,
+    pub verify_permissions: bool,
+    pub min_core_version: Option<&'a str>,
 }
 
 pub struct RpcConfig<'a> {
@@ -206,11 +381,113 @@ impl<'a> RpcConfig<'a> {
             .map(|s| s as &str);
         self.command_args.password.or(toml_value)
     }
+    pub fn timeout(&'a self) -> u64 {
+        let toml_value = self.toml_config.and_then(|config| config.rpc_timeout);
+        self.command_args
+            .timeout
+            .and_then(|s| s.parse::<u64>().ok())
+            .or(toml_value)
+            .unwrap_or(DEFAULT_TIMEOUT_SECS)
+    }
+    /// Time limit(sec) for connecting to TapyrusCore RPC server.
+    pub fn connect_timeout(&'a self) -> u64 {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.rpc_connect_timeout);
+        self.command_args
+            .connect_timeout
+            .and_then(|s| s.parse::<u64>().ok())
+            .or(toml_value)
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS)
+    }
+    pub fn max_response_size(&'a self) -> u64 {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.rpc_max_response_size);
+        self.command_args
+            .max_response_size
+            .and_then(|s| s.parse::<u64>().ok())
+            .or(toml_value)
+            .unwrap_or(DEFAULT_MAX_RESPONSE_SIZE)
+    }
+    /// Count of retries of a RPC call which failed with a transient error.
+    pub fn retries(&'a self) -> u32 {
+        let toml_value = self.toml_config.and_then(|config| config.rpc_retries);
+        self.command_args
+            .retries
+            .and_then(|s| s.parse::<u32>().ok())
+            .or(toml_value)
+            .unwrap_or(DEFAULT_RETRIES)
+    }
+    /// Wait(msec) before the first retry of a RPC call.
+    pub fn retry_backoff(&'a self) -> u64 {
+        let toml_value = self.toml_config.and_then(|config| config.rpc_retry_backoff);
+        self.command_args
+            .retry_backoff
+            .and_then(|s| s.parse::<u64>().ok())
+            .or(toml_value)
+            .unwrap_or(DEFAULT_RETRY_BACKOFF_MILLIS)
+    }
+    /// Connect to Tapyrus Core with `https://`.
+    pub fn tls(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.rpc_tls)
+            .unwrap_or_default();
+        self.command_args.tls
+            || toml_value
+            || self.tls_config().ca_cert.is_some()
+            || self.tls_config().client_cert.is_some()
+    }
+    /// Check permissions of the RPC user on startup.
+    pub fn verify_permissions(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.rpc_verify_permissions)
+            .unwrap_or_default();
+        self.command_args.verify_permissions || toml_value
+    }
+    /// Minimum version of Tapyrus Core which is checked on startup. default is `MIN_CORE_VERSION`.
+    pub fn min_core_version(&'a self) -> u64 {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.rpc_min_core_version);
+        self.command_args
+            .min_core_version
+            .and_then(|s| s.parse::<u64>().ok())
+            .or(toml_value)
+            .unwrap_or(MIN_CORE_VERSION)
+    }
+    pub fn tls_config(&'a self) -> TlsConfig {
+        let toml_config = self.toml_config;
+        let ca_cert = self
+            .command_args
+            .ca_cert
+            .map(str::to_string)
+            .or(toml_config.and_then(|config| config.rpc_ca_cert.clone()));
+        let client_cert = self
+            .command_args
+            .client_cert
+            .map(str::to_string)
+            .or(toml_config.and_then(|config| config.rpc_client_cert.clone()));
+        let client_cert_pass = self
+            .command_args
+            .client_cert_pass
+            .map(str::to_string)
+            .or(toml_config.and_then(|config| config.rpc_client_cert_pass.clone()));
+        TlsConfig {
+            ca_cert: ca_cert.map(PathBuf::from),
+            client_cert: client_cert.map(PathBuf::from),
+            client_cert_pass: client_cert_pass.unwrap_or_default(),
+        }
+    }
 }
 
 pub struct RedisCommandArgs<'a> {
     pub host: Option<&'a str>,
     pub port: Option<&'a str>,
+    pub message_ttl: Option<&'a str>,
+    pub accept_untimed_messages: bool,
 }
 
 pub struct RedisConfig<'a> {
@@ -237,14 +514,64 @@ impl<'a> RedisConfig<'a> {
             .or(toml_value)
             .unwrap_or(DEFAULT_REDIS_PORT.parse().unwrap_or_default())
     }
+    /// Time(msec) after which received messages are dropped as stale. 0 means messages never
+    /// expire. None if not specified, then the node uses the length of a round.
+    pub fn message_ttl(&'a self) -> Option<u64> {
+        let toml_value = self.toml_config.and_then(|config| config.redis_message_ttl);
+        self.command_args
+            .message_ttl
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+    }
+    /// Accept messages without timestamp even if the message TTL is set, for the senders which
+    /// don't put it. Their age isn't checked. default is false.
+    pub fn accept_untimed_messages(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.redis_accept_untimed_messages)
+            .unwrap_or_default();
+        self.command_args.accept_untimed_messages || toml_value
+    }
 }
 
 pub struct GeneralCommandArgs<'a> {
     round_duration: Option<&'a str>,
+    max_round_attempts: Option<&'a str>,
+    timeout_jitter: Option<&'a str>,
+    max_missed_rounds: Option<&'a str>,
+    watchdog_rounds: Option<&'a str>,
+    fixed_master: Option<&'a str>,
+    max_concurrent_rounds: Option<&'a str>,
+    collection_quorum: Option<&'a str>,
+    late_signature_grace: Option<&'a str>,
+    readiness_window: Option<&'a str>,
+    detect_equivocation: bool,
+    poll_interval: Option<&'a str>,
+    submit_confirmation_timeout: Option<&'a str>,
+    startup_timeout: Option<&'a str>,
+    dump_metrics: Option<&'a str>,
+    events_file: Option<&'a str>,
+    last_signed_height_file: Option<&'a str>,
+    zmq_endpoint: Option<&'a str>,
+    admin_socket: Option<&'a str>,
+    statsd_addr: Option<&'a str>,
+    metrics_addr: Option<&'a str>,
+    statsd_interval: Option<&'a str>,
+    sighash_variant: Option<&'a str>,
+    leader_election: Option<&'a str>,
     log_quiet: bool,
     log_level: Option<&'a str>,
     skip_waiting_ibd: bool,
     master: bool,
+    observer: bool,
+    sign_once: bool,
+    allow_no_fault_tolerance: bool,
+    ephemeral_key: bool,
+    network: Option<&'a str>,
+    allowed_color_ids: Option<Vec<&'a str>>,
+    denied_color_ids: Option<Vec<&'a str>>,
+    max_block_size: Option<&'a str>,
+    max_block_weight: Option<&'a str>,
 }
 
 pub struct GeneralConfig<'a> {
@@ -261,6 +588,237 @@ impl<'a> GeneralConfig<'a> {
             .or(toml_value)
             .unwrap_or(ROUND_INTERVAL_DEFAULT_SECS)
     }
+    pub fn max_round_attempts(&'a self) -> u32 {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.max_round_attempts);
+        self.command_args
+            .max_round_attempts
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(MAX_ROUND_ATTEMPTS_DEFAULT)
+    }
+    /// Max random jitter(msec) added to round timeout. default is 0.
+    pub fn timeout_jitter(&'a self) -> u64 {
+        let toml_value = self.toml_config.and_then(|config| config.timeout_jitter);
+        self.command_args
+            .timeout_jitter
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(0)
+    }
+    /// Count of consecutive rounds as master without the signature of a signer, after which
+    /// master doesn't wait for it to meet the collection quorum. default is 0, it means never
+    /// excluded.
+    pub fn max_missed_rounds(&'a self) -> u32 {
+        let toml_value = self.toml_config.and_then(|config| config.max_missed_rounds);
+        self.command_args
+            .max_missed_rounds
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(0)
+    }
+    /// Number of round time limits without progress, after which the state machine is reset.
+    /// default is 3. 0 disables the watchdog.
+    pub fn watchdog_rounds(&'a self) -> u32 {
+        let toml_value = self.toml_config.and_then(|config| config.watchdog_rounds);
+        self.command_args
+            .watchdog_rounds
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(WATCHDOG_ROUNDS_DEFAULT)
+    }
+    /// Signer index of the master of all rounds. default is none, it means master rotates.
+    pub fn fixed_master(&'a self) -> Option<usize> {
+        let toml_value = self.toml_config.and_then(|config| config.fixed_master);
+        self.command_args
+            .fixed_master
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+    }
+    /// Number of rounds which a member signs at the same time. `None` if not specified, it
+    /// means no limit.
+    pub fn max_concurrent_rounds(&'a self) -> Option<u32> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.max_concurrent_rounds);
+        self.command_args
+            .max_concurrent_rounds
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+    }
+    /// Number of signatures master waits for before combining them. `None` if not specified, it
+    /// means threshold.
+    pub fn collection_quorum(&'a self) -> Option<u8> {
+        let toml_value = self.toml_config.and_then(|config| config.collection_quorum);
+        self.command_args
+            .collection_quorum
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+    }
+    /// Time(msec) after the block is produced, in which master counts late signatures in
+    /// participation metrics. default is 0, it means late signatures are ignored.
+    pub fn late_signature_grace(&'a self) -> u64 {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.late_signature_grace);
+        self.command_args
+            .late_signature_grace
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(0)
+    }
+    /// Time(msec) master waits for members to be ready before broadcasting the candidate block.
+    /// default is 0, it means readiness isn't checked.
+    pub fn readiness_window(&'a self) -> u64 {
+        let toml_value = self.toml_config.and_then(|config| config.readiness_window);
+        self.command_args
+            .readiness_window
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(0)
+    }
+    /// Members tell each other the hash of the candidate block to detect the master which
+    /// proposes different blocks. default is false.
+    pub fn detect_equivocation(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.detect_equivocation)
+            .unwrap_or_default();
+        self.command_args.detect_equivocation || toml_value
+    }
+    /// Interval(msec) of requesting candidate block again, when Tapyrus Core failed to create it.
+    pub fn poll_interval(&'a self) -> u64 {
+        let toml_value = self.toml_config.and_then(|config| config.poll_interval);
+        self.command_args
+            .poll_interval
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(POLL_INTERVAL_DEFAULT_MILLIS)
+    }
+    /// Time(msec) master waits for the submitted block to become the best block of Tapyrus Core.
+    /// default is 0, it means the submission isn't confirmed.
+    pub fn submit_confirmation_timeout(&'a self) -> u64 {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.submit_confirmation_timeout);
+        self.command_args
+            .submit_confirmation_timeout
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(0)
+    }
+    /// Time(sec) the node has to connect to RPC and redis and validate the federation on startup.
+    /// default is 0, it means no limit.
+    pub fn startup_timeout(&'a self) -> u64 {
+        let toml_value = self.toml_config.and_then(|config| config.startup_timeout);
+        self.command_args
+            .startup_timeout
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(0)
+    }
+    /// File which the metrics snapshot is written to as JSON at the end of each round.
+    pub fn dump_metrics(&'a self) -> Option<PathBuf> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.dump_metrics.as_ref())
+            .map(|s| s as &str);
+        self.command_args
+            .dump_metrics
+            .or(toml_value)
+            .map(PathBuf::from)
+    }
+    /// File which the event of each round is appended to as a JSON line. "-" means stderr.
+    pub fn events_file(&'a self) -> Option<PathBuf> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.events_file.as_ref())
+            .map(|s| s as &str);
+        self.command_args
+            .events_file
+            .or(toml_value)
+            .map(PathBuf::from)
+    }
+    /// File which the height of the block this node signed last time is written to.
+    pub fn last_signed_height_file(&'a self) -> Option<PathBuf> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.last_signed_height_file.as_ref())
+            .map(|s| s as &str);
+        self.command_args
+            .last_signed_height_file
+            .or(toml_value)
+            .map(PathBuf::from)
+    }
+    /// ZMQ endpoint where Tapyrus Core publishes `hashblock` notifications. `None` if not
+    /// specified, then the node only polls Tapyrus Core.
+    pub fn zmq_endpoint(&'a self) -> Option<&'a str> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.zmq_endpoint.as_ref())
+            .map(|s| s as &str);
+        self.command_args.zmq_endpoint.or(toml_value)
+    }
+    /// `HOST:PORT` of StatsD server which the metrics are pushed to. Disabled if not specified.
+    pub fn statsd_addr(&'a self) -> Option<&'a str> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.statsd_addr.as_ref())
+            .map(|s| s as &str);
+        self.command_args.statsd_addr.or(toml_value)
+    }
+    /// `HOST:PORT` of the HTTP listener which serves the metrics to Prometheus on `/metrics`.
+    /// Disabled if not specified.
+    pub fn metrics_addr(&'a self) -> Option<&'a str> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.metrics_addr.as_ref())
+            .map(|s| s as &str);
+        self.command_args.metrics_addr.or(toml_value)
+    }
+    /// Interval(msec) of pushing the metrics to StatsD.
+    pub fn statsd_interval(&'a self) -> u64 {
+        let toml_value = self.toml_config.and_then(|config| config.statsd_interval);
+        self.command_args
+            .statsd_interval
+            .and_then(|n| n.parse().ok())
+            .or(toml_value)
+            .unwrap_or(STATSD_INTERVAL_DEFAULT_MILLIS)
+    }
+    /// Unix domain socket which serves the status of the node as JSON. Disabled if not specified.
+    pub fn admin_socket(&'a self) -> Option<PathBuf> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.admin_socket.as_ref())
+            .map(|s| s as &str);
+        self.command_args
+            .admin_socket
+            .or(toml_value)
+            .map(PathBuf::from)
+    }
+    /// Variant of the message which signers sign for the block. default is "header".
+    pub fn sighash_variant(&'a self) -> Result<SighashVariant, crate::errors::Error> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.sighash_variant.as_ref())
+            .map(|s| s as &str);
+        match self.command_args.sighash_variant.or(toml_value) {
+            Some(s) => SighashVariant::from_str(s),
+            None => Ok(SighashVariant::default()),
+        }
+    }
+    /// Rule to elect master of each round. default is "round-robin".
+    pub fn leader_election(&'a self) -> Result<ElectionStrategy, crate::errors::Error> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.leader_election.as_ref())
+            .map(|s| s as &str);
+        match self.command_args.leader_election.or(toml_value) {
+            Some(s) => ElectionStrategy::from_str(s),
+            None => Ok(ElectionStrategy::default()),
+        }
+    }
     pub fn log_level(&'a self) -> &'a str {
         let toml_value = self
             .toml_config
@@ -292,6 +850,89 @@ impl<'a> GeneralConfig<'a> {
             .unwrap_or_default();
         self.command_args.master || toml_value
     }
+    pub fn observer(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.observer)
+            .unwrap_or_default();
+        self.command_args.observer || toml_value
+    }
+    /// Sign one candidate block as a member and exit, instead of running as a daemon.
+    pub fn sign_once(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.sign_once)
+            .unwrap_or_default();
+        self.command_args.sign_once || toml_value
+    }
+    /// Don't warn that the federation has no fault tolerance when threshold equals the number
+    /// of signers.
+    pub fn allow_no_fault_tolerance(&'a self) -> bool {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.allow_no_fault_tolerance)
+            .unwrap_or_default();
+        self.command_args.allow_no_fault_tolerance || toml_value
+    }
+    /// Generate a private key on startup instead of the specified one. Only on the command line,
+    /// because the key is thrown away when the node stops.
+    pub fn ephemeral_key(&'a self) -> bool {
+        self.command_args.ephemeral_key
+    }
+    /// Network which private keys belong to. `None` if not specified.
+    /// WIF format doesn't distinguish testnet and regtest, so both of them are `Network::Testnet`.
+    pub fn network(&'a self) -> Option<Network> {
+        let toml_value = self
+            .toml_config
+            .and_then(|config| config.network.as_ref())
+            .map(|s| s as &str);
+        self.command_args.network.or(toml_value).map(|s| match s {
+            "mainnet" => Network::Bitcoin,
+            "testnet" | "regtest" => Network::Testnet,
+            _ => panic!("'{}' is invalid network.", s),
+        })
+    }
+    /// Policy of candidate blocks which the node signs.
+    pub fn block_policy(&'a self) -> BlockPolicy {
+        let allowed = color_ids(
+            &self.command_args.allowed_color_ids,
+            self.toml_config
+                .and_then(|config| config.allowed_color_ids.as_ref()),
+        );
+        let denied = color_ids(
+            &self.command_args.denied_color_ids,
+            self.toml_config
+                .and_then(|config| config.denied_color_ids.as_ref()),
+        );
+        let max_block_size = self.toml_config.and_then(|config| config.max_block_size);
+        let max_block_weight = self.toml_config.and_then(|config| config.max_block_weight);
+        BlockPolicy {
+            allowed_color_ids: allowed,
+            denied_color_ids: denied.unwrap_or_default(),
+            max_block_size: self
+                .command_args
+                .max_block_size
+                .and_then(|n| n.parse().ok())
+                .or(max_block_size),
+            max_block_weight: self
+                .command_args
+                .max_block_weight
+                .and_then(|n| n.parse().ok())
+                .or(max_block_weight),
+        }
+    }
+}
+
+fn color_ids(
+    command_args: &Option<Vec<&str>>,
+    toml_value: Option<&Vec<String>>,
+) -> Option<Vec<ColorId>> {
+    let toml_value: Option<Vec<&str>> = toml_value.map(|v| v.iter().map(|s| s as &str).collect());
+    command_args.clone().or(toml_value).map(|ids| {
+        ids.iter()
+            .map(|s| ColorId::from_str(s).unwrap_or_else(|e| panic!("{:?}", e)))
+            .collect()
+    })
 }
 
 /// command example:
@@ -311,20 +952,28 @@ impl<'a> CommandArgs<'a> {
     pub fn load(matches: clap::ArgMatches) -> Result<CommandArgs, crate::errors::Error> {
         // load from config file if exists.
         let config_file = matches.value_of(OPTION_NAME_CONFIG).unwrap();
-        match read_config(config_file) {
-            Ok(c) => Ok(CommandArgs {
-                matches,
-                config: Some(c),
-            }),
+        let config = match read_config(config_file) {
+            Ok(c) => Some(c),
             Err(crate::errors::Error::ConfigFileIOError(ioerror)) => {
                 log::warn!("config file read error: {:?}", ioerror);
-                Ok(CommandArgs {
-                    matches,
-                    config: None,
-                })
+                None
             }
-            Err(e) => Err(e),
+            Err(e) => return Err(e),
+        };
+        let args = CommandArgs { matches, config };
+        args.validate_features()?;
+        Ok(args)
+    }
+
+    /// Reject the options which this build doesn't support, so that the node doesn't fail after
+    /// it started.
+    fn validate_features(&self) -> Result<(), crate::errors::Error> {
+        if !cfg!(feature = "tls") && self.rpc_config().tls() {
+            return Err(crate::errors::Error::InvalidArgs(
+                "TLS for RPC connection is not supported. Build the signer with `--features tls`, or remove rpc_tls, rpc_ca_cert and rpc_client_cert.".to_string(),
+            ));
         }
+        Ok(())
     }
 
     pub fn signer_config(&self) -> SignerConfig {
@@ -336,7 +985,10 @@ impl<'a> CommandArgs<'a> {
                     .matches
                     .values_of(OPTION_NAME_PUBLIC_KEY)
                     .map(|vs| vs.collect()),
-                private_key: self.matches.value_of(OPTION_NAME_PRIVATE_KEY),
+                private_keys: self
+                    .matches
+                    .values_of(OPTION_NAME_PRIVATE_KEY)
+                    .map(|vs| vs.collect()),
                 threshold: num,
             },
             toml_config: self.config.as_ref().and_then(|c| c.signer.as_ref()),
@@ -350,6 +1002,17 @@ impl<'a> CommandArgs<'a> {
                 port: self.matches.value_of(OPTION_NAME_RPC_ENDPOINT_PORT),
                 username: self.matches.value_of(OPTION_NAME_RPC_ENDPOINT_USER),
                 password: self.matches.value_of(OPTION_NAME_RPC_ENDPOINT_PASS),
+                timeout: self.matches.value_of(OPTION_NAME_RPC_TIMEOUT),
+                connect_timeout: self.matches.value_of(OPTION_NAME_RPC_CONNECT_TIMEOUT),
+                max_response_size: self.matches.value_of(OPTION_NAME_RPC_MAX_RESPONSE_SIZE),
+                retries: self.matches.value_of(OPTION_NAME_RPC_RETRIES),
+                retry_backoff: self.matches.value_of(OPTION_NAME_RPC_RETRY_BACKOFF),
+                tls: self.matches.is_present(OPTION_NAME_RPC_TLS),
+                ca_cert: self.matches.value_of(OPTION_NAME_RPC_CA_CERT),
+                client_cert: self.matches.value_of(OPTION_NAME_RPC_CLIENT_CERT),
+                client_cert_pass: self.matches.value_of(OPTION_NAME_RPC_CLIENT_CERT_PASS),
+                verify_permissions: self.matches.is_present(OPTION_NAME_RPC_VERIFY_PERMISSIONS),
+                min_core_version: self.matches.value_of(OPTION_NAME_RPC_MIN_CORE_VERSION),
             },
             toml_config: self.config.as_ref().and_then(|c| c.rpc.as_ref()),
         }
@@ -360,6 +1023,10 @@ impl<'a> CommandArgs<'a> {
             command_args: RedisCommandArgs {
                 host: self.matches.value_of(OPTION_NAME_REDIS_HOST),
                 port: self.matches.value_of(OPTION_NAME_REDIS_PORT),
+                message_ttl: self.matches.value_of(OPTION_NAME_REDIS_MESSAGE_TTL),
+                accept_untimed_messages: self
+                    .matches
+                    .is_present(OPTION_NAME_REDIS_ACCEPT_UNTIMED_MESSAGES),
             },
             toml_config: self.config.as_ref().and_then(|c| c.redis.as_ref()),
         }
@@ -368,10 +1035,52 @@ impl<'a> CommandArgs<'a> {
         GeneralConfig {
             command_args: GeneralCommandArgs {
                 round_duration: self.matches.value_of(OPTION_NAME_REDIS_HOST),
+                max_round_attempts: self.matches.value_of(OPTION_NAME_MAX_ROUND_ATTEMPTS),
+                timeout_jitter: self.matches.value_of(OPTION_NAME_TIMEOUT_JITTER),
+                max_missed_rounds: self.matches.value_of(OPTION_NAME_MAX_MISSED_ROUNDS),
+                watchdog_rounds: self.matches.value_of(OPTION_NAME_WATCHDOG_ROUNDS),
+                fixed_master: self.matches.value_of(OPTION_NAME_FIXED_MASTER),
+                max_concurrent_rounds: self.matches.value_of(OPTION_NAME_MAX_CONCURRENT_ROUNDS),
+                collection_quorum: self.matches.value_of(OPTION_NAME_COLLECTION_QUORUM),
+                late_signature_grace: self.matches.value_of(OPTION_NAME_LATE_SIGNATURE_GRACE),
+                readiness_window: self.matches.value_of(OPTION_NAME_READINESS_WINDOW),
+                detect_equivocation: self.matches.is_present(OPTION_NAME_DETECT_EQUIVOCATION),
+                poll_interval: self.matches.value_of(OPTION_NAME_POLL_INTERVAL),
+                submit_confirmation_timeout: self
+                    .matches
+                    .value_of(OPTION_NAME_SUBMIT_CONFIRMATION_TIMEOUT),
+                startup_timeout: self.matches.value_of(OPTION_NAME_STARTUP_TIMEOUT),
+                dump_metrics: self.matches.value_of(OPTION_NAME_DUMP_METRICS),
+                events_file: self.matches.value_of(OPTION_NAME_EVENTS_FILE),
+                last_signed_height_file: self.matches.value_of(OPTION_NAME_LAST_SIGNED_HEIGHT_FILE),
+                zmq_endpoint: self.matches.value_of(OPTION_NAME_ZMQ_ENDPOINT),
+                admin_socket: self.matches.value_of(OPTION_NAME_ADMIN_SOCKET),
+                statsd_addr: self.matches.value_of(OPTION_NAME_STATSD_ADDR),
+                metrics_addr: self.matches.value_of(OPTION_NAME_METRICS_ADDR),
+                statsd_interval: self.matches.value_of(OPTION_NAME_STATSD_INTERVAL),
+                sighash_variant: self.matches.value_of(OPTION_NAME_SIGHASH_VARIANT),
+                leader_election: self.matches.value_of(OPTION_NAME_LEADER_ELECTION),
                 log_level: self.matches.value_of(OPTION_NAME_LOG_LEVEL),
                 log_quiet: self.matches.is_present(OPTION_NAME_LOG_QUIET),
                 skip_waiting_ibd: self.matches.is_present(OPTION_NAME_SKIP_WAITING_IBD),
                 master: self.matches.is_present(OPTION_NAME_MASTER_FLAG),
+                observer: self.matches.is_present(OPTION_NAME_OBSERVER),
+                sign_once: self.matches.is_present(OPTION_NAME_SIGN_ONCE),
+                allow_no_fault_tolerance: self
+                    .matches
+                    .is_present(OPTION_NAME_ALLOW_NO_FAULT_TOLERANCE),
+                ephemeral_key: self.matches.is_present(OPTION_NAME_EPHEMERAL_KEY),
+                network: self.matches.value_of(OPTION_NAME_NETWORK),
+                allowed_color_ids: self
+                    .matches
+                    .values_of(OPTION_NAME_ALLOWED_COLOR_IDS)
+                    .map(|vs| vs.collect()),
+                denied_color_ids: self
+                    .matches
+                    .values_of(OPTION_NAME_DENIED_COLOR_IDS)
+                    .map(|vs| vs.collect()),
+                max_block_size: self.matches.value_of(OPTION_NAME_MAX_BLOCK_SIZE),
+                max_block_weight: self.matches.value_of(OPTION_NAME_MAX_BLOCK_WEIGHT),
             },
             toml_config: self.config.as_ref().and_then(|c| c.general.as_ref()),
         }
@@ -384,11 +1093,333 @@ fn read_config(file_path: &str) -> Result<ConfigToml, crate::errors::Error> {
     Ok(toml)
 }
 
+/// Keys which each section of the config file can have.
+const CONFIG_KEYS: [(&str, &[&str]); 4] = [
+    (
+        "signer",
+        &["publickeys", "privatekey", "privatekeys", "threshold"],
+    ),
+    (
+        "rpc",
+        &[
+            "rpc_endpoint_host",
+            "rpc_endpoint_port",
+            "rpc_endpoint_user",
+            "rpc_endpoint_pass",
+            "rpc_timeout",
+            "rpc_connect_timeout",
+            "rpc_max_response_size",
+            "rpc_retries",
+            "rpc_retry_backoff",
+            "rpc_tls",
+            "rpc_ca_cert",
+            "rpc_client_cert",
+            "rpc_client_cert_pass",
+            "rpc_verify_permissions",
+            "rpc_min_core_version",
+        ],
+    ),
+    (
+        "redis",
+        &[
+            "redis_host",
+            "redis_port",
+            "redis_message_ttl",
+            "redis_accept_untimed_messages",
+        ],
+    ),
+    (
+        "general",
+        &[
+            "round_duration",
+            "max_round_attempts",
+            "timeout_jitter",
+            "max_missed_rounds",
+            "watchdog_rounds",
+            "fixed_master",
+            "max_concurrent_rounds",
+            "collection_quorum",
+            "late_signature_grace",
+            "readiness_window",
+            "detect_equivocation",
+            "poll_interval",
+            "submit_confirmation_timeout",
+            "startup_timeout",
+            "dump_metrics",
+            "events_file",
+            "last_signed_height_file",
+            "zmq_endpoint",
+            "admin_socket",
+            "statsd_addr",
+            "metrics_addr",
+            "statsd_interval",
+            "sighash_variant",
+            "leader_election",
+            "log_level",
+            "log_quiet",
+            "skip_waiting_ibd",
+            "master",
+            "observer",
+            "sign_once",
+            "allow_no_fault_tolerance",
+            "network",
+            "allowed_color_ids",
+            "denied_color_ids",
+            "max_block_size",
+            "max_block_weight",
+        ],
+    ),
+];
+
+/// Problem found in the config file.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ConfigProblem {
+    /// Key of the problem, like `signer.threshold`. Empty if it is about the whole file.
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn new(key: &str, message: String) -> Self {
+        ConfigProblem {
+            key: key.to_string(),
+            message,
+        }
+    }
+}
+
+/// Check the config file contents without connecting to anything. It reports syntax and type
+/// errors, unknown keys and invalid values.
+pub fn validate_config(contents: &str) -> Vec<ConfigProblem> {
+    let value: toml::Value = match toml::from_str(contents) {
+        Ok(v) => v,
+        Err(e) => return vec![ConfigProblem::new("", e.to_string())],
+    };
+    let mut problems = unknown_keys(&value);
+    match toml::from_str::<ConfigToml>(contents) {
+        Ok(config) => problems.extend(invalid_values(&config)),
+        Err(e) => problems.push(ConfigProblem::new("", e.to_string())),
+    }
+    problems
+}
+
+fn unknown_keys(value: &toml::Value) -> Vec<ConfigProblem> {
+    let mut problems = vec![];
+    let sections = match value.as_table() {
+        Some(t) => t,
+        None => return problems,
+    };
+    for (section, table) in sections {
+        let known = match CONFIG_KEYS.iter().find(|(name, _)| name == section) {
+            Some((_, keys)) => keys,
+            None => {
+                problems.push(ConfigProblem::new(section, "unknown section.".to_string()));
+                continue;
+            }
+        };
+        for key in table.as_table().iter().flat_map(|t| t.keys()) {
+            if !known.contains(&key.as_str()) {
+                let key = format!("{}.{}", section, key);
+                problems.push(ConfigProblem::new(&key, "unknown key.".to_string()));
+            }
+        }
+    }
+    problems
+}
+
+fn invalid_values(config: &ConfigToml) -> Vec<ConfigProblem> {
+    let mut problems = vec![];
+    if let Some(ref signer) = config.signer {
+        let pubkeys = signer.publickeys.clone().unwrap_or_default();
+        for pk in &pubkeys {
+            if PublicKey::from_str(pk).is_err() {
+                problems.push(ConfigProblem::new(
+                    "signer.publickeys",
+                    format!("'{}' is invalid public key format.", pk),
+                ));
+            }
+        }
+        for wif in signer
+            .privatekey
+            .iter()
+            .chain(signer.privatekeys.iter().flatten())
+        {
+            if PrivateKey::from_str(wif).is_err() {
+                problems.push(ConfigProblem::new(
+                    "signer.privatekey",
+                    "invalid WIF format.".to_string(),
+                ));
+            }
+        }
+        if let Some(threshold) = signer.threshold {
+            if threshold == 0 || threshold as usize > pubkeys.len() {
+                problems.push(ConfigProblem::new(
+                    "signer.threshold",
+                    format!(
+                        "threshold must be 1 to the number of public keys. threshold: {}, publickeys: {}",
+                        threshold,
+                        pubkeys.len()
+                    ),
+                ));
+            }
+        }
+    }
+    if let Some(ref rpc) = config.rpc {
+        problems.extend(invalid_host(
+            "rpc.rpc_endpoint_host",
+            &rpc.rpc_endpoint_host,
+        ));
+        problems.extend(invalid_port("rpc.rpc_endpoint_port", rpc.rpc_endpoint_port));
+        if !cfg!(feature = "tls") {
+            let tls_options = [
+                ("rpc.rpc_tls", rpc.rpc_tls == Some(true)),
+                ("rpc.rpc_ca_cert", rpc.rpc_ca_cert.is_some()),
+                ("rpc.rpc_client_cert", rpc.rpc_client_cert.is_some()),
+                (
+                    "rpc.rpc_client_cert_pass",
+                    rpc.rpc_client_cert_pass.is_some(),
+                ),
+            ];
+            for (key, _) in tls_options.iter().filter(|(_, set)| *set) {
+                problems.push(ConfigProblem::new(
+                    key,
+                    "is not supported in the build without `tls` feature.".to_string(),
+                ));
+            }
+        }
+    }
+    if let Some(ref redis) = config.redis {
+        problems.extend(invalid_host("redis.redis_host", &redis.redis_host));
+        problems.extend(invalid_port("redis.redis_port", redis.redis_port));
+    }
+    if let Some(ref general) = config.general {
+        if let Some(ref network) = general.network {
+            if !["mainnet", "testnet", "regtest"].contains(&network.as_str()) {
+                problems.push(ConfigProblem::new(
+                    "general.network",
+                    format!("'{}' is invalid network.", network),
+                ));
+            }
+        }
+        if let Some(ref level) = general.log_level {
+            if !["error", "warn", "info", "debug", "trace"].contains(&level.as_str()) {
+                problems.push(ConfigProblem::new(
+                    "general.log_level",
+                    format!("'{}' is invalid log level.", level),
+                ));
+            }
+        }
+        if let Some(poll_interval) = general.poll_interval {
+            if poll_interval < crate::signer_node::POLL_INTERVAL_MIN_MILLIS {
+                problems.push(ConfigProblem::new(
+                    "general.poll_interval",
+                    format!(
+                        "must be {} or more.",
+                        crate::signer_node::POLL_INTERVAL_MIN_MILLIS
+                    ),
+                ));
+            }
+        }
+        if let (Some(index), Some(pubkeys)) = (
+            general.fixed_master,
+            config.signer.as_ref().and_then(|s| s.publickeys.as_ref()),
+        ) {
+            if index >= pubkeys.len() {
+                problems.push(ConfigProblem::new(
+                    "general.fixed_master",
+                    format!(
+                        "must be less than the number of public keys. fixed_master: {}, publickeys: {}",
+                        index,
+                        pubkeys.len()
+                    ),
+                ));
+            }
+        }
+        if let (Some(quorum), Some(signer)) = (general.collection_quorum, config.signer.as_ref()) {
+            let threshold = signer.threshold.unwrap_or_default();
+            let signers = signer
+                .publickeys
+                .as_ref()
+                .map_or(0, |pubkeys| pubkeys.len());
+            if quorum < threshold || quorum as usize > signers {
+                problems.push(ConfigProblem::new(
+                    "general.collection_quorum",
+                    format!(
+                        "must be threshold to the number of public keys. collection_quorum: {}, threshold: {}, publickeys: {}",
+                        quorum, threshold, signers
+                    ),
+                ));
+            }
+        }
+        if general.max_concurrent_rounds == Some(0) {
+            problems.push(ConfigProblem::new(
+                "general.max_concurrent_rounds",
+                "must be 1 or more.".to_string(),
+            ));
+        }
+        if let Some(ref variant) = general.sighash_variant {
+            if let Err(e) = SighashVariant::from_str(variant) {
+                problems.push(ConfigProblem::new(
+                    "general.sighash_variant",
+                    format!("{:?}", e),
+                ));
+            }
+        }
+        if let Some(ref election) = general.leader_election {
+            if let Err(e) = ElectionStrategy::from_str(election) {
+                problems.push(ConfigProblem::new(
+                    "general.leader_election",
+                    format!("{:?}", e),
+                ));
+            }
+        }
+        let color_ids = [
+            ("general.allowed_color_ids", &general.allowed_color_ids),
+            ("general.denied_color_ids", &general.denied_color_ids),
+        ];
+        for (key, ids) in color_ids.iter() {
+            for id in ids.iter().flat_map(|ids| ids.iter()) {
+                if ColorId::from_str(id).is_err() {
+                    problems.push(ConfigProblem::new(
+                        key,
+                        format!("'{}' is invalid color id.", id),
+                    ));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Host must be a host name or IP address, not URL.
+fn invalid_host(key: &str, host: &Option<String>) -> Option<ConfigProblem> {
+    let host = host.as_ref()?;
+    if host.is_empty() || host.contains("://") || host.contains('/') || host.contains(' ') {
+        Some(ConfigProblem::new(
+            key,
+            format!("'{}' is invalid host. Set host name or IP address.", host),
+        ))
+    } else {
+        None
+    }
+}
+
+fn invalid_port(key: &str, port: Option<u32>) -> Option<ConfigProblem> {
+    match port {
+        Some(port) if port == 0 || port > 65535 => Some(ConfigProblem::new(
+            key,
+            format!("{} is invalid port.", port),
+        )),
+        _ => None,
+    }
+}
+
 /// command example:
 /// ./target/debug/node -p=03831a69b8009833ab5b0326012eaf489bfea35a7321b1ca15b11d88131423fafc -p=02ce7edc292d7b747fab2f23584bbafaffde5c8ff17cf689969614441e0527b900 -p=02785a891f323acd6cef0fc509bb14304410595914267c50467e51c87142acbb5e --privatekey=cUwpWhH9CbYwjUWzfz1UVaSjSQm9ALXWRqeFFiZKnn8cV6wqNXQA -t 2 --master
 pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
     App::new("node")
         .about("Tapyrus siner node")
+        .after_help("EXAMPLES:\n    Launch one of the signers of 3 of 5 signer network. Specify -p for each public key.\n    node -p=<public key 1> -p=<public key 2> -p=<public key 3> -p=<public key 4> -p=<public key 5> \\\n        --privatekey=<WIF private key> -t 3 --rpcport=12381 --rpcuser=user --rpcpass=pass\n\n    Launch with config file.\n    node -c signer_config.toml")
         .arg(Arg::with_name(OPTION_NAME_CONFIG)
             .short("c")
             .long("config")
@@ -409,10 +1440,27 @@ pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
         .arg(Arg::with_name(OPTION_NAME_PRIVATE_KEY)
             .long("privatekey")
             .value_name("PRIVATE_KEY")
-            .help("The PrivateKey of this signer node. WIF format."))
+            .multiple(true)
+            .number_of_values(1)
+            .help("The PrivateKey of this signer node. WIF format. When specified multiple times, the key paired with one of the public keys is used."))
         .arg(Arg::with_name(OPTION_NAME_MASTER_FLAG)
             .long("master")
             .help("Master Node Flag. If launch as Master node, then set this option."))
+        .arg(Arg::with_name(OPTION_NAME_OBSERVER)
+            .long("observer")
+            .conflicts_with(OPTION_NAME_MASTER_FLAG)
+            .help("Observer mode. The node follows rounds and verifies blocks and signatures, but never signs. Private key is not needed."))
+        .arg(Arg::with_name(OPTION_NAME_SIGN_ONCE)
+            .long("sign-once")
+            .conflicts_with_all(&[OPTION_NAME_MASTER_FLAG, OPTION_NAME_OBSERVER])
+            .help("Sign one candidate block as a member and exit. It exits with non-zero code if no valid candidate block arrived within the round time limit. The node never becomes master in this mode."))
+        .arg(Arg::with_name(OPTION_NAME_ALLOW_NO_FAULT_TOLERANCE)
+            .long("allow-no-fault-tolerance")
+            .help("Don't warn on startup that threshold equals the number of signers. In such federation, losing any one signer halts block production."))
+        .arg(Arg::with_name(OPTION_NAME_EPHEMERAL_KEY)
+            .long("ephemeral-key")
+            .conflicts_with_all(&[OPTION_NAME_PRIVATE_KEY, OPTION_NAME_OBSERVER])
+            .help("Generate a new private key on startup and print its public key, for tests. The node exits if the public key isn't in the public key list. Not available in the build with production feature."))
         .arg(Arg::with_name(OPTION_NAME_RPC_ENDPOINT_HOST)
             .long("rpchost")
             .value_name("HOST_NAME or IP")
@@ -429,6 +1477,49 @@ pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
             .long("rpcpass")
             .value_name("PASS")
             .help("TapyrusCore RPC user password."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_TIMEOUT)
+            .long("rpctimeout")
+            .value_name("SECs")
+            .help("Time limit for receiving the whole response of TapyrusCore RPC. default is 30 sec."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_CONNECT_TIMEOUT)
+            .long("rpcconnecttimeout")
+            .value_name("SECs")
+            .help("Time limit for connecting to TapyrusCore RPC server. It is separate from rpctimeout, so that slow block validation can be allowed while an unreachable server fails fast. default is 5 sec."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_MAX_RESPONSE_SIZE)
+            .long("rpcmaxresponsesize")
+            .value_name("BYTES")
+            .help("Maximum size of TapyrusCore RPC response. default is 33554432 bytes(32MiB)."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_RETRIES)
+            .long("rpcretries")
+            .value_name("NUM")
+            .help("Count of retries of TapyrusCore RPC call which failed with timeout, connection error or 5xx status. Only calls which read the chain are retried, within the RPC timeout. default is 2."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_RETRY_BACKOFF)
+            .long("rpcretrybackoff")
+            .value_name("MILLISECs")
+            .help("Wait(msec) before the first retry of TapyrusCore RPC call. It doubles on each retry. default is 500."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_TLS)
+            .long("rpc-tls")
+            .help("Connect to TapyrusCore RPC with https. The signer must be built with `tls` feature."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_VERIFY_PERMISSIONS)
+            .long("rpc-verify-permissions")
+            .help("Check on startup that TapyrusCore RPC user can call all RPC methods which the signer needs."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_MIN_CORE_VERSION)
+            .long("rpc-min-core-version")
+            .takes_value(true)
+            .value_name("VERSION")
+            .help("Minimum version of TapyrusCore, in the format of `version` of getnetworkinfo RPC. The signer refuses to start with older TapyrusCore. default is 10000(v0.1.0). Lower it only if you know the older version is compatible."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_CA_CERT)
+            .long("rpc-ca-cert")
+            .value_name("PATH")
+            .help("CA certificate file(PEM) to verify the TapyrusCore RPC server certificate. It implies --rpc-tls."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_CLIENT_CERT)
+            .long("rpc-client-cert")
+            .value_name("PATH")
+            .help("Client certificate and private key file(PKCS#12) for TapyrusCore RPC. It implies --rpc-tls."))
+        .arg(Arg::with_name(OPTION_NAME_RPC_CLIENT_CERT_PASS)
+            .long("rpc-client-cert-pass")
+            .value_name("PASSWORD")
+            .help("Password of the client certificate file."))
         .arg(Arg::with_name(OPTION_NAME_REDIS_HOST)
             .long("redishost")
             .value_name("HOST_NAME or IP")
@@ -437,6 +1528,14 @@ pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
             .long("redisport")
             .value_name("PORT")
             .help("Redis port."))
+        .arg(Arg::with_name(OPTION_NAME_REDIS_MESSAGE_TTL)
+            .long("redis-message-ttl")
+            .takes_value(true)
+            .value_name("MILLISECs")
+            .help("Received messages which were sent more than this time(msec) ago are dropped as stale, like messages of a crashed node. Clocks of the signers must be synchronized. 0 means messages never expire. default is the length of a round, round duration and round time limit."))
+        .arg(Arg::with_name(OPTION_NAME_REDIS_ACCEPT_UNTIMED_MESSAGES)
+            .long("redis-accept-untimed-messages")
+            .help("Accept messages without timestamp, whose age can't be checked by --redis-message-ttl. Only for the senders which don't put timestamp. Signed messages always have it."))
         .arg(Arg::with_name(OPTION_NAME_LOG_QUIET)
             .long("quiet")
             .short("q")
@@ -454,6 +1553,146 @@ pub fn get_options<'a, 'b>() -> clap::App<'a, 'b> {
             .takes_value(true)
             .value_name("SECs")
             .help("Round interval times(sec)."))
+        .arg(Arg::with_name(OPTION_NAME_MAX_ROUND_ATTEMPTS)
+            .long("max-round-attempts")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Number of rounds tried for a block height with the normal round time limit. When all of these rounds failed, the time limit of the next rounds doubles on each failed round, up to 8 times."))
+        .arg(Arg::with_name(OPTION_NAME_TIMEOUT_JITTER)
+            .long("timeout-jitter")
+            .takes_value(true)
+            .value_name("MILLISECs")
+            .help("Max random jitter(msec) added to round timeout. It staggers master rotation of nodes."))
+        .arg(Arg::with_name(OPTION_NAME_MAX_MISSED_ROUNDS)
+            .long("max-missed-rounds")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Stop waiting for the signer to meet the collection quorum after this node as master produced or timed out this number of rounds in a row without its signature, until it sends a signature again. Master always waits for threshold signers. default is 0, it means never excluded."))
+        .arg(Arg::with_name(OPTION_NAME_WATCHDOG_ROUNDS)
+            .long("watchdog-rounds")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Reset the state machine when it neither changes its state nor times out the round for this number of round time limits. default is 3. 0 disables it."))
+        .arg(Arg::with_name(OPTION_NAME_FIXED_MASTER)
+            .long("fixed-master")
+            .takes_value(true)
+            .value_name("INDEX")
+            .help("Signer index of the master of all rounds. Master doesn't rotate, so rounds don't proceed while the node is down."))
+        .arg(Arg::with_name(OPTION_NAME_MAX_CONCURRENT_ROUNDS)
+            .long("max-concurrent-rounds")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Number of rounds which the node signs at the same time. Candidate blocks from other masters are dropped while this number of rounds are in flight. default is no limit."))
+        .arg(Arg::with_name(OPTION_NAME_COLLECTION_QUORUM)
+            .long("collection-quorum")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Number of signatures which master waits for before combining them. It must be threshold or more. If the round time limit is reached with threshold signatures, master combines them. default is threshold."))
+        .arg(Arg::with_name(OPTION_NAME_LATE_SIGNATURE_GRACE)
+            .long("late-signature-grace")
+            .takes_value(true)
+            .value_name("MILLISECs")
+            .help("Time(msec) after the block is produced, in which master counts late signatures for it in participation metrics. The block isn't changed. default is 0, it means late signatures are ignored."))
+        .arg(Arg::with_name(OPTION_NAME_READINESS_WINDOW)
+            .long("readiness-window")
+            .takes_value(true)
+            .value_name("MILLISECs")
+            .help("Master announces the candidate block and waits this time(msec) for threshold signers to be ready, before broadcasting it. All signers must enable it together. default is 0, it means readiness isn't checked."))
+        .arg(Arg::with_name(OPTION_NAME_DETECT_EQUIVOCATION)
+            .long("detect-equivocation")
+            .help("Members tell each other the hash of the candidate block, and abort the round when the master proposed different blocks. All signers must enable it together."))
+        .arg(Arg::with_name(OPTION_NAME_POLL_INTERVAL)
+            .long("poll-interval")
+            .takes_value(true)
+            .value_name("MILLISECs")
+            .help("Interval(msec) of requesting candidate block again on master, when Tapyrus Core failed to create it. default is 1000, minimum is 100."))
+        .arg(Arg::with_name(OPTION_NAME_SUBMIT_CONFIRMATION_TIMEOUT)
+            .long("submit-confirmation-timeout")
+            .takes_value(true)
+            .value_name("MILLISECs")
+            .help("After submitblock, master polls Tapyrus Core every poll interval until the block becomes the best block, up to this time(msec), and logs whether it was accepted. default is 0, it means the submission isn't confirmed."))
+        .arg(Arg::with_name(OPTION_NAME_STARTUP_TIMEOUT)
+            .long("startup-timeout")
+            .takes_value(true)
+            .value_name("SECs")
+            .help("Exit with error if connecting to RPC and redis and validating the federation on startup don't finish in this time(sec). default is 0, it means no limit."))
+        .arg(Arg::with_name(OPTION_NAME_DUMP_METRICS)
+            .long("dump-metrics")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Write metrics snapshot as JSON to the file at the end of each round. It is for collecting metrics by cron without Prometheus."))
+        .arg(Arg::with_name(OPTION_NAME_EVENTS_FILE)
+            .long("events-file")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Append the outcome of each round as a JSON line to the file. \"-\" means stderr."))
+        .arg(Arg::with_name(OPTION_NAME_LAST_SIGNED_HEIGHT_FILE)
+            .long("last-signed-height-file")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Write the height of the block which the node signed to the file at the end of each round. On startup, the node warns if the chain tip is behind it or far ahead of it."))
+        .arg(Arg::with_name(OPTION_NAME_ZMQ_ENDPOINT)
+            .long("zmq-endpoint")
+            .takes_value(true)
+            .value_name("tcp://HOST:PORT")
+            .help("ZMQ endpoint of Tapyrus Core set by -zmqpubhashblock. On a new block, master requests candidate block without waiting for the next poll, or starts the next round if its candidate block is stale. The signer must be built with `zmq` feature."))
+        .arg(Arg::with_name(OPTION_NAME_ADMIN_SOCKET)
+            .long("admin-socket")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("Unix domain socket which serves the status of the node, like round state and master index, as JSON. It is read-only."))
+        .arg(Arg::with_name(OPTION_NAME_STATSD_ADDR)
+            .long("statsd-addr")
+            .takes_value(true)
+            .value_name("HOST:PORT")
+            .help("StatsD server which the metrics are pushed to over UDP. If the server is unreachable, the metrics are dropped."))
+        .arg(Arg::with_name(OPTION_NAME_METRICS_ADDR)
+            .long("metrics-addr")
+            .takes_value(true)
+            .value_name("HOST:PORT")
+            .help("Address of the HTTP listener which serves the metrics in Prometheus text format on /metrics."))
+        .arg(Arg::with_name(OPTION_NAME_STATSD_INTERVAL)
+            .long("statsd-interval")
+            .takes_value(true)
+            .value_name("MILLISECs")
+            .help("Interval(msec) of pushing the metrics to StatsD. default is 10000."))
+        .arg(Arg::with_name(OPTION_NAME_SIGHASH_VARIANT)
+            .long("sighash-variant")
+            .takes_value(true)
+            .value_name("VARIANT")
+            .help("Variant of the message which signers sign for the block. Only \"header\" is supported now. default is \"header\"."))
+        .arg(Arg::with_name(OPTION_NAME_LEADER_ELECTION)
+            .long("leader-election")
+            .takes_value(true)
+            .possible_values(&["round-robin", "hash-based"])
+            .help("Rule to elect master of each round. All signers must use the same rule. default is \"round-robin\"."))
+        .arg(Arg::with_name(OPTION_NAME_NETWORK)
+            .long("network")
+            .takes_value(true)
+            .possible_values(&["mainnet", "testnet", "regtest"])
+            .help("Network of Tapyrus Core. If specified, the private key must be WIF for the network."))
+        .arg(Arg::with_name(OPTION_NAME_ALLOWED_COLOR_IDS)
+            .long("allow-color-id")
+            .value_name("COLOR_ID")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Color id which is allowed in candidate blocks. If specified, the node refuses to sign blocks which have outputs of other color ids."))
+        .arg(Arg::with_name(OPTION_NAME_DENIED_COLOR_IDS)
+            .long("deny-color-id")
+            .value_name("COLOR_ID")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Color id which is denied in candidate blocks. The node refuses to sign blocks which have outputs of the color id."))
+        .arg(Arg::with_name(OPTION_NAME_MAX_BLOCK_SIZE)
+            .long("max-block-size")
+            .takes_value(true)
+            .value_name("BYTES")
+            .help("The node refuses to sign candidate blocks larger than this size(bytes), not including the signatures. All signers must use the same value."))
+        .arg(Arg::with_name(OPTION_NAME_MAX_BLOCK_WEIGHT)
+            .long("max-block-weight")
+            .takes_value(true)
+            .value_name("WEIGHT")
+            .help("The node refuses to sign candidate blocks heavier than this weight, as defined in BIP141. All signers must use the same value."))
         .arg(Arg::with_name(OPTION_NAME_SKIP_WAITING_IBD)
             .long("skip-waiting-ibd")
             .help("This flag make signer node don't waiting connected Tapyrus full node finishes Initial Block Download when signer node started. When block creation stopped much time, The status of Tapyrus full node changes to progressing Initial Block Download. In this case, block creation is never resume, because signer node waits the status is back to non-IBD. So you can use this flag to start signer node with ignore tapyrus full node status."))
@@ -507,9 +1746,10 @@ fn test_load_from_file() {
     let threshold = args.signer_config().threshold();
     assert_eq!(threshold, 2);
 
-    let privkey = args.signer_config().private_key();
+    let privkeys = args.signer_config().private_keys();
+    assert_eq!(privkeys.len(), 1);
     assert_eq!(
-        privkey.to_wif(),
+        privkeys[0].to_wif(),
         "cMtJPWz8D1KmTseJa778nWTS93uePrrN5FtUARUZHu7RsjuSTjGX"
     );
 
@@ -518,16 +1758,81 @@ fn test_load_from_file() {
     assert_eq!(args.rpc_config().port(), 12381);
     assert_eq!(args.rpc_config().user_name(), Some("user"));
     assert_eq!(args.rpc_config().password(), Some("pass"));
+    assert_eq!(args.rpc_config().timeout(), 10);
+    assert_eq!(args.rpc_config().connect_timeout(), 2);
+    assert_eq!(args.rpc_config().max_response_size(), 1048576);
+    assert_eq!(args.rpc_config().retries(), 3);
+    assert_eq!(args.rpc_config().retry_backoff(), 200);
+    assert!(!args.rpc_config().tls());
+    assert!(args.rpc_config().verify_permissions());
+    assert_eq!(args.rpc_config().min_core_version(), 10000);
 
     // redis parameters are loaded from toml data.
     assert_eq!(args.redis_config().host(), "192.168.0.63");
     assert_eq!(args.redis_config().port(), 16379);
+    assert_eq!(args.redis_config().message_ttl(), Some(70000));
+    assert!(!args.redis_config().accept_untimed_messages());
 
     // general parameters are loaded from toml data.
     assert_eq!(args.general_config().round_duration(), 5);
+    assert_eq!(args.general_config().max_round_attempts(), 3);
+    assert_eq!(args.general_config().timeout_jitter(), 500);
+    assert_eq!(args.general_config().max_missed_rounds(), 3);
+    assert_eq!(args.general_config().watchdog_rounds(), 5);
+    assert_eq!(args.general_config().fixed_master(), None);
+    assert_eq!(args.general_config().max_concurrent_rounds(), Some(1));
+    assert_eq!(args.general_config().collection_quorum(), Some(3));
+    assert_eq!(args.general_config().late_signature_grace(), 2000);
+    assert_eq!(args.general_config().readiness_window(), 1000);
+    assert!(args.general_config().detect_equivocation());
+    assert!(!args.general_config().sign_once());
+    assert!(args.general_config().allow_no_fault_tolerance());
+    assert_eq!(args.general_config().poll_interval(), 2000);
+    assert_eq!(args.general_config().submit_confirmation_timeout(), 3000);
+    assert_eq!(args.general_config().startup_timeout(), 30);
+    assert_eq!(
+        args.general_config().dump_metrics(),
+        Some(PathBuf::from("/var/lib/tapyrus-signer/metrics.json"))
+    );
+    assert_eq!(
+        args.general_config().events_file(),
+        Some(PathBuf::from("/var/log/tapyrus-signer/rounds.jsonl"))
+    );
+    assert_eq!(
+        args.general_config().last_signed_height_file(),
+        Some(PathBuf::from("/var/lib/tapyrus-signer/last_signed_height"))
+    );
+    assert_eq!(
+        args.general_config().zmq_endpoint(),
+        Some("tcp://127.0.0.1:28332")
+    );
+    assert_eq!(args.general_config().statsd_addr(), Some("127.0.0.1:8125"));
+    assert_eq!(args.general_config().metrics_addr(), Some("127.0.0.1:9184"));
+    assert_eq!(args.general_config().statsd_interval(), 5000);
+    assert_eq!(
+        args.general_config().admin_socket(),
+        Some(PathBuf::from("/var/run/tapyrus-signer/admin.sock"))
+    );
+    assert_eq!(
+        args.general_config().leader_election().unwrap(),
+        ElectionStrategy::HashBased
+    );
+    assert_eq!(
+        args.general_config().sighash_variant().unwrap(),
+        SighashVariant::Header
+    );
     assert_eq!(args.general_config().log_level(), "debug");
     assert_eq!(args.general_config().log_quiet(), true);
     assert_eq!(args.general_config().master(), true);
+    assert_eq!(args.general_config().network(), Some(Network::Testnet));
+    let policy = args.general_config().block_policy();
+    assert_eq!(policy.allowed_color_ids, None);
+    assert_eq!(
+        policy.denied_color_ids[0].to_string(),
+        "c1ec2fd806701a3f55808cbec3922c38dafaa3070c48c803e9043ee3642c660b46"
+    );
+    assert_eq!(policy.max_block_size, Some(1000000));
+    assert_eq!(policy.max_block_weight, Some(4000000));
 }
 
 #[test]
@@ -543,6 +1848,9 @@ fn test_priority_commandline() {
         "--rpcport=12345",
         "--rpcuser=test",
         "--rpcpass=test",
+        "--rpctimeout=3",
+        "--rpcmaxresponsesize=2048",
+        "--rpcretries=0",
         "--redishost=redis.endpoint.dev.chaintope.com",
         "--redisport=88888",
     ]);
@@ -560,9 +1868,10 @@ fn test_priority_commandline() {
     let threshold = args.signer_config().threshold();
     assert_eq!(threshold, 1);
 
-    let privkey = args.signer_config().private_key();
+    let privkeys = args.signer_config().private_keys();
+    assert_eq!(privkeys.len(), 1);
     assert_eq!(
-        privkey.to_wif(),
+        privkeys[0].to_wif(),
         "L4Bw5GTJXL7Nd5wjprXim2sMpNgTSieZ14FCaHax7zzRnHbx19sc"
     );
 
@@ -571,6 +1880,9 @@ fn test_priority_commandline() {
     assert_eq!(args.rpc_config().port(), 12345);
     assert_eq!(args.rpc_config().user_name(), Some("test"));
     assert_eq!(args.rpc_config().password(), Some("test"));
+    assert_eq!(args.rpc_config().timeout(), 3);
+    assert_eq!(args.rpc_config().max_response_size(), 2048);
+    assert_eq!(args.rpc_config().retries(), 0);
 
     // redis parameters are loaded from toml data.
     assert_eq!(
@@ -625,7 +1937,46 @@ fn test_no_private_key() {
         matches,
         config: Some(ConfigToml::default()),
     };
-    let _privkey = args.signer_config().private_key();
+    let _privkeys = args.signer_config().private_keys();
+}
+
+#[test]
+fn test_multiple_private_keys() {
+    let matches = get_options().get_matches_from(vec![
+        "node",
+        "--privatekey=cUwpWhH9CbYwjUWzfz1UVaSjSQm9ALXWRqeFFiZKnn8cV6wqNXQA",
+        "--privatekey=cTRkG8i8PP7imvryqQwcYm787WHRdMmUqBvi1Z456gHvVoKnJ9TK",
+    ]);
+    let args = CommandArgs {
+        matches,
+        config: None,
+    };
+    let privkeys = args.signer_config().private_keys();
+    assert_eq!(privkeys.len(), 2);
+    assert_eq!(
+        privkeys[1].to_wif(),
+        "cTRkG8i8PP7imvryqQwcYm787WHRdMmUqBvi1Z456gHvVoKnJ9TK"
+    );
+
+    // toml config lists keys in `privatekey` and `privatekeys`.
+    let matches = get_options().get_matches_from(vec!["node"]);
+    let args = CommandArgs {
+        matches,
+        config: Some(ConfigToml {
+            signer: Some(SignerToml {
+                publickeys: None,
+                threshold: None,
+                privatekey: Some(
+                    "cUwpWhH9CbYwjUWzfz1UVaSjSQm9ALXWRqeFFiZKnn8cV6wqNXQA".to_string(),
+                ),
+                privatekeys: Some(vec![
+                    "cTRkG8i8PP7imvryqQwcYm787WHRdMmUqBvi1Z456gHvVoKnJ9TK".to_string()
+                ]),
+            }),
+            ..ConfigToml::default()
+        }),
+    };
+    assert_eq!(args.signer_config().private_keys().len(), 2);
 }
 
 #[test]
@@ -639,9 +1990,119 @@ fn test_invalid_private_key() {
                 publickeys: None,
                 threshold: Some(0),
                 privatekey: Some("aabbccdd".to_string()),
+                privatekeys: None,
             }),
             ..ConfigToml::default()
         }),
     };
-    let _privkey = args.signer_config().private_key();
+    let _privkeys = args.signer_config().private_keys();
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn test_tls_options() {
+    let matches = get_options().get_matches_from(vec![
+        "node",
+        "-c=tests/resources/signer_config.toml",
+        "--rpc-ca-cert=/etc/ssl/tapyrus-ca.pem",
+    ]);
+    let args = CommandArgs::load(matches).unwrap();
+    assert!(args.rpc_config().tls());
+    assert_eq!(
+        args.rpc_config().tls_config().ca_cert,
+        Some(PathBuf::from("/etc/ssl/tapyrus-ca.pem"))
+    );
+}
+
+#[test]
+#[cfg(not(feature = "tls"))]
+fn test_tls_options_without_tls_feature() {
+    for option in &["--rpc-tls", "--rpc-ca-cert=/etc/ssl/tapyrus-ca.pem"] {
+        let matches = get_options().get_matches_from(vec![
+            "node",
+            "-c=tests/resources/signer_config.toml",
+            option,
+        ]);
+        match CommandArgs::load(matches) {
+            Err(crate::errors::Error::InvalidArgs(_)) => {}
+            Err(e) => panic!("Should be InvalidArgs, but {:?}", e),
+            Ok(_) => panic!("Should be InvalidArgs, but loaded. option: {}", option),
+        }
+    }
+
+    let keys: Vec<String> = validate_config(
+        "[rpc]\nrpc_tls = true\nrpc_ca_cert = \"ca.pem\"\nrpc_client_cert_pass = \"pass\"\n",
+    )
+    .into_iter()
+    .map(|p| p.key)
+    .collect();
+    assert_eq!(
+        keys,
+        vec!["rpc.rpc_tls", "rpc.rpc_ca_cert", "rpc.rpc_client_cert_pass"]
+    );
+}
+
+#[test]
+fn test_unknown_sighash_variant() {
+    let matches = get_options().get_matches_from(vec![
+        "node",
+        "-c=tests/resources/signer_config.toml",
+        "--sighash-variant=schnorr",
+    ]);
+    let args = CommandArgs::load(matches).unwrap();
+    match args.general_config().sighash_variant() {
+        Err(crate::errors::Error::InvalidArgs(_)) => {}
+        r => panic!("Should be InvalidArgs, but {:?}", r),
+    }
+}
+
+#[test]
+fn test_debug_does_not_leak_secrets() {
+    let config = read_config("tests/resources/signer_config_sample.toml").unwrap();
+    let debug = format!("{:?}", config);
+    assert!(debug.contains("rpc_endpoint_user: Some(\"user\")"));
+    assert!(!debug.contains("cMtJPWz8D1KmTseJa778nWTS93uePrrN5FtUARUZHu7RsjuSTjGX"));
+    assert!(!debug.contains("\"pass\""));
+}
+
+#[test]
+fn test_validate_config() {
+    let contents = std::fs::read_to_string("tests/resources/signer_config_sample.toml").unwrap();
+    assert_eq!(validate_config(&contents), vec![]);
+}
+
+#[test]
+fn test_validate_config_problems() {
+    let contents = r#"
+[signer]
+publickeys = ["033cfe7fa1be58191b9108883543e921d31dc7726e051ee773e0ea54786ce438f8"]
+threshold = 2
+
+[rpc]
+rpc_endpoint_host = "http://localhost"
+rpc_endpont_port = 12381
+
+[general]
+network = "signet"
+"#;
+    let keys: Vec<String> = validate_config(contents)
+        .into_iter()
+        .map(|p| p.key)
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            "rpc.rpc_endpont_port",
+            "signer.threshold",
+            "rpc.rpc_endpoint_host",
+            "general.network"
+        ]
+    );
+}
+
+#[test]
+fn test_validate_config_type_error() {
+    let problems = validate_config("[signer]\nthreshold = \"two\"\n");
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].key, "");
 }
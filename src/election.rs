@@ -0,0 +1,150 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Rules to elect master of each round. All signers must use the same rule, otherwise
+//! multiple masters appear in a round.
+
+use crate::errors::Error;
+use bitcoin::PublicKey;
+use bitcoin_hashes::{sha256, Hash};
+use std::str::FromStr;
+
+pub trait LeaderElection {
+    /// Signer index of the master of `round`. `signers` are public keys in signer index order.
+    fn master_index(&self, round: u64, signers: &[PublicKey]) -> usize;
+}
+
+/// Each signer becomes master in turn of signer index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundRobin;
+
+impl LeaderElection for RoundRobin {
+    fn master_index(&self, round: u64, signers: &[PublicKey]) -> usize {
+        (round % signers.len() as u64) as usize
+    }
+}
+
+/// The signer whose `SHA256(round || public key)` is the smallest becomes master. The round is
+/// 8 bytes big endian and the public key is compressed form. The order of masters is
+/// unpredictable, but it is same on all signers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashBased;
+
+impl LeaderElection for HashBased {
+    fn master_index(&self, round: u64, signers: &[PublicKey]) -> usize {
+        signers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, pubkey)| {
+                let mut data = round.to_be_bytes().to_vec();
+                data.extend_from_slice(&pubkey.key.serialize());
+                sha256::Hash::hash(&data).into_inner()
+            })
+            .map(|(index, _)| index)
+            .expect("signers must not be empty.")
+    }
+}
+
+/// Leader election rule selected by `--leader-election`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ElectionStrategy {
+    #[default]
+    RoundRobin,
+    HashBased,
+}
+
+impl LeaderElection for ElectionStrategy {
+    fn master_index(&self, round: u64, signers: &[PublicKey]) -> usize {
+        match self {
+            ElectionStrategy::RoundRobin => RoundRobin.master_index(round, signers),
+            ElectionStrategy::HashBased => HashBased.master_index(round, signers),
+        }
+    }
+}
+
+impl FromStr for ElectionStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(ElectionStrategy::RoundRobin),
+            "hash-based" => Ok(ElectionStrategy::HashBased),
+            _ => Err(Error::InvalidArgs(format!(
+                "'{}' is unknown leader election.",
+                s
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helper::TestKeys;
+
+    /// Masters of the rounds elected by each of the signers, which have the same public keys.
+    fn elect_on_each_signer<E: LeaderElection>(election: E) -> Vec<Vec<usize>> {
+        let signers = TestKeys::new().pubkeys();
+        (0..signers.len())
+            .map(|_| {
+                (0..100)
+                    .map(|round| election.master_index(round, &signers))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let masters = elect_on_each_signer(RoundRobin);
+        assert!(masters.iter().all(|m| m == &masters[0]));
+        assert_eq!(&masters[0][..7], &[0, 1, 2, 3, 4, 0, 1]);
+    }
+
+    #[test]
+    fn test_hash_based() {
+        let masters = elect_on_each_signer(HashBased);
+        assert!(masters.iter().all(|m| m == &masters[0]));
+        // every signer becomes master, not in turn.
+        for index in 0..5 {
+            assert!(masters[0].contains(&index));
+        }
+        assert_ne!(masters[0], elect_on_each_signer(RoundRobin)[0]);
+
+        let signers = TestKeys::new().pubkeys();
+        let mut data = 7u64.to_be_bytes().to_vec();
+        let hashes: Vec<[u8; 32]> = signers
+            .iter()
+            .map(|pubkey| {
+                data.truncate(8);
+                data.extend_from_slice(&pubkey.key.serialize());
+                sha256::Hash::hash(&data).into_inner()
+            })
+            .collect();
+        let min = hashes.iter().min().unwrap();
+        assert_eq!(&hashes[HashBased.master_index(7, &signers)], min);
+    }
+
+    #[test]
+    fn test_election_strategy() {
+        let signers = TestKeys::new().pubkeys();
+        assert_eq!(ElectionStrategy::default(), ElectionStrategy::RoundRobin);
+        let strategy = ElectionStrategy::from_str("hash-based").unwrap();
+        assert_eq!(strategy, ElectionStrategy::HashBased);
+        for round in 0..10 {
+            assert_eq!(
+                strategy.master_index(round, &signers),
+                HashBased.master_index(round, &signers)
+            );
+            assert_eq!(
+                ElectionStrategy::RoundRobin.master_index(round, &signers),
+                RoundRobin.master_index(round, &signers)
+            );
+        }
+        match ElectionStrategy::from_str("random") {
+            Err(Error::InvalidArgs(_)) => {}
+            r => panic!("Should be InvalidArgs, but {:?}", r),
+        }
+    }
+}
@@ -13,13 +13,181 @@ pub enum Error {
     BitcoinConsensusEncodeError(bitcoin::consensus::encode::Error),
     /// Errors cause sender side matter, like parameter was wrong.
     InvalidRequest(jsonrpc::error::RpcError),
+    /// Tapyrus Core didn't respond within the RPC timeout.
+    RpcTimeout,
+    /// Connection to Tapyrus Core wasn't established within the RPC connect timeout.
+    RpcConnectTimeout,
+    /// RPC response body exceeded the limit(bytes).
+    RpcResponseTooLarge(u64),
+    /// TLS connection to Tapyrus Core failed, like the server certificate is not trusted.
+    RpcTlsError(String),
+    /// Tapyrus Core responded 5xx status without RPC result.
+    RpcServerError(u16),
+    /// RPC user isn't allowed to call the method.
+    RpcMethodNotAllowed(String),
+    /// Tapyrus Core is older than the minimum version which the signer supports.
+    UnsupportedCoreVersion {
+        version: u64,
+        minimum: u64,
+    },
+    /// Data can't be parsed as a Tapyrus block.
+    InvalidBlock,
+    /// Candidate block has outputs of the color id which the policy doesn't allow.
+    DisallowedColorId(crate::policy::ColorId),
+    /// Candidate block violates the block policy, like it is larger than the limit.
+    BlockPolicyViolation(String),
     DuplicatedMessage,
+    /// Message was sent before the message TTL. Age(msec) of the message.
+    MessageExpired(u64),
+    /// Message has no timestamp, so its age can't be checked against the message TTL.
+    MessageWithoutTimestamp,
+    /// Message is for another round than the current round of the node, like a replayed
+    /// message of a past round. Height and round of the message.
+    UnexpectedRound {
+        height: u64,
+        round: u32,
+    },
+    /// Message isn't signed by the signer which it claims to be sent from.
+    MessageAuthFailed(bitcoin::PublicKey),
+    /// Message is encoded in the wire format of other version.
+    UnsupportedWireVersion(u32),
+    /// Master of the round proposed different blocks to members.
+    MasterEquivocation(bitcoin::PublicKey),
     InvalidSignature(secp256k1::Error),
-    TimerAlreadyStarted,
     InvalidTomlFormat(toml::de::Error),
     ConfigFileIOError(std::io::Error),
     InvalidPublicKeyFormat(String),
+    /// Federation token can't be decoded, like its checksum doesn't match.
+    InvalidToken(String),
+    /// Threshold must be 1 to the number of signers.
+    InvalidThreshold {
+        threshold: u8,
+        signers: usize,
+    },
+    /// Private key WIF is for another network.
+    NetworkMismatch {
+        expected: bitcoin::Network,
+        found: bitcoin::Network,
+    },
     RedisError(RedisError),
+    /// Random number generator is unavailable or its output doesn't look random.
+    InsufficientEntropy(String),
+}
+
+/// Machine names of all the errors, which `Error::kind_str` returns.
+pub const ALL_ERROR_KINDS: &[&str] = &[
+    "json_rpc",
+    "json",
+    "invalid_length",
+    "invalid_args",
+    "bitcoin_consensus_encode_error",
+    "invalid_request",
+    "rpc_timeout",
+    "rpc_connect_timeout",
+    "rpc_response_too_large",
+    "rpc_tls_error",
+    "rpc_server_error",
+    "rpc_method_not_allowed",
+    "unsupported_core_version",
+    "invalid_block",
+    "disallowed_color_id",
+    "block_policy_violation",
+    "duplicated_message",
+    "message_expired",
+    "message_without_timestamp",
+    "unexpected_round",
+    "message_auth_failed",
+    "unsupported_wire_version",
+    "master_equivocation",
+    "invalid_signature",
+    "invalid_toml_format",
+    "config_file_io_error",
+    "invalid_public_key_format",
+    "invalid_token",
+    "invalid_threshold",
+    "network_mismatch",
+    "redis_error",
+    "insufficient_entropy",
+];
+
+impl Error {
+    /// Stable machine name of the error, for documentation and dashboards. It doesn't change even
+    /// if the variant is renamed.
+    pub fn kind_str(&self) -> &'static str {
+        match self {
+            Error::JsonRpc(_) => "json_rpc",
+            Error::Json(_) => "json",
+            Error::InvalidLength(..) => "invalid_length",
+            Error::InvalidArgs(_) => "invalid_args",
+            Error::BitcoinConsensusEncodeError(_) => "bitcoin_consensus_encode_error",
+            Error::InvalidRequest(_) => "invalid_request",
+            Error::RpcTimeout => "rpc_timeout",
+            Error::RpcConnectTimeout => "rpc_connect_timeout",
+            Error::RpcResponseTooLarge(_) => "rpc_response_too_large",
+            Error::RpcTlsError(_) => "rpc_tls_error",
+            Error::RpcServerError(_) => "rpc_server_error",
+            Error::RpcMethodNotAllowed(_) => "rpc_method_not_allowed",
+            Error::UnsupportedCoreVersion { .. } => "unsupported_core_version",
+            Error::InvalidBlock => "invalid_block",
+            Error::DisallowedColorId(_) => "disallowed_color_id",
+            Error::BlockPolicyViolation(_) => "block_policy_violation",
+            Error::DuplicatedMessage => "duplicated_message",
+            Error::MessageExpired(_) => "message_expired",
+            Error::MessageWithoutTimestamp => "message_without_timestamp",
+            Error::UnexpectedRound { .. } => "unexpected_round",
+            Error::MessageAuthFailed(_) => "message_auth_failed",
+            Error::UnsupportedWireVersion(_) => "unsupported_wire_version",
+            Error::MasterEquivocation(_) => "master_equivocation",
+            Error::InvalidSignature(_) => "invalid_signature",
+            Error::InvalidTomlFormat(_) => "invalid_toml_format",
+            Error::ConfigFileIOError(_) => "config_file_io_error",
+            Error::InvalidPublicKeyFormat(_) => "invalid_public_key_format",
+            Error::InvalidToken(_) => "invalid_token",
+            Error::InvalidThreshold { .. } => "invalid_threshold",
+            Error::NetworkMismatch { .. } => "network_mismatch",
+            Error::RedisError(_) => "redis_error",
+            Error::InsufficientEntropy(_) => "insufficient_entropy",
+        }
+    }
+
+    /// HTTP status code for the error, for services which serve the signer over HTTP.
+    /// 400 for invalid input, 502 for errors of Tapyrus Core RPC and 500 for the others.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Error::InvalidLength(..)
+            | Error::InvalidArgs(_)
+            | Error::Json(_)
+            | Error::BitcoinConsensusEncodeError(_)
+            | Error::InvalidBlock
+            | Error::DisallowedColorId(_)
+            | Error::BlockPolicyViolation(_)
+            | Error::DuplicatedMessage
+            | Error::MessageExpired(_)
+            | Error::MessageWithoutTimestamp
+            | Error::UnexpectedRound { .. }
+            | Error::MessageAuthFailed(_)
+            | Error::UnsupportedWireVersion(_)
+            | Error::InvalidSignature(_)
+            | Error::InvalidTomlFormat(_)
+            | Error::InvalidPublicKeyFormat(_)
+            | Error::InvalidToken(_)
+            | Error::InvalidThreshold { .. }
+            | Error::NetworkMismatch { .. } => 400,
+            Error::JsonRpc(_)
+            | Error::InvalidRequest(_)
+            | Error::RpcTimeout
+            | Error::RpcConnectTimeout
+            | Error::RpcResponseTooLarge(_)
+            | Error::RpcTlsError(_)
+            | Error::RpcServerError(_)
+            | Error::RpcMethodNotAllowed(_)
+            | Error::UnsupportedCoreVersion { .. } => 502,
+            Error::MasterEquivocation(_)
+            | Error::ConfigFileIOError(_)
+            | Error::RedisError(_)
+            | Error::InsufficientEntropy(_) => 500,
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -36,6 +204,24 @@ impl From<jsonrpc::error::Error> for Error {
     }
 }
 
+impl From<hyper::Error> for Error {
+    fn from(e: hyper::Error) -> Error {
+        match e {
+            hyper::Error::Io(ref io) if matches!(io.get_ref(), Some(inner) if inner.is::<crate::rpc::ConnectTimeout>()) => {
+                Error::RpcConnectTimeout
+            }
+            hyper::Error::Io(ref io)
+                if io.kind() == std::io::ErrorKind::WouldBlock
+                    || io.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                Error::RpcTimeout
+            }
+            hyper::Error::Ssl(e) => Error::RpcTlsError(e.to_string()),
+            e => Error::JsonRpc(jsonrpc::error::Error::Hyper(e)),
+        }
+    }
+}
+
 impl From<serde_json::error::Error> for Error {
     fn from(e: serde_json::error::Error) -> Error {
         Error::Json(e)
@@ -64,3 +250,111 @@ impl From<RedisError> for Error {
         Error::RedisError(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::ColorId;
+    use crate::test_helper::TestKeys;
+
+    fn rpc_error() -> jsonrpc::error::RpcError {
+        jsonrpc::error::RpcError {
+            code: -1,
+            message: "error".to_string(),
+            data: None,
+        }
+    }
+
+    fn bad_request_errors() -> Vec<Error> {
+        let pubkey = TestKeys::new().pubkeys()[0];
+        vec![
+            Error::InvalidLength(32, 31),
+            Error::InvalidArgs("invalid".to_string()),
+            Error::Json(serde_json::from_str::<u8>("x").unwrap_err()),
+            Error::BitcoinConsensusEncodeError(bitcoin::consensus::encode::Error::ParseFailed(
+                "invalid",
+            )),
+            Error::InvalidBlock,
+            Error::DisallowedColorId(ColorId::from_slice(&[0xc1; 33]).unwrap()),
+            Error::BlockPolicyViolation("too large".to_string()),
+            Error::DuplicatedMessage,
+            Error::MessageExpired(6000),
+            Error::MessageWithoutTimestamp,
+            Error::UnexpectedRound {
+                height: 10,
+                round: 1,
+            },
+            Error::MessageAuthFailed(pubkey),
+            Error::UnsupportedWireVersion(2),
+            Error::InvalidSignature(secp256k1::Error::IncorrectSignature),
+            Error::InvalidTomlFormat(toml::from_str::<toml::Value>("x").unwrap_err()),
+            Error::InvalidPublicKeyFormat("invalid".to_string()),
+            Error::InvalidToken("invalid".to_string()),
+            Error::InvalidThreshold {
+                threshold: 6,
+                signers: 5,
+            },
+            Error::NetworkMismatch {
+                expected: bitcoin::Network::Testnet,
+                found: bitcoin::Network::Bitcoin,
+            },
+        ]
+    }
+
+    fn bad_gateway_errors() -> Vec<Error> {
+        vec![
+            Error::JsonRpc(jsonrpc::error::Error::Rpc(rpc_error())),
+            Error::InvalidRequest(rpc_error()),
+            Error::RpcTimeout,
+            Error::RpcConnectTimeout,
+            Error::RpcResponseTooLarge(1024),
+            Error::RpcTlsError("untrusted".to_string()),
+            Error::RpcServerError(503),
+            Error::RpcMethodNotAllowed("getnewblock".to_string()),
+            Error::UnsupportedCoreVersion {
+                version: 9900,
+                minimum: 10000,
+            },
+        ]
+    }
+
+    fn internal_errors() -> Vec<Error> {
+        let pubkey = TestKeys::new().pubkeys()[0];
+        vec![
+            Error::MasterEquivocation(pubkey),
+            Error::ConfigFileIOError(std::io::Error::from(std::io::ErrorKind::NotFound)),
+            Error::RedisError(RedisError::from((redis::ErrorKind::IoError, "io"))),
+            Error::InsufficientEntropy("unavailable".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_http_status() {
+        for e in bad_request_errors() {
+            assert_eq!(e.http_status(), 400, "{:?}", e);
+        }
+        for e in bad_gateway_errors() {
+            assert_eq!(e.http_status(), 502, "{:?}", e);
+        }
+        for e in internal_errors() {
+            assert_eq!(e.http_status(), 500, "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn test_kind_str() {
+        let errors: Vec<Error> = bad_request_errors()
+            .into_iter()
+            .chain(bad_gateway_errors())
+            .chain(internal_errors())
+            .collect();
+        let kinds: Vec<&str> = errors.iter().map(Error::kind_str).collect();
+        let distinct: std::collections::HashSet<&str> = kinds.iter().cloned().collect();
+        assert_eq!(distinct.len(), errors.len(), "kind_str is duplicated.");
+        // the lists above have one error of each variant.
+        assert_eq!(kinds.len(), ALL_ERROR_KINDS.len());
+        for kind in kinds {
+            assert!(ALL_ERROR_KINDS.contains(&kind), "{} is not listed.", kind);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Sources of the private key which the signer node signs with.
+//!
+//! The node loads the key once on startup through `KeyStore`, and signs candidate blocks with it
+//! in process. A store which keeps the key on a device, like a PKCS#11 token, needs signing to
+//! be done on the device too, so it isn't supported by this trait yet.
+
+use crate::errors::Error;
+use crate::signer_node::select_private_key;
+use bitcoin::{PrivateKey, PublicKey};
+
+pub trait KeyStore {
+    /// Private key paired with one of `pubkey_list`, which are the public keys of the signer
+    /// network.
+    fn private_key(&self, pubkey_list: &[PublicKey]) -> Result<PrivateKey, Error>;
+}
+
+/// Private keys in the config file, or on the command line which overrides it. This is the
+/// default key store.
+pub struct FileKeyStore {
+    keys: Vec<PrivateKey>,
+}
+
+impl FileKeyStore {
+    pub fn new(keys: Vec<PrivateKey>) -> FileKeyStore {
+        FileKeyStore { keys }
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    /// Returns error if no key or several keys are paired. See `select_private_key`.
+    fn private_key(&self, pubkey_list: &[PublicKey]) -> Result<PrivateKey, Error> {
+        select_private_key(&self.keys, pubkey_list)
+    }
+}
+
+/// Private key generated on startup for tests, which is thrown away when the node stops.
+pub struct EphemeralKeyStore {
+    key: PrivateKey,
+}
+
+impl EphemeralKeyStore {
+    pub fn new(key: PrivateKey) -> EphemeralKeyStore {
+        EphemeralKeyStore { key }
+    }
+}
+
+impl KeyStore for EphemeralKeyStore {
+    /// The public key list is the federation, so it is never changed for the ephemeral key.
+    /// Returns error unless the key is already in the list.
+    fn private_key(&self, pubkey_list: &[PublicKey]) -> Result<PrivateKey, Error> {
+        let public_key = self.key.public_key(&secp256k1::Secp256k1::new());
+        if !pubkey_list.contains(&public_key) {
+            return Err(Error::InvalidArgs(format!(
+                "Ephemeral public key {} is not in the public key list. Signers don't change the federation.",
+                public_key
+            )));
+        }
+        Ok(self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helper::TestKeys;
+
+    #[test]
+    fn test_file_key_store() {
+        let testkeys = TestKeys::new();
+        let pubkey_list = testkeys.pubkeys();
+        let store = FileKeyStore::new(vec![testkeys.key[1]]);
+        assert_eq!(store.private_key(&pubkey_list).unwrap(), testkeys.key[1]);
+        match store.private_key(&pubkey_list[2..]) {
+            Err(Error::InvalidArgs(_)) => {}
+            r => panic!("Should be InvalidArgs, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_ephemeral_key_store() {
+        let testkeys = TestKeys::new();
+        let pubkey_list = testkeys.pubkeys();
+        let store = EphemeralKeyStore::new(testkeys.key[0]);
+        assert_eq!(store.private_key(&pubkey_list).unwrap(), testkeys.key[0]);
+        match store.private_key(&pubkey_list[1..]) {
+            Err(Error::InvalidArgs(_)) => {}
+            r => panic!("Should be InvalidArgs, but {:?}", r),
+        }
+    }
+}
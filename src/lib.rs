@@ -9,15 +9,26 @@ extern crate log;
 extern crate base64;
 extern crate byteorder;
 extern crate hex;
+extern crate rand;
 extern crate redis;
 
 pub mod blockdata;
 pub mod command_args;
+pub mod election;
 pub mod errors;
+pub mod key_store;
+pub mod metrics;
 pub mod net;
+pub mod observer;
+pub mod policy;
+pub mod prometheus;
 pub mod rpc;
 pub mod serialize;
 pub mod sign;
 pub mod signer_node;
+pub mod statsd;
 pub mod test_helper;
-pub mod timer;
+pub mod token;
+pub mod wire;
+#[cfg(feature = "zmq")]
+pub mod zmq;
@@ -0,0 +1,293 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Lower bound of latency histogram buckets(msec).
+const HISTOGRAM_START_MILLIS: u64 = 10;
+
+/// Histogram which has exponential bucket bounds.
+/// Recording is lock free, so it can be called on the hot path of the signer node.
+pub struct Histogram {
+    /// Upper bounds of buckets(msec). The last bucket is for values over all bounds.
+    bounds: Vec<u64>,
+    buckets: Vec<AtomicU64>,
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HistogramSnapshot {
+    /// Upper bounds of buckets(msec).
+    pub bounds: Vec<u64>,
+    /// Count of each bucket, not cumulative. It has one more element than `bounds` for values
+    /// over all bounds.
+    pub counts: Vec<u64>,
+    /// Sum of all recorded values(msec).
+    pub sum: u64,
+    pub count: u64,
+}
+
+impl Histogram {
+    /// Create histogram whose bucket bounds are `start`, `start * 2`, `start * 4`, ... until it
+    /// covers `max`.
+    pub fn exponential(start: Duration, max: Duration) -> Histogram {
+        let start = (start.as_millis() as u64).max(1);
+        let max = max.as_millis() as u64;
+        let mut bounds = vec![start];
+        while *bounds.last().unwrap() < max {
+            let next = bounds.last().unwrap() * 2;
+            bounds.push(next);
+        }
+        let buckets = (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect();
+        Histogram {
+            bounds,
+            buckets,
+            sum: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: Duration) {
+        let millis = value.as_millis() as u64;
+        let index = match self.bounds.binary_search(&millis) {
+            Ok(i) => i,
+            Err(i) => i,
+        };
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(millis, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            bounds: self.bounds.clone(),
+            counts: self
+                .buckets
+                .iter()
+                .map(|b| b.load(Ordering::Relaxed))
+                .collect(),
+            sum: self.sum.load(Ordering::Relaxed),
+            count: self.count.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Write the histogram in Prometheus text exposition format.
+    fn render(&self, name: &str, help: &str, out: &mut String) {
+        let snapshot = self.snapshot();
+        writeln!(out, "# HELP {} {}", name, help).unwrap();
+        writeln!(out, "# TYPE {} histogram", name).unwrap();
+        let mut cumulative = 0;
+        for (bound, count) in snapshot.bounds.iter().zip(snapshot.counts.iter()) {
+            cumulative += count;
+            writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative).unwrap();
+        }
+        writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, snapshot.count).unwrap();
+        writeln!(out, "{}_sum {}", name, snapshot.sum).unwrap();
+        writeln!(out, "{}_count {}", name, snapshot.count).unwrap();
+    }
+}
+
+fn render_counter(name: &str, help: &str, value: u64, out: &mut String) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} counter", name).unwrap();
+    writeln!(out, "{} {}", name, value).unwrap();
+}
+
+/// Metrics of the signer node.
+pub struct Metrics {
+    /// Time from round start to the completed block, on the master node.
+    pub round_latency: Histogram,
+    /// Time from broadcasting the candidate block to collecting enough signatures, on the
+    /// master node.
+    pub signature_wait: Histogram,
+    /// Time of getnewblock RPC, on the master node.
+    pub block_fetch: Histogram,
+    /// Time of testing the candidate block with Tapyrus Core and the policy, on member nodes.
+    pub block_validation: Histogram,
+    /// Count of rounds which produced a block.
+    pub rounds_completed: AtomicU64,
+    /// Count of rounds which timed out.
+    pub rounds_failed: AtomicU64,
+    /// Count of resets of the state machine by the watchdog.
+    pub watchdog_resets: AtomicU64,
+    /// Count of valid signatures from each signer, on the master node. Indexed by signer index.
+    pub participation: Vec<AtomicU64>,
+    /// Count of signatures from public keys which aren't in the federation, on the master node.
+    pub unknown_sender_shares: AtomicU64,
+}
+
+impl Metrics {
+    /// `round_timelimit` is upper bound of the histogram buckets. `signers` is the number of
+    /// signers in the signer network.
+    pub fn new(round_timelimit: Duration, signers: usize) -> Metrics {
+        let start = Duration::from_millis(HISTOGRAM_START_MILLIS);
+        Metrics {
+            round_latency: Histogram::exponential(start, round_timelimit),
+            signature_wait: Histogram::exponential(start, round_timelimit),
+            block_fetch: Histogram::exponential(start, round_timelimit),
+            block_validation: Histogram::exponential(start, round_timelimit),
+            rounds_completed: AtomicU64::new(0),
+            rounds_failed: AtomicU64::new(0),
+            watchdog_resets: AtomicU64::new(0),
+            participation: (0..signers).map(|_| AtomicU64::new(0)).collect(),
+            unknown_sender_shares: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_signature(&self, signer_index: usize) {
+        if let Some(counter) = self.participation.get(signer_index) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Snapshot of the metrics as JSON, for environments which don't scrape Prometheus.
+    pub fn to_json(&self) -> serde_json::Value {
+        let participation: Vec<u64> = self
+            .participation
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        serde_json::json!({
+            "rounds_completed": self.rounds_completed.load(Ordering::Relaxed),
+            "rounds_failed": self.rounds_failed.load(Ordering::Relaxed),
+            "watchdog_resets": self.watchdog_resets.load(Ordering::Relaxed),
+            "participation": participation,
+            "unknown_sender_shares": self.unknown_sender_shares.load(Ordering::Relaxed),
+            "round_latency_milliseconds": self.round_latency.snapshot(),
+            "signature_wait_milliseconds": self.signature_wait.snapshot(),
+            "block_fetch_milliseconds": self.block_fetch.snapshot(),
+            "block_validation_milliseconds": self.block_validation.snapshot(),
+        })
+    }
+
+    /// Metrics in Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.round_latency.render(
+            "tapyrus_signer_round_latency_milliseconds",
+            "Time from round start to the completed block.",
+            &mut out,
+        );
+        self.signature_wait.render(
+            "tapyrus_signer_signature_wait_milliseconds",
+            "Time spent waiting for enough signatures.",
+            &mut out,
+        );
+        self.block_fetch.render(
+            "tapyrus_signer_block_fetch_milliseconds",
+            "Time of getnewblock RPC.",
+            &mut out,
+        );
+        self.block_validation.render(
+            "tapyrus_signer_block_validation_milliseconds",
+            "Time of testing the candidate block.",
+            &mut out,
+        );
+        render_counter(
+            "tapyrus_signer_rounds_completed_total",
+            "Count of rounds which produced a block.",
+            self.rounds_completed.load(Ordering::Relaxed),
+            &mut out,
+        );
+        render_counter(
+            "tapyrus_signer_rounds_failed_total",
+            "Count of rounds which timed out.",
+            self.rounds_failed.load(Ordering::Relaxed),
+            &mut out,
+        );
+        render_counter(
+            "tapyrus_signer_watchdog_resets_total",
+            "Count of resets of the state machine by the watchdog.",
+            self.watchdog_resets.load(Ordering::Relaxed),
+            &mut out,
+        );
+        render_counter(
+            "tapyrus_signer_unknown_sender_shares_total",
+            "Count of signatures from public keys which aren't in the federation.",
+            self.unknown_sender_shares.load(Ordering::Relaxed),
+            &mut out,
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_bounds() {
+        let histogram = Histogram::exponential(Duration::from_millis(10), Duration::from_secs(65));
+        let bounds = histogram.snapshot().bounds;
+        assert_eq!(bounds[0], 10);
+        assert_eq!(bounds[1], 20);
+        assert_eq!(*bounds.last().unwrap(), 81920);
+        assert_eq!(bounds.len(), 14);
+    }
+
+    #[test]
+    fn test_observe() {
+        let histogram =
+            Histogram::exponential(Duration::from_millis(10), Duration::from_millis(40));
+        histogram.observe(Duration::from_millis(5));
+        histogram.observe(Duration::from_millis(10));
+        histogram.observe(Duration::from_millis(11));
+        histogram.observe(Duration::from_secs(1));
+
+        assert_eq!(
+            histogram.snapshot(),
+            HistogramSnapshot {
+                bounds: vec![10, 20, 40],
+                counts: vec![2, 1, 0, 1],
+                sum: 1026,
+                count: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new(Duration::from_millis(20), 5);
+        metrics.round_latency.observe(Duration::from_millis(15));
+        metrics.round_latency.observe(Duration::from_millis(30));
+
+        let rendered = metrics.render();
+        assert!(
+            rendered.contains("tapyrus_signer_round_latency_milliseconds_bucket{le=\"10\"} 0\n")
+        );
+        assert!(
+            rendered.contains("tapyrus_signer_round_latency_milliseconds_bucket{le=\"20\"} 1\n")
+        );
+        assert!(
+            rendered.contains("tapyrus_signer_round_latency_milliseconds_bucket{le=\"+Inf\"} 2\n")
+        );
+        assert!(rendered.contains("tapyrus_signer_round_latency_milliseconds_sum 45\n"));
+        assert!(rendered.contains("tapyrus_signer_signature_wait_milliseconds_count 0\n"));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let metrics = Metrics::new(Duration::from_millis(20), 3);
+        metrics.round_latency.observe(Duration::from_millis(15));
+        metrics.rounds_completed.fetch_add(1, Ordering::Relaxed);
+        metrics.rounds_failed.fetch_add(2, Ordering::Relaxed);
+        metrics.record_signature(1);
+        metrics.record_signature(1);
+        // unknown index is ignored.
+        metrics.record_signature(3);
+
+        let json = metrics.to_json();
+        assert_eq!(json["rounds_completed"], 1);
+        assert_eq!(json["rounds_failed"], 2);
+        assert_eq!(json["participation"], serde_json::json!([0, 2, 0]));
+        assert_eq!(json["unknown_sender_shares"], 0);
+        assert_eq!(json["round_latency_milliseconds"]["count"], 1);
+        assert_eq!(json["round_latency_milliseconds"]["sum"], 15);
+        assert_eq!(json["signature_wait_milliseconds"]["count"], 0);
+    }
+}
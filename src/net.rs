@@ -2,12 +2,14 @@
 // Distributed under the MIT software license, see the accompanying
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
-use crate::blockdata::Block;
+use crate::blockdata::{Block, BlockHash};
 use crate::errors;
 use crate::serialize::ByteBufVisitor;
-use bitcoin::PublicKey;
+use crate::wire;
+use bitcoin::{PrivateKey, PublicKey};
+use bitcoin_hashes::{sha256d, Hash};
 use redis::{Client, Commands, ControlFlow, PubSubCommands, RedisError};
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::sync::mpsc::{channel, Receiver, Sender};
 /// メッセージを受け取って、それを処理するためのモジュール
 /// メッセージの処理は、メッセージの種類とラウンドの状態に依存する。
@@ -15,7 +17,10 @@ use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Redis channel of the signer network.
+pub const SIGNER_CHANNEL: &str = "tapyrus-signer";
 
 /// Signerの識別子。公開鍵を識別子にする。
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
@@ -48,8 +53,8 @@ impl<'de> Deserialize<'de> for SignerID {
     {
         let vec = deserializer.deserialize_byte_buf(ByteBufVisitor)?;
 
-        // TODO: Handle when PublicKey::from_slice returns Error
-        let pubkey = PublicKey::from_slice(&vec).unwrap();
+        let pubkey = PublicKey::from_slice(&vec)
+            .map_err(|e| de::Error::custom(format!("invalid public key: {}", e)))?;
         let signer_id = SignerID::new(pubkey);
         Ok(signer_id)
     }
@@ -61,12 +66,28 @@ pub enum MessageType {
     Signature(Signature),
     Completedblock(Block),
     Roundfailure,
+    /// Master announces the candidate block before the round, when readiness check is enabled.
+    Roundannounce(Block),
+    /// Member validated the announced candidate block and is ready to sign it.
+    Ready {
+        signer_index: usize,
+        block_hash: BlockHash,
+    },
+    /// Member tells the other members the hash of the candidate block which it is about to sign.
+    Blockhash {
+        master_index: usize,
+        block_hash: BlockHash,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Message {
     pub message_type: MessageType,
     pub sender_id: SignerID,
+    /// Height of the block which the round of the sender produces.
+    pub height: u64,
+    /// Number of the round for the height, counted from 0. It is incremented on round timeout.
+    pub round: u32,
 }
 
 #[derive(Debug, PartialEq)]
@@ -89,12 +110,142 @@ impl<'de> Deserialize<'de> for Signature {
     {
         let vec = deserializer.deserialize_byte_buf(ByteBufVisitor)?;
 
-        // TODO: handle parse error
-        let signature = secp256k1::Signature::from_der(&vec).unwrap();
+        let signature = secp256k1::Signature::from_der(&vec)
+            .map_err(|e| de::Error::custom(format!("invalid signature: {}", e)))?;
         Ok(Signature(signature))
     }
 }
 
+/// Message with the signature of the sender's signer private key. Receivers verify it against the
+/// public keys of the federation, so that other parties on the broker can't spoof signers.
+/// The signature covers the height and the round in the message and the time when it is sent,
+/// so that old messages replayed on the broker are told from new ones.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub message: Message,
+    /// Time(msec since unix epoch) when the message is sent.
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+impl SignedMessage {
+    pub fn sign(
+        message: Message,
+        timestamp: u64,
+        private_key: &PrivateKey,
+    ) -> Result<Self, errors::Error> {
+        let hash = Self::message_hash(&message, timestamp)?;
+        let signature = secp256k1::Secp256k1::signing_only().sign(&hash, &private_key.key);
+        Ok(SignedMessage {
+            message,
+            timestamp,
+            signature: Signature(signature),
+        })
+    }
+
+    /// Returns the message and the time when it is sent, if the sender is in `pubkey_list` and
+    /// the signature is made by the sender's key.
+    pub fn verify(self, pubkey_list: &[PublicKey]) -> Result<(Message, u64), errors::Error> {
+        let pubkey = self.message.sender_id.pubkey;
+        if !pubkey_list.contains(&pubkey) {
+            return Err(errors::Error::MessageAuthFailed(pubkey));
+        }
+        let hash = Self::message_hash(&self.message, self.timestamp)?;
+        secp256k1::Secp256k1::verification_only()
+            .verify(&hash, &self.signature.0, &pubkey.key)
+            .map_err(|_| errors::Error::MessageAuthFailed(pubkey))?;
+        Ok((self.message, self.timestamp))
+    }
+
+    /// sha256d of the serialized message followed by the timestamp in 8 bytes big endian.
+    fn message_hash(
+        message: &Message,
+        timestamp: u64,
+    ) -> Result<secp256k1::Message, errors::Error> {
+        let mut serialized = serde_json::to_vec(message)?;
+        serialized.extend_from_slice(&timestamp.to_be_bytes());
+        let hash = sha256d::Hash::hash(&serialized);
+        Ok(secp256k1::Message::from_slice(&hash[..])?)
+    }
+}
+
+/// Keys to authenticate messages between signers.
+#[derive(Clone)]
+pub struct MessageAuth {
+    /// Key to sign the messages which this node broadcasts. Observer doesn't have it.
+    pub private_key: Option<PrivateKey>,
+    /// Public keys of the federation. Messages which aren't signed by these keys are dropped.
+    pub pubkey_list: Vec<PublicKey>,
+}
+
+/// Private key is redacted so that it never appears in logs.
+impl std::fmt::Debug for MessageAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MessageAuth")
+            .field(
+                "private_key",
+                &self.private_key.as_ref().map(|_| "PrivateKey(***)"),
+            )
+            .field("pubkey_list", &self.pubkey_list)
+            .finish()
+    }
+}
+
+impl MessageAuth {
+    /// The timestamp is in the signed body, so it isn't put in the envelope.
+    fn encode(&self, message: Message, timestamp: u64) -> String {
+        let private_key = self
+            .private_key
+            .as_ref()
+            .expect("Can't broadcast message without private key.");
+        let signed = SignedMessage::sign(message, timestamp, private_key).unwrap();
+        wire::encode(&signed).unwrap()
+    }
+
+    fn decode(&self, payload: &str) -> Result<(Message, Option<u64>), errors::Error> {
+        let signed: SignedMessage = wire::decode(payload)?;
+        let (message, timestamp) = signed.verify(&self.pubkey_list)?;
+        Ok((message, Some(timestamp)))
+    }
+}
+
+/// Milliseconds since unix epoch, for the timestamp of messages.
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Decode the payload received from the broker at `now`(msec since unix epoch). If `ttl` is set,
+/// the message which was sent before `ttl` is dropped with `Error::MessageExpired`, and the
+/// message without timestamp is dropped with `Error::MessageWithoutTimestamp` unless
+/// `accept_untimed` is set.
+fn decode_payload(
+    auth: &Option<MessageAuth>,
+    ttl: Option<Duration>,
+    accept_untimed: bool,
+    payload: &str,
+    now: u64,
+) -> Result<Message, errors::Error> {
+    let (message, timestamp) = match auth {
+        Some(ref auth) => auth.decode(payload)?,
+        None => wire::decode_with_timestamp(payload)?,
+    };
+    match (ttl, timestamp) {
+        (Some(ttl), Some(timestamp)) => {
+            let age = now.saturating_sub(timestamp);
+            if age > ttl.as_millis() as u64 {
+                return Err(errors::Error::MessageExpired(age));
+            }
+        }
+        (Some(_), None) if !accept_untimed => {
+            return Err(errors::Error::MessageWithoutTimestamp);
+        }
+        _ => {}
+    }
+    Ok(message)
+}
+
 pub trait ConnectionManager {
     type ERROR: std::error::Error;
     fn broadcast_message(&self, message: Message);
@@ -143,21 +294,39 @@ pub struct RedisManager {
     pub client: Arc<Client>,
     error_sender: Sender<ConnectionManagerError<RedisError>>,
     pub error_receiver: Option<Receiver<ConnectionManagerError<RedisError>>>,
+    /// If set, messages are signed on broadcast and verified on receive.
+    pub auth: Option<MessageAuth>,
+    /// Redis channel which messages are published to and subscribed from.
+    pub channel: String,
+    /// If set, received messages which were sent before this time are dropped. Redis pub/sub
+    /// doesn't keep messages, so the age is told by the timestamp which the sender puts.
+    pub message_ttl: Option<Duration>,
+    /// Accept messages without timestamp even if `message_ttl` is set. Only for the senders
+    /// which don't put it. Signed messages always have it.
+    pub accept_untimed_messages: bool,
 }
 
 impl RedisManager {
     pub fn new(host: String, port: String) -> Self {
-        let url: &str = &format!("redis://{}:{}", host, port);
-        let client = Arc::new(Client::open(url).unwrap());
+        Self::open(&format!("redis://{}:{}", host, port)).unwrap()
+    }
+
+    /// Same as `new`, but with redis url like `redis://127.0.0.1:6379`.
+    pub fn open(url: &str) -> Result<Self, errors::Error> {
+        let client = Arc::new(Client::open(url)?);
         let (s, r): (
             Sender<ConnectionManagerError<RedisError>>,
             Receiver<ConnectionManagerError<RedisError>>,
         ) = channel();
-        RedisManager {
+        Ok(RedisManager {
             client,
             error_sender: s,
             error_receiver: Some(r),
-        }
+            auth: None,
+            channel: SIGNER_CHANNEL.to_string(),
+            message_ttl: None,
+            accept_untimed_messages: false,
+        })
     }
 
     pub fn test_connection(&self) -> Result<(), errors::Error> {
@@ -173,28 +342,64 @@ impl RedisManager {
     {
         let client = Arc::clone(&self.client);
         let error_sender = self.error_sender.clone();
+        let auth = self.auth.clone();
+        let channel = self.channel.clone();
+        let message_ttl = self.message_ttl;
+        let accept_untimed = self.accept_untimed_messages;
         thread::Builder::new()
             .name("RedisManagerThread".to_string())
             .spawn(move || {
                 fn inner_subscribe<F2>(
                     client: Arc<Client>,
+                    auth: Option<MessageAuth>,
+                    message_ttl: Option<Duration>,
+                    accept_untimed: bool,
+                    channel: &str,
                     mut message_processor: F2,
                 ) -> Result<(), ConnectionManagerError<RedisError>>
                 where
                     F2: FnMut(Message) -> ControlFlow<()> + Send + 'static,
                 {
                     let mut conn = client.get_connection()?;
-                    conn.subscribe(&["tapyrus-signer"], |msg| {
+                    conn.subscribe(&[channel], |msg| {
                         let _ch = msg.get_channel_name();
                         let payload: String = msg.get_payload().unwrap();
                         log::trace!("receive message. payload: {}", payload);
 
-                        let message: Message = serde_json::from_str(&payload).unwrap();
+                        let decoded = decode_payload(
+                            &auth,
+                            message_ttl,
+                            accept_untimed,
+                            &payload,
+                            unix_millis(SystemTime::now()),
+                        );
+                        let message: Message = match decoded {
+                            Ok(message) => message,
+                            Err(errors::Error::UnsupportedWireVersion(version)) => {
+                                log::warn!(
+                                    "Drop message of wire version {}. This node uses version {}, so all signers must be upgraded together.",
+                                    version,
+                                    wire::WIRE_VERSION
+                                );
+                                return ControlFlow::Continue;
+                            }
+                            Err(e) => {
+                                log::warn!("Drop invalid message: {:?}", e);
+                                return ControlFlow::Continue;
+                            }
+                        };
                         message_processor(message)
                     })?;
                     Ok(())
                 }
-                match inner_subscribe(client, message_processor) {
+                match inner_subscribe(
+                    client,
+                    auth,
+                    message_ttl,
+                    accept_untimed,
+                    &channel,
+                    message_processor,
+                ) {
                     Ok(()) => {}
                     Err(e) => error_sender
                         .send(e)
@@ -209,16 +414,21 @@ impl ConnectionManager for RedisManager {
     type ERROR = RedisError;
     fn broadcast_message(&self, message: Message) {
         let client = Arc::clone(&self.client);
-        let message_in_thread = serde_json::to_string(&message).unwrap();
+        let channel = self.channel.clone();
+        let timestamp = unix_millis(SystemTime::now());
+        let message_in_thread = match self.auth {
+            Some(ref auth) => auth.encode(message, timestamp),
+            None => wire::encode_with_timestamp(&message, Some(timestamp)).unwrap(),
+        };
         thread::Builder::new()
             .name("RedisBroadcastThread".to_string())
             .spawn(move || {
                 let conn = client.get_connection().unwrap();
                 thread::sleep(Duration::from_millis(500));
 
-                log::trace!("Publish {} to tapyrus-signer channel.", message_in_thread);
+                log::trace!("Publish {} to {} channel.", message_in_thread, channel);
 
-                let _: () = conn.publish("tapyrus-signer", message_in_thread).unwrap();
+                let _: () = conn.publish(channel, message_in_thread).unwrap();
             })
             .unwrap()
             .join()
@@ -263,6 +473,8 @@ mod test {
         let message = Message {
             message_type: MessageType::Roundfailure,
             sender_id,
+            height: 1,
+            round: 0,
         };
         connection_manager.broadcast_message(message);
 
@@ -285,6 +497,14 @@ mod test {
         let pubkey = TestKeys::new().pubkeys()[0];
         let expected: SignerID = SignerID { pubkey };
         assert_eq!(expected, signer_id);
+
+        // bytes from the broker which aren't a public key.
+        assert!(serde_json::from_str::<SignerID>("[3,131,26]").is_err());
+    }
+
+    #[test]
+    fn signature_deserialize_invalid_test() {
+        assert!(serde_json::from_str::<Signature>("[48,69,2,33]").is_err());
     }
 
     #[test]
@@ -294,7 +514,7 @@ mod test {
         let serialized = serde_json::to_string(&message).unwrap();
 
         // check serialize
-        let expected_serialized_message = r#"{"message_type":{"Signature":[48,69,2,33,0,209,78,75,40,108,63,135,236,126,58,248,69,201,134,198,123,9,100,136,101,202,168,134,119,114,0,86,36,17,238,152,190,2,32,91,12,234,133,10,255,32,122,215,249,21,62,10,88,133,223,155,69,205,171,31,105,114,13,174,21,159,118,161,43,58,137]},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252]}"#;
+        let expected_serialized_message = r#"{"message_type":{"Signature":[48,69,2,33,0,209,78,75,40,108,63,135,236,126,58,248,69,201,134,198,123,9,100,136,101,202,168,134,119,114,0,86,36,17,238,152,190,2,32,91,12,234,133,10,255,32,122,215,249,21,62,10,88,133,223,155,69,205,171,31,105,114,13,174,21,159,118,161,43,58,137]},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}"#;
         assert_eq!(expected_serialized_message, serialized);
 
         // check deserialize
@@ -306,4 +526,127 @@ mod test {
             SignerID::new(TestKeys::new().pubkeys()[0])
         );
     }
+
+    #[test]
+    fn test_signed_message() {
+        let testkeys = TestKeys::new();
+        let signed = SignedMessage::sign(create_message(), 0, &testkeys.key[0]).unwrap();
+        let serialized = serde_json::to_string(&signed).unwrap();
+
+        let deserialized = serde_json::from_str::<SignedMessage>(&serialized).unwrap();
+        let (message, _) = deserialized.verify(&testkeys.pubkeys()).unwrap();
+        assert_eq!(message.message_type, create_message().message_type);
+    }
+
+    #[test]
+    fn test_signed_message_by_other_key() {
+        // message claims to be from pubkeys()[0], but signed by another key.
+        let testkeys = TestKeys::new();
+        let signed = SignedMessage::sign(create_message(), 0, &testkeys.key[1]).unwrap();
+        match signed.verify(&testkeys.pubkeys()) {
+            Err(errors::Error::MessageAuthFailed(pubkey)) => {
+                assert_eq!(pubkey, testkeys.pubkeys()[0])
+            }
+            r => panic!("Should be MessageAuthFailed, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_signed_message_tampered() {
+        let testkeys = TestKeys::new();
+        let mut signed = SignedMessage::sign(create_message(), 0, &testkeys.key[0]).unwrap();
+        signed.message.message_type = MessageType::Roundfailure;
+        assert!(signed.verify(&testkeys.pubkeys()).is_err());
+    }
+
+    #[test]
+    fn test_signed_message_replayed_in_other_round() {
+        // height, round and timestamp are signed, so a replayed message can't be moved to them.
+        let testkeys = TestKeys::new();
+        let mut signed = SignedMessage::sign(create_message(), 0, &testkeys.key[0]).unwrap();
+        signed.message.height += 1;
+        assert!(signed.verify(&testkeys.pubkeys()).is_err());
+
+        let mut signed = SignedMessage::sign(create_message(), 0, &testkeys.key[0]).unwrap();
+        signed.message.round += 1;
+        assert!(signed.verify(&testkeys.pubkeys()).is_err());
+
+        let mut signed = SignedMessage::sign(create_message(), 0, &testkeys.key[0]).unwrap();
+        signed.timestamp = 1_564_000_000_000;
+        assert!(signed.verify(&testkeys.pubkeys()).is_err());
+    }
+
+    #[test]
+    fn test_signed_message_from_unknown_signer() {
+        let testkeys = TestKeys::new();
+        let signed = SignedMessage::sign(create_message(), 0, &testkeys.key[0]).unwrap();
+        match signed.verify(&testkeys.pubkeys()[1..]) {
+            Err(errors::Error::MessageAuthFailed(_)) => {}
+            r => panic!("Should be MessageAuthFailed, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_message_auth_drops_unsigned_message() {
+        let testkeys = TestKeys::new();
+        let auth = MessageAuth {
+            private_key: Some(testkeys.key[0]),
+            pubkey_list: testkeys.pubkeys(),
+        };
+        let payload = auth.encode(create_message(), 0);
+        assert!(auth.decode(&payload).is_ok());
+
+        let unsigned = wire::encode(&create_message()).unwrap();
+        assert!(auth.decode(&unsigned).is_err());
+    }
+
+    #[test]
+    fn test_drop_expired_message() {
+        let testkeys = TestKeys::new();
+        let auth = Some(MessageAuth {
+            private_key: Some(testkeys.key[0]),
+            pubkey_list: testkeys.pubkeys(),
+        });
+        let ttl = Some(Duration::from_secs(5));
+        let sent_at = 1_564_000_000_000;
+        let payload = auth.as_ref().unwrap().encode(create_message(), sent_at);
+
+        assert!(decode_payload(&auth, ttl, false, &payload, sent_at + 5000).is_ok());
+        match decode_payload(&auth, ttl, false, &payload, sent_at + 5001) {
+            Err(errors::Error::MessageExpired(5001)) => {}
+            r => panic!("Should be MessageExpired, but {:?}", r),
+        }
+        // without ttl, old messages are accepted.
+        assert!(decode_payload(&auth, None, false, &payload, sent_at + 60000).is_ok());
+    }
+
+    #[test]
+    fn test_drop_message_without_timestamp() {
+        // message from the sender which doesn't put timestamp.
+        let payload = wire::encode(&create_message()).unwrap();
+        let ttl = Some(Duration::from_secs(5));
+        match decode_payload(&None, ttl, false, &payload, 1_564_000_000_000) {
+            Err(errors::Error::MessageWithoutTimestamp) => {}
+            r => panic!("Should be MessageWithoutTimestamp, but {:?}", r),
+        }
+        assert!(decode_payload(&None, ttl, true, &payload, 1_564_000_000_000).is_ok());
+        assert!(decode_payload(&None, None, false, &payload, 1_564_000_000_000).is_ok());
+
+        // timestamp of the signed message can't be changed to pass the TTL.
+        let testkeys = TestKeys::new();
+        let auth = Some(MessageAuth {
+            private_key: Some(testkeys.key[0]),
+            pubkey_list: testkeys.pubkeys(),
+        });
+        let sent_at = 1_564_000_000_000;
+        let payload = auth
+            .as_ref()
+            .unwrap()
+            .encode(create_message(), sent_at)
+            .replace(&sent_at.to_string(), &(sent_at + 60000).to_string());
+        match decode_payload(&auth, ttl, true, &payload, sent_at + 60000) {
+            Err(errors::Error::MessageAuthFailed(_)) => {}
+            r => panic!("Should be MessageAuthFailed, but {:?}", r),
+        }
+    }
 }
@@ -0,0 +1,411 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bitcoin::PublicKey;
+use redis::ControlFlow;
+
+use crate::blockdata::Block;
+use crate::net::{ConnectionManager, Message, MessageType, SignerID};
+use crate::rpc::TapyrusApi;
+use crate::sign::SighashVariant;
+use crate::signer_node::{canonical_order, ROUND_TIMELIMIT_DELTA};
+
+/// Number of consecutive failed rounds after which the federation is reported as unhealthy.
+pub const UNHEALTHY_FAILED_ROUNDS: u32 = 3;
+
+/// Number of the latest round outcomes which the observer keeps for monitoring.
+pub const RECENT_ROUNDS: usize = 10;
+
+/// Outcome of an observed round.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoundOutcome {
+    /// Master published the completed block. `latency` is from the candidate block.
+    Completed { master: SignerID, latency: Duration },
+    /// Round didn't complete in time. `master` is None if no candidate block was proposed.
+    TimedOut { master: Option<SignerID> },
+}
+
+/// Round which the observer is following.
+#[derive(Debug, PartialEq)]
+struct ObservedRound {
+    master: SignerID,
+    candidate_block: Block,
+    signers: HashSet<SignerID>,
+    started_at: Instant,
+}
+
+/// Observer follows the rounds of the signer network and verifies candidate blocks and
+/// signatures. It has no private key, so it never signs or sends any message.
+pub struct Observer<T: TapyrusApi> {
+    rpc: Arc<T>,
+    pubkey_list: Vec<PublicKey>,
+    threshold: u8,
+    round: Option<ObservedRound>,
+    round_timelimit: Duration,
+    round_deadline: Option<Instant>,
+    failed_rounds: u32,
+    last_seen: HashMap<SignerID, Instant>,
+    signature_latency: HashMap<SignerID, Duration>,
+    recent_rounds: VecDeque<RoundOutcome>,
+    stop_signal: Option<Receiver<u32>>,
+    pub sighash_variant: SighashVariant,
+}
+
+impl<T: TapyrusApi> Observer<T> {
+    pub fn new(pubkey_list: Vec<PublicKey>, threshold: u8, rpc: T, round_duration: u64) -> Self {
+        let mut pubkey_list = pubkey_list;
+        canonical_order(&mut pubkey_list);
+        Observer {
+            rpc: Arc::new(rpc),
+            pubkey_list,
+            threshold,
+            round: None,
+            round_timelimit: Duration::from_secs(round_duration + ROUND_TIMELIMIT_DELTA),
+            round_deadline: None,
+            failed_rounds: 0,
+            last_seen: HashMap::new(),
+            signature_latency: HashMap::new(),
+            recent_rounds: VecDeque::new(),
+            stop_signal: None,
+            sighash_variant: SighashVariant::default(),
+        }
+    }
+
+    pub fn stop_handler(&mut self, receiver: Receiver<u32>) {
+        self.stop_signal = Some(receiver);
+    }
+
+    /// Run the observer until the stop signal is received.
+    pub fn run<C: ConnectionManager>(&mut self, connection_manager: C) {
+        self.run_with(connection_manager, |_| {});
+    }
+
+    /// Same as `run`, but `on_tick` is called with the observer in each iteration of the loop,
+    /// to show its status.
+    pub fn run_with<C, F>(&mut self, mut connection_manager: C, mut on_tick: F)
+    where
+        C: ConnectionManager,
+        F: FnMut(&Self),
+    {
+        let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
+        let closure = move |message: Message| match sender.send(message) {
+            Ok(_) => ControlFlow::Continue,
+            Err(error) => {
+                log::warn!("Happened error!: {:?}", error);
+                ControlFlow::Break(())
+            }
+        };
+        let _handler = connection_manager.start(closure);
+        log::info!("observer start. signers: {}", self.pubkey_list.len());
+
+        let connection_manager_error_handler = connection_manager.error_handler();
+        loop {
+            if let Some(ref r) = self.stop_signal {
+                if r.try_recv().is_ok() {
+                    log::warn!("Stop by Terminate Signal.");
+                    break;
+                }
+            }
+            if let Ok(msg) = receiver.try_recv() {
+                self.process_message(msg);
+            }
+            self.tick(Instant::now());
+            on_tick(self);
+            if let Some(ref receiver) = connection_manager_error_handler {
+                if let Ok(e) = receiver.try_recv() {
+                    panic!("{}", e);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(300));
+        }
+    }
+
+    /// Process a message received from the signer network.
+    pub fn process_message(&mut self, message: Message) {
+        let sender_id = message.sender_id;
+        if !self.pubkey_list.contains(&sender_id.pubkey) {
+            log::warn!("Received message from unknown signer: {:?}", sender_id);
+            return;
+        }
+        self.last_seen.insert(sender_id, Instant::now());
+        match message.message_type {
+            MessageType::Candidateblock(block) => self.process_candidateblock(sender_id, block),
+            MessageType::Signature(sig) => self.process_signature(sender_id, &sig.0),
+            MessageType::Completedblock(block) => self.process_completedblock(sender_id, &block),
+            MessageType::Roundfailure => {
+                log::warn!("Round failure is reported. sender: {:?}", sender_id);
+            }
+            MessageType::Roundannounce(_)
+            | MessageType::Ready { .. }
+            | MessageType::Blockhash { .. } => {
+                log::debug!("Readiness check of the round. sender: {:?}", sender_id);
+            }
+        }
+    }
+
+    /// Advance the round timer to `now`. Reports the round which doesn't complete in time.
+    pub fn tick(&mut self, now: Instant) {
+        match self.round_deadline {
+            None => self.round_deadline = Some(now + self.round_timelimit),
+            Some(deadline) if now >= deadline => {
+                self.failed_rounds += 1;
+                let master = match self.round.take() {
+                    Some(round) => {
+                        log::warn!(
+                            "Round timed out. master: {:?}, signatures: {}/{}",
+                            round.master,
+                            round.signers.len(),
+                            self.threshold
+                        );
+                        Some(round.master)
+                    }
+                    None => {
+                        log::warn!("Round timed out. No candidate block is proposed.");
+                        None
+                    }
+                };
+                self.record_outcome(RoundOutcome::TimedOut { master });
+                if self.failed_rounds >= UNHEALTHY_FAILED_ROUNDS {
+                    log::error!(
+                        "Federation is unhealthy. {} rounds failed in a row.",
+                        self.failed_rounds
+                    );
+                }
+                self.round_deadline = Some(now + self.round_timelimit);
+            }
+            Some(_) => {}
+        }
+    }
+
+    pub fn failed_rounds(&self) -> u32 {
+        self.failed_rounds
+    }
+
+    pub fn rpc(&self) -> &T {
+        &self.rpc
+    }
+
+    /// Public keys of the signers in signer index order.
+    pub fn pubkey_list(&self) -> &[PublicKey] {
+        &self.pubkey_list
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn round_timelimit(&self) -> Duration {
+        self.round_timelimit
+    }
+
+    /// Master of the current round, if it proposed a candidate block.
+    pub fn master(&self) -> Option<SignerID> {
+        self.round.as_ref().map(|round| round.master)
+    }
+
+    /// Number of the valid signatures for the candidate block of the current round.
+    pub fn signatures(&self) -> usize {
+        self.round.as_ref().map_or(0, |round| round.signers.len())
+    }
+
+    /// Time when any message from the signer was received last.
+    pub fn last_seen(&self, signer_id: &SignerID) -> Option<Instant> {
+        self.last_seen.get(signer_id).cloned()
+    }
+
+    /// Time from the candidate block to the last valid signature of the signer.
+    pub fn signature_latency(&self, signer_id: &SignerID) -> Option<Duration> {
+        self.signature_latency.get(signer_id).cloned()
+    }
+
+    /// Outcomes of the latest rounds, the newest first.
+    pub fn recent_rounds(&self) -> &VecDeque<RoundOutcome> {
+        &self.recent_rounds
+    }
+
+    fn record_outcome(&mut self, outcome: RoundOutcome) {
+        self.recent_rounds.push_front(outcome);
+        self.recent_rounds.truncate(RECENT_ROUNDS);
+    }
+
+    fn process_candidateblock(&mut self, sender_id: SignerID, block: Block) {
+        if let Err(e) = self.rpc.testproposedblock(&block) {
+            log::warn!(
+                "Received Invalid candidate block!!: sender: {:?}, error: {:?}",
+                sender_id,
+                e
+            );
+            return;
+        }
+        log::info!("Round started. master: {:?}", sender_id);
+        let mut signers = HashSet::new();
+        // master signs the candidate block itself.
+        signers.insert(sender_id);
+        self.round = Some(ObservedRound {
+            master: sender_id,
+            candidate_block: block,
+            signers,
+            started_at: Instant::now(),
+        });
+        self.round_deadline = None;
+    }
+
+    fn process_signature(&mut self, sender_id: SignerID, sig: &secp256k1::Signature) {
+        let round = match self.round {
+            Some(ref mut round) => round,
+            None => {
+                log::debug!("Received signature out of round. sender: {:?}", sender_id);
+                return;
+            }
+        };
+        let verifier = secp256k1::Secp256k1::verification_only();
+        let verified = self
+            .sighash_variant
+            .sighash(&round.candidate_block)
+            .and_then(|message| Ok(verifier.verify(&message, sig, &sender_id.pubkey.key)?));
+        match verified {
+            Ok(_) => {
+                round.signers.insert(sender_id);
+                self.signature_latency
+                    .insert(sender_id, round.started_at.elapsed());
+                if round.signers.len() == self.threshold as usize {
+                    log::info!(
+                        "Enough signatures are collected. master: {:?}",
+                        round.master
+                    );
+                }
+            }
+            Err(e) => log::warn!("Invalid Signature!: sender={:?}, error={:?}", sender_id, e),
+        }
+    }
+
+    fn process_completedblock(&mut self, sender_id: SignerID, block: &Block) {
+        log::info!(
+            "Round completed. master: {:?}, block: {}",
+            sender_id,
+            block
+                .hash()
+                .map(|h| hex::encode(h.borrow_inner()))
+                .unwrap_or_default()
+        );
+        if let Some(round) = self.round.take() {
+            self.record_outcome(RoundOutcome::Completed {
+                master: sender_id,
+                latency: round.started_at.elapsed(),
+            });
+        }
+        self.round_deadline = None;
+        self.failed_rounds = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::Signature;
+    use crate::rpc::tests::{safety, safety_error, MockRpc};
+    use crate::sign::sign;
+    use crate::test_helper::{get_block, TestKeys};
+
+    fn create_observer(rpc: MockRpc) -> Observer<MockRpc> {
+        Observer::new(TestKeys::new().pubkeys(), 3, rpc, 0)
+    }
+
+    fn signature_message(key_index: usize, block: &Block) -> Message {
+        let testkeys = TestKeys::new();
+        let sig = sign(&testkeys.key[key_index], &block.hash().unwrap());
+        Message {
+            message_type: MessageType::Signature(Signature(sig)),
+            sender_id: SignerID::new(testkeys.pubkeys()[key_index]),
+            height: 1,
+            round: 0,
+        }
+    }
+
+    #[test]
+    fn test_follow_round() {
+        let block = get_block(0);
+        let mut observer = create_observer(MockRpc {
+            return_block: safety(block.clone()),
+        });
+        let master = SignerID::new(TestKeys::new().pubkeys()[1]);
+
+        observer.process_message(Message {
+            message_type: MessageType::Candidateblock(block.clone()),
+            sender_id: master,
+            height: 1,
+            round: 0,
+        });
+        observer.process_message(signature_message(2, &block));
+        // signature for other block is ignored.
+        observer.process_message(signature_message(3, &get_block(1)));
+        assert_eq!(observer.signatures(), 2);
+        assert_eq!(observer.master(), Some(master));
+        let signer = SignerID::new(TestKeys::new().pubkeys()[2]);
+        assert!(observer.last_seen(&signer).is_some());
+        assert!(observer.signature_latency(&signer).is_some());
+
+        observer.process_message(Message {
+            message_type: MessageType::Completedblock(block.clone()),
+            sender_id: master,
+            height: 1,
+            round: 0,
+        });
+        assert_eq!(observer.round, None);
+        assert_eq!(observer.failed_rounds(), 0);
+        match observer.recent_rounds().front() {
+            Some(RoundOutcome::Completed { master: m, .. }) => assert_eq!(*m, master),
+            r => panic!("Should be completed round, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_ignore_invalid_candidate_block() {
+        let mut observer = create_observer(MockRpc {
+            return_block: safety_error("invalid block".to_string()),
+        });
+        observer.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(TestKeys::new().pubkeys()[1]),
+            height: 1,
+            round: 0,
+        });
+        assert_eq!(observer.round, None);
+    }
+
+    #[test]
+    fn test_failed_rounds() {
+        let mut observer = create_observer(MockRpc {
+            return_block: safety(get_block(0)),
+        });
+        let now = Instant::now();
+        observer.tick(now);
+        for i in 1..=UNHEALTHY_FAILED_ROUNDS {
+            observer.tick(now + Duration::from_secs(5) * i);
+        }
+        assert_eq!(observer.failed_rounds(), UNHEALTHY_FAILED_ROUNDS);
+        assert_eq!(
+            observer.recent_rounds().front(),
+            Some(&RoundOutcome::TimedOut { master: None })
+        );
+    }
+
+    #[test]
+    fn test_recent_rounds_limit() {
+        let mut observer = create_observer(MockRpc {
+            return_block: safety(get_block(0)),
+        });
+        let now = Instant::now();
+        observer.tick(now);
+        for i in 1..=(RECENT_ROUNDS as u32 + 2) {
+            observer.tick(now + Duration::from_secs(5) * i);
+        }
+        assert_eq!(observer.recent_rounds().len(), RECENT_ROUNDS);
+    }
+}
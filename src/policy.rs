@@ -0,0 +1,285 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+use crate::blockdata::Block;
+use crate::errors::Error;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::Transaction;
+use std::fmt;
+use std::str::FromStr;
+
+/// OP_COLOR opcode of Tapyrus.
+const OP_COLOR: u8 = 0xbc;
+/// Push opcode for 33 bytes data.
+const OP_PUSHBYTES_33: u8 = 0x21;
+/// Weight of a byte out of the witness, as defined in BIP141.
+const WITNESS_SCALE_FACTOR: u64 = 4;
+
+/// Color identifier of colored coin in Tapyrus. It is 1 byte token type and 32 bytes hash.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct ColorId([u8; 33]);
+
+impl ColorId {
+    const LEN: usize = 33;
+
+    pub fn from_slice(sl: &[u8]) -> Result<ColorId, Error> {
+        if sl.len() != Self::LEN {
+            return Err(Error::InvalidLength(Self::LEN, sl.len()));
+        }
+        // token types are reissuable(0xc1), non-reissuable(0xc2) and NFT(0xc3).
+        if sl[0] < 0xc1 || sl[0] > 0xc3 {
+            return Err(Error::InvalidArgs(format!(
+                "Unknown token type of color id: {:x}",
+                sl[0]
+            )));
+        }
+        let mut ret = [0; 33];
+        ret.copy_from_slice(sl);
+        Ok(ColorId(ret))
+    }
+
+    /// Color id of the output script. Colored script starts with `<color id> OP_COLOR`.
+    pub fn from_script(script: &[u8]) -> Option<ColorId> {
+        if script.len() > Self::LEN + 1
+            && script[0] == OP_PUSHBYTES_33
+            && script[Self::LEN + 1] == OP_COLOR
+        {
+            ColorId::from_slice(&script[1..=Self::LEN]).ok()
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for ColorId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s)
+            .map_err(|_| Error::InvalidArgs(format!("'{}' is invalid color id.", s)))?;
+        ColorId::from_slice(&bytes)
+    }
+}
+
+impl fmt::Display for ColorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.0[..]))
+    }
+}
+
+/// Policy of blocks which the signer is willing to sign.
+#[derive(Debug, Default, Clone)]
+pub struct BlockPolicy {
+    /// If set, the block must not have colored outputs except these color ids.
+    pub allowed_color_ids: Option<Vec<ColorId>>,
+    /// The block must not have colored outputs of these color ids.
+    pub denied_color_ids: Vec<ColorId>,
+    /// If set, the candidate block must not be larger than this size(bytes). The signatures which
+    /// are added to the proof later are not counted.
+    pub max_block_size: Option<u64>,
+    /// If set, the candidate block must not be heavier than this weight, as defined in BIP141.
+    pub max_block_weight: Option<u64>,
+}
+
+impl BlockPolicy {
+    /// Check the candidate block satisfies the policy.
+    pub fn check(&self, block: &Block) -> Result<(), Error> {
+        self.check_size(block)?;
+        if self.allowed_color_ids.is_none() && self.denied_color_ids.is_empty() {
+            return Ok(());
+        }
+
+        for tx in block.transactions()? {
+            for output in tx.output {
+                if let Some(color_id) = ColorId::from_script(output.script_pubkey.as_bytes()) {
+                    self.check_color_id(&color_id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_size(&self, block: &Block) -> Result<(), Error> {
+        let size = block.payload().len() as u64;
+        if let Some(max) = self.max_block_size {
+            if size > max {
+                return Err(Error::BlockPolicyViolation(format!(
+                    "Block size {} exceeds the limit {}.",
+                    size, max
+                )));
+            }
+        }
+        if let Some(max) = self.max_block_weight {
+            let txs = block.transactions()?;
+            let txs_size: u64 = txs.iter().map(|tx| serialize(tx).len() as u64).sum();
+            // header, proof and the number of transactions have no witness.
+            let weight = (size - txs_size) * WITNESS_SCALE_FACTOR
+                + txs.iter().map(Transaction::get_weight).sum::<u64>();
+            if weight > max {
+                return Err(Error::BlockPolicyViolation(format!(
+                    "Block weight {} exceeds the limit {}.",
+                    weight, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_color_id(&self, color_id: &ColorId) -> Result<(), Error> {
+        let allowed = match self.allowed_color_ids {
+            Some(ref ids) => ids.contains(color_id),
+            None => true,
+        };
+        if !allowed || self.denied_color_ids.contains(color_id) {
+            return Err(Error::DisallowedColorId(*color_id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helper::get_block;
+    use bitcoin::{Script, TxOut};
+
+    const COLOR_ID_1: &str = "c1ec2fd806701a3f55808cbec3922c38dafaa3070c48c803e9043ee3642c660b46";
+    const COLOR_ID_2: &str = "c3ec2fd806701a3f55808cbec3922c38dafaa3070c48c803e9043ee3642c660b46";
+
+    fn colored_script(color_id: &str) -> Script {
+        // <color id> OP_COLOR OP_DUP OP_HASH160 <pubkey hash> OP_EQUALVERIFY OP_CHECKSIG
+        let mut script = vec![OP_PUSHBYTES_33];
+        script.extend(hex::decode(color_id).unwrap());
+        script.extend(vec![OP_COLOR, 0x76, 0xa9, 0x14]);
+        script.extend(vec![0; 20]);
+        script.extend(vec![0x88, 0xac]);
+        Script::from(script)
+    }
+
+    /// Block which has coinbase of `get_block(0)` and a transaction with colored outputs.
+    fn block_with_colors(color_ids: &[&str]) -> Block {
+        let base = get_block(0);
+        let mut txs = base.transactions().unwrap();
+        txs.push(Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![],
+            output: color_ids
+                .iter()
+                .map(|id| TxOut {
+                    value: 100,
+                    script_pubkey: colored_script(id),
+                })
+                .collect(),
+        });
+        let mut data = base.get_header_without_proof().to_vec();
+        data.extend(serialize(&Vec::<Vec<u8>>::new()));
+        data.extend(serialize(&txs));
+        Block::new(data)
+    }
+
+    fn color_id(s: &str) -> ColorId {
+        ColorId::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_color_id_from_script() {
+        let script = colored_script(COLOR_ID_1);
+        assert_eq!(
+            ColorId::from_script(script.as_bytes()),
+            Some(color_id(COLOR_ID_1))
+        );
+
+        let uncolored = get_block(0).transactions().unwrap()[0].output[0]
+            .script_pubkey
+            .clone();
+        assert_eq!(ColorId::from_script(uncolored.as_bytes()), None);
+    }
+
+    #[test]
+    fn test_invalid_color_id() {
+        assert!(ColorId::from_str("c1ec").is_err());
+        assert!(ColorId::from_str(
+            "00ec2fd806701a3f55808cbec3922c38dafaa3070c48c803e9043ee3642c660b46"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_default_policy_accepts_any_block() {
+        let policy = BlockPolicy::default();
+        assert!(policy.check(&block_with_colors(&[COLOR_ID_1])).is_ok());
+    }
+
+    #[test]
+    fn test_denied_color_id() {
+        let policy = BlockPolicy {
+            allowed_color_ids: None,
+            denied_color_ids: vec![color_id(COLOR_ID_2)],
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&get_block(0)).is_ok());
+        assert!(policy.check(&block_with_colors(&[COLOR_ID_1])).is_ok());
+        match policy.check(&block_with_colors(&[COLOR_ID_1, COLOR_ID_2])) {
+            Err(Error::DisallowedColorId(id)) => assert_eq!(id, color_id(COLOR_ID_2)),
+            r => panic!("Should be DisallowedColorId, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_allowed_color_id() {
+        let policy = BlockPolicy {
+            allowed_color_ids: Some(vec![color_id(COLOR_ID_1)]),
+            denied_color_ids: vec![],
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&get_block(0)).is_ok());
+        assert!(policy.check(&block_with_colors(&[COLOR_ID_1])).is_ok());
+        match policy.check(&block_with_colors(&[COLOR_ID_2])) {
+            Err(Error::DisallowedColorId(id)) => assert_eq!(id, color_id(COLOR_ID_2)),
+            r => panic!("Should be DisallowedColorId, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_max_block_size() {
+        let block = block_with_colors(&[COLOR_ID_1]);
+        let size = block.payload().len() as u64;
+        let policy = BlockPolicy {
+            max_block_size: Some(size),
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&block).is_ok());
+
+        let policy = BlockPolicy {
+            max_block_size: Some(size - 1),
+            ..BlockPolicy::default()
+        };
+        match policy.check(&block) {
+            Err(Error::BlockPolicyViolation(_)) => {}
+            r => panic!("Should be BlockPolicyViolation, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_max_block_weight() {
+        // coinbase has the witness of the commitment, which weighs 1 for a byte instead of 4.
+        let block = get_block(0);
+        let size = block.payload().len() as u64;
+        let policy = BlockPolicy {
+            max_block_weight: Some(size * WITNESS_SCALE_FACTOR - 1),
+            ..BlockPolicy::default()
+        };
+        assert!(policy.check(&block).is_ok());
+
+        let policy = BlockPolicy {
+            max_block_weight: Some(size),
+            ..BlockPolicy::default()
+        };
+        match policy.check(&block) {
+            Err(Error::BlockPolicyViolation(_)) => {}
+            r => panic!("Should be BlockPolicyViolation, but {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! HTTP listener which serves the metrics to Prometheus.
+//!
+//! `GET /metrics` returns `Metrics::render` in the text exposition format. Other paths are not
+//! found. Requests are served one by one on a thread, because a scrape is small and infrequent.
+
+use crate::errors::Error;
+use crate::metrics::Metrics;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Timeout of reading the request and writing the response to a client.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct MetricsListener {
+    listener: TcpListener,
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsListener {
+    /// `addr` is `HOST:PORT` to listen on.
+    pub fn bind(addr: &str, metrics: Arc<Metrics>) -> Result<MetricsListener, Error> {
+        Ok(MetricsListener {
+            listener: TcpListener::bind(addr)?,
+            metrics,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Start the thread which serves the requests.
+    pub fn spawn(self) -> JoinHandle<()> {
+        std::thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let result = stream.and_then(|stream| self.respond(stream));
+                if let Err(e) = result {
+                    log::warn!("Failed to serve metrics: {:?}", e);
+                }
+            }
+        })
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // read the headers, so that the client doesn't get reset while sending them.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("");
+        let path = parts.next().unwrap_or("").split('?').next().unwrap_or("");
+        let (status, body) = if method == "GET" && path == "/metrics" {
+            ("200 OK", self.metrics.render())
+        } else {
+            ("404 Not Found", "Not Found\n".to_string())
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::sync::atomic::Ordering;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let metrics = Arc::new(Metrics::new(Duration::from_secs(5), 5));
+        metrics.rounds_completed.fetch_add(3, Ordering::Relaxed);
+        let listener = MetricsListener::bind("127.0.0.1:0", metrics.clone()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.spawn();
+
+        let response = get(addr, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body = &response[response.find("\r\n\r\n").unwrap() + 4..];
+        assert_eq!(body, metrics.render());
+        assert!(body.contains("tapyrus_signer_rounds_completed_total 3\n"));
+
+        let response = get(addr, "/");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -2,7 +2,15 @@
 // Distributed under the MIT software license, see the accompanying
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use bitcoin::Address;
+use hyper::header::{Authorization, Basic, ContentLength, ContentType, Headers};
 use log::Level::Trace;
 use log::{log_enabled, trace};
 use secp256k1::Signature;
@@ -12,6 +20,41 @@ use serde_json::Value;
 use crate::blockdata::Block;
 use crate::errors::Error;
 
+/// Default time limit(sec) for waiting a response of a RPC request.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// Default time limit(sec) for connecting to Tapyrus Core.
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 5;
+/// Default maximum size(bytes) of a RPC response body.
+pub const DEFAULT_MAX_RESPONSE_SIZE: u64 = 32 * 1024 * 1024;
+/// Default count of retries of a RPC call which failed with a transient error.
+pub const DEFAULT_RETRIES: u32 = 2;
+/// Default wait(msec) before the first retry. It doubles on each retry.
+pub const DEFAULT_RETRY_BACKOFF_MILLIS: u64 = 500;
+/// RPC methods which the signer calls. RPC user of the signer needs permission only for these.
+pub const REQUIRED_RPC_METHODS: [&str; 6] = [
+    "getblockchaininfo",
+    "getnetworkinfo",
+    "getnewblock",
+    "testproposedblock",
+    "combineblocksigs",
+    "submitblock",
+];
+/// Minimum version of Tapyrus Core which the signer supports. It is in the format of `version`
+/// field of getnetworkinfo, like 10000 for v0.1.0.
+pub const MIN_CORE_VERSION: u64 = 10000;
+/// Latest version of Tapyrus Core which the signer is tested with.
+pub const TESTED_CORE_VERSION: u64 = 10000;
+/// Wallet RPC methods which the signer never calls. Permission for these is too broad.
+const WALLET_RPC_METHODS: [&str; 2] = ["dumpprivkey", "sendtoaddress"];
+/// RPC methods which only read the chain, so retrying them has no side effect. Other methods,
+/// like submitblock, are never retried.
+const IDEMPOTENT_RPC_METHODS: [&str; 4] = [
+    "getblockchaininfo",
+    "getnetworkinfo",
+    "getblockhash",
+    "getblock",
+];
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CombineBlockSigsResult {
     hex: String,
@@ -29,8 +72,23 @@ pub struct GetBlockchainInfoResult {
     pub initialblockdownload: bool,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct GetNetworkInfoResult {
+    pub version: u64,
+    pub subversion: String,
+}
+
 pub struct Rpc {
-    client: jsonrpc::client::Client,
+    url: String,
+    user: Option<String>,
+    pass: Option<String>,
+    client: hyper::Client,
+    connect_timeout: Arc<AtomicU64>,
+    timeout: Duration,
+    nonce: AtomicUsize,
+    max_response_size: u64,
+    retries: u32,
+    retry_backoff: Duration,
 }
 
 pub trait TapyrusApi {
@@ -46,24 +104,316 @@ pub trait TapyrusApi {
     fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error>;
 }
 
+/// TLS settings for `https://` RPC endpoint.
+#[derive(Default, Clone)]
+pub struct TlsConfig {
+    /// CA certificate file(PEM) which is trusted in addition to the system trust store.
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate and its private key file(PKCS#12) for client authentication.
+    pub client_cert: Option<PathBuf>,
+    /// Password of `client_cert`.
+    pub client_cert_pass: String,
+}
+
+/// Password is redacted so that it never appears in logs.
+impl std::fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("ca_cert", &self.ca_cert)
+            .field("client_cert", &self.client_cert)
+            .field("client_cert_pass", &"***")
+            .finish()
+    }
+}
+
+/// Cause of the connection error, which tells that it is the connect timeout rather than the
+/// read timeout.
+#[derive(Debug)]
+pub struct ConnectTimeout;
+
+impl std::fmt::Display for ConnectTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "connect timeout")
+    }
+}
+
+impl std::error::Error for ConnectTimeout {}
+
+/// HTTP connector which gives up connecting after the timeout(msec). hyper's connector waits as
+/// long as the OS does.
+struct TimeoutConnector {
+    timeout: Arc<AtomicU64>,
+}
+
+impl hyper::net::NetworkConnector for TimeoutConnector {
+    type Stream = hyper::net::HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<Self::Stream> {
+        if scheme != "http" {
+            return Err(hyper::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid scheme for Http",
+            )));
+        }
+        let timeout = Duration::from_millis(self.timeout.load(Ordering::Relaxed));
+        let mut last_error = None;
+        for addr in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(hyper::net::HttpStream(stream)),
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                    last_error = Some(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        ConnectTimeout,
+                    ))
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(hyper::Error::Io(last_error.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        })))
+    }
+}
+
 impl Rpc {
     pub fn new(url: String, user: Option<String>, pass: Option<String>) -> Self {
+        let connect_timeout = Rpc::default_connect_timeout();
+        let connector = TimeoutConnector {
+            timeout: connect_timeout.clone(),
+        };
+        let client = hyper::Client::with_connector(hyper::client::pool::Pool::with_connector(
+            Default::default(),
+            connector,
+        ));
+        Rpc::with_client(url, user, pass, client, connect_timeout)
+    }
+
+    fn default_connect_timeout() -> Arc<AtomicU64> {
+        Arc::new(AtomicU64::new(DEFAULT_CONNECT_TIMEOUT_SECS * 1000))
+    }
+
+    /// Create RPC client for `https://` endpoint.
+    #[cfg(feature = "tls")]
+    pub fn new_with_tls(
+        url: String,
+        user: Option<String>,
+        pass: Option<String>,
+        tls: &TlsConfig,
+    ) -> Result<Self, Error> {
+        fn tls_error(e: native_tls::Error) -> Error {
+            Error::RpcTlsError(e.to_string())
+        }
+
+        let mut builder = native_tls::TlsConnector::builder();
+        if let Some(ref path) = tls.ca_cert {
+            let pem = std::fs::read(path)?;
+            builder
+                .add_root_certificate(native_tls::Certificate::from_pem(&pem).map_err(tls_error)?);
+        }
+        if let Some(ref path) = tls.client_cert {
+            let der = std::fs::read(path)?;
+            builder.identity(
+                native_tls::Identity::from_pkcs12(&der, &tls.client_cert_pass)
+                    .map_err(tls_error)?,
+            );
+        }
+        let ssl = hyper_native_tls::NativeTlsClient::from(builder.build().map_err(tls_error)?);
+        let connect_timeout = Rpc::default_connect_timeout();
+        let connector = TimeoutConnector {
+            timeout: connect_timeout.clone(),
+        };
+        let client = hyper::Client::with_connector(hyper::net::HttpsConnector::with_connector(
+            ssl, connector,
+        ));
+        Ok(Rpc::with_client(url, user, pass, client, connect_timeout))
+    }
+
+    fn with_client(
+        url: String,
+        user: Option<String>,
+        pass: Option<String>,
+        client: hyper::Client,
+        connect_timeout: Arc<AtomicU64>,
+    ) -> Self {
         // Check that if we have a password, we have a username; other way around is ok
         debug_assert!(pass.is_none() || user.is_some());
-        Rpc {
-            client: jsonrpc::client::Client::new(url, user, pass),
+        let mut rpc = Rpc {
+            url,
+            user,
+            pass,
+            client,
+            connect_timeout,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            nonce: AtomicUsize::new(0),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            retries: 0,
+            retry_backoff: Duration::from_millis(DEFAULT_RETRY_BACKOFF_MILLIS),
+        };
+        rpc.set_timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS));
+        rpc
+    }
+
+    /// Set time limit for sending a request and receiving its whole response.
+    /// If Tapyrus Core doesn't respond within this limit, the call fails with `Error::RpcTimeout`.
+    /// It doesn't include connecting, which is limited by `set_connect_timeout`.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+        self.client.set_read_timeout(Some(timeout));
+        self.client.set_write_timeout(Some(timeout));
+    }
+
+    /// Set time limit for connecting to Tapyrus Core. If the connection isn't established within
+    /// this limit, the call fails with `Error::RpcConnectTimeout`. Default is 5 sec.
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.connect_timeout
+            .store(timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Set maximum size of a response body.
+    /// If the response is bigger than this, the call fails with `Error::RpcResponseTooLarge`.
+    pub fn set_max_response_size(&mut self, max_response_size: u64) {
+        self.max_response_size = max_response_size;
+    }
+
+    /// Retry a call of the read-only methods up to `retries` times when it fails with a transient
+    /// error, like timeout, connection reset or 5xx response. It waits `backoff` before the first
+    /// retry, and the wait doubles on each retry. All attempts of a call end within the timeout
+    /// of `set_timeout`. Errors returned by Tapyrus Core, like invalid parameter, are never
+    /// retried. Default is no retry.
+    pub fn set_retry(&mut self, retries: u32, backoff: Duration) {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+    }
+
+    fn build_request<'a, 'b>(
+        &self,
+        name: &'a str,
+        params: &'b [serde_json::Value],
+    ) -> jsonrpc::Request<'a, 'b> {
+        let nonce = self.nonce.fetch_add(1, Ordering::SeqCst) + 1;
+        jsonrpc::Request {
+            method: name,
+            params,
+            id: nonce.into(),
+            jsonrpc: Some("2.0"),
+        }
+    }
+
+    fn send_request(
+        &self,
+        request: &jsonrpc::Request,
+        deadline: Instant,
+    ) -> Result<jsonrpc::Response, Error> {
+        let body = serde_json::to_vec(request)?;
+
+        let mut headers = Headers::new();
+        headers.set(ContentType::json());
+        if let Some(ref user) = self.user {
+            headers.set(Authorization(Basic {
+                username: user.clone(),
+                password: self.pass.clone(),
+            }));
+        }
+
+        let send = || {
+            self.client
+                .post(&self.url)
+                .headers(headers.clone())
+                .body(&body[..])
+                .send()
+        };
+        let mut stream = match send() {
+            Ok(s) => s,
+            // Hyper keeps connections in its pool and can't tell that a connection was dropped
+            // until it sends on it. Re-sending makes hyper open a new connection.
+            Err(hyper::Error::Io(ref e))
+                if e.kind() == std::io::ErrorKind::BrokenPipe
+                    || e.kind() == std::io::ErrorKind::ConnectionAborted =>
+            {
+                send().map_err(Error::from)?
+            }
+            Err(e) => return Err(Error::from(e)),
+        };
+
+        // Tapyrus Core responds 403 when `rpcwhitelist` doesn't allow the user to call the method.
+        if stream.status == hyper::status::StatusCode::Forbidden {
+            return Err(Error::RpcMethodNotAllowed(request.method.to_string()));
         }
+
+        if let Some(&ContentLength(len)) = stream.headers.get::<ContentLength>() {
+            if len > self.max_response_size {
+                return Err(Error::RpcResponseTooLarge(self.max_response_size));
+            }
+        }
+        let raw = read_body(&mut stream, self.max_response_size, deadline)?;
+
+        let response: jsonrpc::Response = match serde_json::from_slice(&raw) {
+            Ok(r) => r,
+            // Tapyrus Core responds RPC errors with 500 and JSON body, so 5xx without JSON body
+            // is an error of the server itself, like the work queue is full.
+            Err(_) if stream.status.is_server_error() => {
+                return Err(Error::RpcServerError(stream.status.to_u16()));
+            }
+            Err(e) => return Err(Error::from(e)),
+        };
+        if response.jsonrpc.is_some() && response.jsonrpc != Some("2.0".to_string()) {
+            return Err(Error::JsonRpc(jsonrpc::Error::VersionMismatch));
+        }
+        if response.id != request.id {
+            return Err(Error::JsonRpc(jsonrpc::Error::NonceMismatch));
+        }
+        Ok(response)
     }
 
     fn call<T>(&self, name: &str, params: &[serde_json::Value]) -> Result<T, Error>
     where
         T: serde::de::DeserializeOwned,
     {
-        let req = self.client.build_request(name, params);
+        let deadline = Instant::now() + self.timeout;
+        let retries = if IDEMPOTENT_RPC_METHODS.contains(&name) {
+            self.retries
+        } else {
+            0
+        };
+        let mut backoff = self.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            match self.call_once(name, params, deadline) {
+                Err(ref e)
+                    if attempt < retries
+                        && is_retryable(e)
+                        && Instant::now() + backoff < deadline =>
+                {
+                    warn!(
+                        "RPC {} failed. Retry after {:?}. error: {:?}",
+                        name, backoff, e
+                    );
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn call_once<T>(
+        &self,
+        name: &str,
+        params: &[serde_json::Value],
+        deadline: Instant,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let req = self.build_request(name, params);
 
         trace!("JSON-RPC request: {}", serde_json::to_string(&req).unwrap());
 
-        match self.client.send_request(&req) {
+        match self.send_request(&req, deadline) {
             Ok(resp) => {
                 if log_enabled!(Trace) {
                     trace!(
@@ -82,10 +432,67 @@ impl Rpc {
                     Err(e) => Err(Error::JsonRpc(e)),
                 }
             }
-            Err(e) => Err(Error::from(e)),
+            Err(e) => Err(e),
         }
     }
 
+    /// Check that the RPC user can call all of `REQUIRED_RPC_METHODS`. Each method is called
+    /// without parameters, so it fails with a parameter error and has no side effect if the
+    /// user is allowed to call it. Allowed wallet methods are reported as warnings.
+    pub fn verify_permissions(&self) -> Result<(), Error> {
+        for method in REQUIRED_RPC_METHODS.iter() {
+            match self.call::<Value>(method, &[]) {
+                Ok(_) | Err(Error::InvalidRequest(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        for method in WALLET_RPC_METHODS.iter() {
+            match self.call::<Value>(method, &[]) {
+                Ok(_) | Err(Error::InvalidRequest(_)) => warn!(
+                    "RPC user can call {}. The signer doesn't need wallet RPCs.",
+                    method
+                ),
+                Err(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn getnetworkinfo(&self) -> Result<GetNetworkInfoResult, Error> {
+        self.call::<GetNetworkInfoResult>("getnetworkinfo", &[])
+    }
+
+    /// Check that Tapyrus Core isn't older than `minimum`. Newer version than
+    /// `TESTED_CORE_VERSION` is reported as a warning.
+    pub fn verify_core_version(&self, minimum: u64) -> Result<(), Error> {
+        let info = self.getnetworkinfo()?;
+        if info.version < minimum {
+            return Err(Error::UnsupportedCoreVersion {
+                version: info.version,
+                minimum,
+            });
+        }
+        if info.version > TESTED_CORE_VERSION {
+            warn!(
+                "Tapyrus Core {} is newer than the version which the signer is tested with.",
+                info.subversion
+            );
+        }
+        Ok(())
+    }
+
+    /// Hash of the block at `height` in the best chain.
+    pub fn getblockhash(&self, height: u64) -> Result<String, Error> {
+        self.call::<String>("getblockhash", &[height.into()])
+    }
+
+    /// Get the block which has `hash`, including its proof.
+    pub fn getblock(&self, hash: &str) -> Result<Block, Error> {
+        let args = [hash.into(), false.into()];
+        let hex = self.call::<String>("getblock", &args)?;
+        Block::from_hex(&hex)
+    }
+
     pub fn test_connection(&self) -> Result<(), Error> {
         match self.getblockchaininfo() {
             Ok(_) => Ok(()),
@@ -94,6 +501,44 @@ impl Rpc {
     }
 }
 
+/// Read the response body up to `limit` bytes until `deadline`. The read timeout of the socket
+/// limits each read only, so a server which sends the body slowly is stopped by the deadline.
+fn read_body<R: Read>(reader: &mut R, limit: u64, deadline: Instant) -> Result<Vec<u8>, Error> {
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        if Instant::now() >= deadline {
+            return Err(Error::RpcTimeout);
+        }
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(raw),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::from(hyper::Error::Io(e))),
+        };
+        raw.extend_from_slice(&buf[..n]);
+        // bodies without Content-Length header may exceed the limit.
+        if raw.len() as u64 > limit {
+            return Err(Error::RpcResponseTooLarge(limit));
+        }
+    }
+}
+
+/// Transient errors which may succeed on retry.
+fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::RpcTimeout | Error::RpcConnectTimeout | Error::RpcServerError(_) => true,
+        Error::JsonRpc(jsonrpc::Error::Hyper(hyper::Error::Io(e))) => matches!(
+            e.kind(),
+            std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::BrokenPipe
+        ),
+        _ => false,
+    }
+}
+
 impl TapyrusApi for Rpc {
     /// Call getnewblock rpc
     fn getnewblock(&self, address: &Address) -> Result<Block, Error> {
@@ -149,6 +594,7 @@ pub mod tests {
     use crate::sign::sign;
     use crate::test_helper::{get_block, TestKeys};
     use secp256k1::Secp256k1;
+    use std::time::{Duration, Instant};
 
     pub fn get_rpc_client() -> Rpc {
         Rpc::new(
@@ -246,6 +692,393 @@ pub mod tests {
         }
     }
 
+    /// HTTP response with `status` line like "200 OK" and JSON `body`, which may be empty.
+    fn json_response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    /// Start a HTTP server which answers a request with `body` after `delay`.
+    /// Returns url of the server.
+    pub fn spawn_http_server(delay: std::time::Duration, body: String) -> String {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                std::thread::sleep(delay);
+                let response = json_response("200 OK", &body);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        url
+    }
+
+    fn getblockchaininfo_response() -> String {
+        r#"{"result":{"chain":"regtest","blocks":1,"headers":1,"bestblockhash":"xxx","mediantime":0,"initialblockdownload":false},"error":null,"id":1}"#.to_string()
+    }
+
+    #[test]
+    fn test_call_with_mock_server() {
+        let url = spawn_http_server(Duration::from_millis(0), getblockchaininfo_response());
+        let rpc = Rpc::new(url, None, None);
+
+        let result = rpc.getblockchaininfo().unwrap();
+        assert_eq!(result.blocks, 1);
+    }
+
+    #[test]
+    fn test_getblock_with_mock_server() {
+        let block = get_block(0);
+        let body = format!(r#"{{"result":"{}","error":null,"id":1}}"#, block.hex());
+        let url = spawn_http_server(Duration::from_millis(0), body);
+        let rpc = Rpc::new(url, None, None);
+
+        assert_eq!(rpc.getblock("xxx").unwrap(), block);
+    }
+
+    /// Start a HTTPS server with the self-signed certificate for localhost, which answers each
+    /// request with `body`. Returns url of the server.
+    #[cfg(feature = "tls")]
+    fn spawn_https_server(body: String) -> String {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        let der = std::fs::read("tests/resources/tls/localhost.p12").unwrap();
+        let identity = native_tls::Identity::from_pkcs12(&der, "pass").unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "https://localhost:{}",
+            listener.local_addr().unwrap().port()
+        );
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                // handshake fails if the client doesn't trust the certificate.
+                let mut stream = match stream.map(|s| acceptor.accept(s)) {
+                    Ok(Ok(stream)) => stream,
+                    _ => continue,
+                };
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let response = json_response("200 OK", &body);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        url
+    }
+
+    #[test]
+    #[cfg(feature = "tls")]
+    fn test_tls_with_self_signed_server() {
+        let url = spawn_https_server(getblockchaininfo_response());
+        let tls = TlsConfig {
+            ca_cert: Some(PathBuf::from("tests/resources/tls/localhost.pem")),
+            ..TlsConfig::default()
+        };
+        let rpc = Rpc::new_with_tls(url.clone(), None, None, &tls).unwrap();
+        assert_eq!(rpc.getblockchaininfo().unwrap().blocks, 1);
+
+        // the certificate isn't trusted without the CA certificate.
+        let rpc = Rpc::new_with_tls(url, None, None, &TlsConfig::default()).unwrap();
+        assert!(rpc.getblockchaininfo().is_err());
+    }
+
+    /// Start a HTTP server which answers each request with the next of `responses`.
+    fn spawn_http_server_with_responses(responses: Vec<String>) -> String {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                if let Ok((mut stream, _)) = listener.accept() {
+                    let mut buf = [0u8; 4096];
+                    let _ = stream.read(&mut buf);
+                    let _ = stream.write_all(response.as_bytes());
+                }
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_retry() {
+        let unavailable = json_response("503 Service Unavailable", "");
+        // nonce of the second request is 2.
+        let url = spawn_http_server_with_responses(vec![
+            unavailable.clone(),
+            json_response(
+                "200 OK",
+                &getblockchaininfo_response().replace(r#""id":1"#, r#""id":2"#),
+            ),
+        ]);
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_retry(1, Duration::from_millis(10));
+
+        let result = rpc.getblockchaininfo().unwrap();
+        assert_eq!(result.blocks, 1);
+    }
+
+    #[test]
+    fn test_no_retry_for_submitblock() {
+        let unavailable = json_response("503 Service Unavailable", "");
+        let url = spawn_http_server_with_responses(vec![
+            unavailable.clone(),
+            json_response("200 OK", r#"{"result":null,"error":null,"id":2}"#),
+        ]);
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_retry(1, Duration::from_millis(10));
+
+        match rpc.submitblock(&get_block(0)) {
+            Err(Error::RpcServerError(503)) => {}
+            r => panic!("Should be RpcServerError, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_retry_within_timeout() {
+        let unavailable = json_response("503 Service Unavailable", "");
+        let url = spawn_http_server_with_responses(vec![
+            unavailable.clone(),
+            unavailable.clone(),
+            json_response(
+                "200 OK",
+                &getblockchaininfo_response().replace(r#""id":1"#, r#""id":3"#),
+            ),
+        ]);
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_timeout(Duration::from_millis(500));
+        rpc.set_retry(3, Duration::from_millis(200));
+
+        // the second retry after 400 msec exceeds the timeout.
+        let started_at = Instant::now();
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcServerError(503)) => {}
+            r => panic!("Should be RpcServerError, but {:?}", r),
+        }
+        assert!(started_at.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_no_retry_for_rpc_error() {
+        let error = r#"{"result":null,"error":{"code":-8,"message":"invalid parameter"},"id":1}"#;
+        let url = spawn_http_server_with_responses(vec![
+            json_response("200 OK", error),
+            json_response("200 OK", &getblockchaininfo_response()),
+        ]);
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_retry(1, Duration::from_millis(10));
+
+        match rpc.getblockchaininfo() {
+            Err(Error::InvalidRequest(e)) => assert_eq!(e.code, -8),
+            r => panic!("Should be InvalidRequest, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_server_error_without_retry() {
+        let unavailable = json_response("503 Service Unavailable", "");
+        let url = spawn_http_server_with_responses(vec![unavailable.clone()]);
+        let rpc = Rpc::new(url, None, None);
+
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcServerError(503)) => {}
+            r => panic!("Should be RpcServerError, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let url = spawn_http_server(Duration::from_millis(1000), getblockchaininfo_response());
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_timeout(Duration::from_millis(100));
+
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcTimeout) => {}
+            r => panic!("Should be RpcTimeout, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_timeout_for_trickling_response() {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        // the server sends a byte of the body in each 20 msec. Each read is shorter than the
+        // timeout, but the whole body takes seconds.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let response = json_response("200 OK", &getblockchaininfo_response());
+                let body_start = response.find("\r\n\r\n").unwrap() + 4;
+                let (head, body) = response.as_bytes().split_at(body_start);
+                let _ = stream.write_all(head);
+                for byte in body {
+                    if stream.write_all(&[*byte]).is_err() {
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+            }
+        });
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_timeout(Duration::from_millis(300));
+
+        let started_at = Instant::now();
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcTimeout) => {}
+            r => panic!("Should be RpcTimeout, but {:?}", r),
+        }
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_read_timeout_with_connect_timeout() {
+        // connects quickly, but the response is slower than the read timeout.
+        let url = spawn_http_server(Duration::from_millis(1000), getblockchaininfo_response());
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_connect_timeout(Duration::from_millis(50));
+        rpc.set_timeout(Duration::from_millis(200));
+
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcTimeout) => {}
+            r => panic!("Should be RpcTimeout, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_connect_timeout_doesnt_limit_response() {
+        let url = spawn_http_server(Duration::from_millis(300), getblockchaininfo_response());
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_connect_timeout(Duration::from_millis(50));
+        rpc.set_timeout(Duration::from_secs(3));
+
+        assert_eq!(rpc.getblockchaininfo().unwrap().blocks, 1);
+    }
+
+    #[test]
+    fn test_connect_timeout_error() {
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, ConnectTimeout);
+        match Error::from(hyper::Error::Io(io)) {
+            Error::RpcConnectTimeout => {}
+            r => panic!("Should be RpcConnectTimeout, but {:?}", r),
+        }
+        let io = std::io::Error::from(std::io::ErrorKind::TimedOut);
+        match Error::from(hyper::Error::Io(io)) {
+            Error::RpcTimeout => {}
+            r => panic!("Should be RpcTimeout, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_response_too_large() {
+        let url = spawn_http_server(Duration::from_millis(0), getblockchaininfo_response());
+        let mut rpc = Rpc::new(url, None, None);
+        rpc.set_max_response_size(16);
+
+        match rpc.getblockchaininfo() {
+            Err(Error::RpcResponseTooLarge(16)) => {}
+            r => panic!("Should be RpcResponseTooLarge, but {:?}", r),
+        }
+    }
+
+    /// Start a HTTP server which answers 403 for `forbidden` methods, and parameter error for
+    /// other methods like Tapyrus Core does for calls without parameters.
+    fn spawn_whitelist_server(forbidden: Vec<&'static str>) -> String {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().filter_map(|s| s.ok()) {
+                // read until the whole JSON body is received.
+                let mut raw = Vec::new();
+                let request: Value = loop {
+                    let mut buf = [0u8; 4096];
+                    let n = stream.read(&mut buf).unwrap();
+                    raw.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&raw).to_string();
+                    if let Some(i) = text.find("\r\n\r\n") {
+                        if let Ok(v) = serde_json::from_str(&text[i + 4..]) {
+                            break v;
+                        }
+                    }
+                };
+                let response = if forbidden.contains(&request["method"].as_str().unwrap()) {
+                    json_response("403 Forbidden", "")
+                } else {
+                    let body = serde_json::json!({
+                        "result": null,
+                        "error": {"code": -1, "message": "invalid parameters"},
+                        "id": request["id"],
+                    })
+                    .to_string();
+                    json_response("200 OK", &body)
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        url
+    }
+
+    fn getnetworkinfo_response(version: u64) -> String {
+        format!(
+            r#"{{"result":{{"version":{},"subversion":"/Tapyrus Core:0.1.0/"}},"error":null,"id":1}}"#,
+            version
+        )
+    }
+
+    #[test]
+    fn test_verify_core_version() {
+        let url = spawn_http_server(Duration::from_millis(0), getnetworkinfo_response(10000));
+        let rpc = Rpc::new(url, None, None);
+        assert!(rpc.verify_core_version(MIN_CORE_VERSION).is_ok());
+    }
+
+    #[test]
+    fn test_verify_core_version_too_old() {
+        let url = spawn_http_server(Duration::from_millis(0), getnetworkinfo_response(9900));
+        let rpc = Rpc::new(url, None, None);
+        match rpc.verify_core_version(MIN_CORE_VERSION) {
+            Err(Error::UnsupportedCoreVersion { version, minimum }) => {
+                assert_eq!(version, 9900);
+                assert_eq!(minimum, MIN_CORE_VERSION);
+            }
+            r => panic!("Should be UnsupportedCoreVersion, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_verify_permissions() {
+        let url = spawn_whitelist_server(vec!["dumpprivkey", "sendtoaddress"]);
+        let rpc = Rpc::new(url, None, None);
+        assert!(rpc.verify_permissions().is_ok());
+    }
+
+    #[test]
+    fn test_verify_permissions_forbidden() {
+        let url = spawn_whitelist_server(vec!["combineblocksigs"]);
+        let rpc = Rpc::new(url, None, None);
+        match rpc.verify_permissions() {
+            Err(Error::RpcMethodNotAllowed(method)) => assert_eq!(method, "combineblocksigs"),
+            r => panic!("Should be RpcMethodNotAllowed, but {:?}", r),
+        }
+    }
+
     /// TODO: use rpc mock. Now this test needs tapyrus node process.
     #[test]
     #[ignore]
@@ -2,9 +2,108 @@
 // Distributed under the MIT software license, see the accompanying
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
-use crate::blockdata::BlockHash;
-use bitcoin::PrivateKey;
-use secp256k1::{Message, Secp256k1, Signature};
+use crate::blockdata::{Block, BlockHash};
+use crate::errors::Error;
+use bitcoin::{Network, PrivateKey, PublicKey};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use secp256k1::{Message, Secp256k1, SecretKey, Signature};
+use std::str::FromStr;
+
+/// Variant of the message which signers sign for the block. Tapyrus has only one variant now.
+/// It is selectable so that the node and the offline tools stay aligned on a protocol upgrade.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SighashVariant {
+    /// `sighash`. Hash of the block header without proof.
+    #[default]
+    Header,
+}
+
+impl SighashVariant {
+    pub fn sighash(self, block: &Block) -> Result<Message, Error> {
+        match self {
+            SighashVariant::Header => sighash(block),
+        }
+    }
+}
+
+impl FromStr for SighashVariant {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "header" => Ok(SighashVariant::Header),
+            _ => Err(Error::InvalidArgs(format!(
+                "'{}' is unknown sighash variant.",
+                s
+            ))),
+        }
+    }
+}
+
+/// Message signed by each signer for the block. This is the hash of the block header without
+/// proof.
+pub fn sighash(block: &Block) -> Result<Message, Error> {
+    let hash = block.hash()?;
+    Ok(Message::from_slice(&(hash.borrow_inner())[..])?)
+}
+
+/// Count of the signers in `pubkey_list` whose signature in the proof of the block is valid.
+pub fn verify_proof(
+    block: &Block,
+    variant: SighashVariant,
+    pubkey_list: &[PublicKey],
+) -> Result<usize, Error> {
+    let message = variant.sighash(block)?;
+    let signatures: Vec<Signature> = block
+        .proof()?
+        .iter()
+        .filter_map(|der| Signature::from_der(der).ok())
+        .collect();
+    Ok(count_signers(&message, &signatures, pubkey_list))
+}
+
+/// Count of the signers in `pubkey_list` who has a valid signature for `message` in `signatures`.
+pub fn count_signers(
+    message: &Message,
+    signatures: &[Signature],
+    pubkey_list: &[PublicKey],
+) -> usize {
+    let secp = Secp256k1::verification_only();
+    pubkey_list
+        .iter()
+        .filter(|pk| {
+            signatures
+                .iter()
+                .any(|sig| secp.verify(message, sig, &pk.key).is_ok())
+        })
+        .count()
+}
+
+/// Sign arbitrary 32 bytes `hash` instead of the block sighash by each of `private_keys`, with
+/// the same primitive as signing blocks. This is for checking interop with test vectors of
+/// other implementations, and never used for producing blocks.
+#[cfg(feature = "signmessage")]
+pub fn sign_hash(
+    private_keys: &[PrivateKey],
+    hash: &[u8],
+) -> Result<(Message, Vec<Signature>), Error> {
+    if hash.len() != 32 {
+        return Err(Error::InvalidLength(32, hash.len()));
+    }
+    let message = Message::from_slice(hash)?;
+    let signatures = private_keys
+        .iter()
+        .map(|key| sign_message(key, &message))
+        .collect();
+    Ok((message, signatures))
+}
+
+/// Nonce of the signature is derived from the private key and the message by RFC6979, so
+/// signing doesn't use the random number generator.
+pub fn sign_message(private_key: &PrivateKey, message: &Message) -> Signature {
+    Secp256k1::signing_only().sign(message, &(private_key.key))
+}
 
 pub fn sign(private_key: &PrivateKey, hash: &BlockHash) -> Signature {
     let sign = Secp256k1::signing_only();
@@ -12,6 +111,53 @@ pub fn sign(private_key: &PrivateKey, hash: &BlockHash) -> Signature {
     sign.sign(&message, &(private_key.key))
 }
 
+/// Generate a compressed private key with the random number generator of the OS.
+/// Returns `Error::InsufficientEntropy` if the generator is unavailable or fails the self test.
+pub fn generate_private_key(network: Network) -> Result<PrivateKey, Error> {
+    let mut rng = OsRng::new().map_err(|e| Error::InsufficientEntropy(e.to_string()))?;
+    generate_private_key_with(&mut rng, network)
+}
+
+/// Same as `generate_private_key`, but with the given random number generator.
+pub fn generate_private_key_with<R: RngCore>(
+    rng: &mut R,
+    network: Network,
+) -> Result<PrivateKey, Error> {
+    check_entropy(rng)?;
+    loop {
+        let mut bytes = [0u8; 32];
+        fill_random(rng, &mut bytes)?;
+        // retry if the bytes are out of range of secp256k1 private key.
+        if let Ok(key) = SecretKey::from_slice(&bytes) {
+            return Ok(PrivateKey {
+                compressed: true,
+                network,
+                key,
+            });
+        }
+    }
+}
+
+/// Self test of the random number generator. A generator which isn't seeded properly returns
+/// zeros or the same output repeatedly.
+fn check_entropy<R: RngCore>(rng: &mut R) -> Result<(), Error> {
+    let mut first = [0u8; 32];
+    let mut second = [0u8; 32];
+    fill_random(rng, &mut first)?;
+    fill_random(rng, &mut second)?;
+    if first == [0u8; 32] || first == second {
+        return Err(Error::InsufficientEntropy(
+            "random number generator returned the same output repeatedly.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn fill_random<R: RngCore>(rng: &mut R, bytes: &mut [u8]) -> Result<(), Error> {
+    rng.try_fill_bytes(bytes)
+        .map_err(|e| Error::InsufficientEntropy(e.to_string()))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -35,4 +181,116 @@ mod test {
         let public_key = private_key.public_key(&secp).key;
         assert!(&secp.verify(&message, &sig, &public_key).is_ok());
     }
+
+    #[test]
+    fn sighash_test() {
+        let message = sighash(&get_block(0)).unwrap();
+        assert_eq!(
+            "13d877fc2bd35a1829d2b7f0809c6ba64ad5589569a8e65e49855adb4e494a36",
+            hex::encode(&message[..])
+        );
+    }
+
+    #[test]
+    fn sighash_variant_test() {
+        let block = get_block(0);
+        let variant = SighashVariant::from_str("header").unwrap();
+        assert_eq!(variant, SighashVariant::default());
+        assert_eq!(variant.sighash(&block).unwrap(), sighash(&block).unwrap());
+
+        match SighashVariant::from_str("schnorr") {
+            Err(Error::InvalidArgs(_)) => {}
+            r => panic!("Should be InvalidArgs, but {:?}", r),
+        }
+    }
+
+    #[cfg(feature = "signmessage")]
+    #[test]
+    fn sign_hash_test() {
+        let testkeys = TestKeys::new();
+        let pubkeys = testkeys.pubkeys();
+        let hash = [0x11u8; 32];
+
+        let (message, signatures) = sign_hash(&testkeys.key[..3], &hash).unwrap();
+        assert_eq!(&message[..], &hash[..]);
+        assert_eq!(count_signers(&message, &signatures, &pubkeys), 3);
+        assert_eq!(count_signers(&message, &signatures, &pubkeys[3..]), 0);
+
+        match sign_hash(&testkeys.key[..3], &hash[..31]) {
+            Err(Error::InvalidLength(32, 31)) => {}
+            r => panic!("Should be InvalidLength, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn verify_proof_test() {
+        use bitcoin::consensus::encode::serialize;
+
+        let testkeys = TestKeys::new();
+        let candidate = get_block(0);
+        let block_hash = candidate.hash().unwrap();
+        let proof: Vec<Vec<u8>> = testkeys.key[..3]
+            .iter()
+            .map(|key| sign(key, &block_hash).serialize_der())
+            .collect();
+        let mut data = candidate.get_header_without_proof().to_vec();
+        data.extend(serialize(&proof));
+        data.extend(serialize(&candidate.transactions().unwrap()));
+        let block = Block::new(data);
+
+        let pubkeys = testkeys.pubkeys();
+        assert_eq!(block.proof().unwrap(), proof);
+        assert_eq!(
+            verify_proof(&block, SighashVariant::Header, &pubkeys).unwrap(),
+            3
+        );
+        assert_eq!(
+            verify_proof(&block, SighashVariant::Header, &pubkeys[1..]).unwrap(),
+            2
+        );
+        assert_eq!(
+            verify_proof(&candidate, SighashVariant::Header, &pubkeys).unwrap(),
+            0
+        );
+    }
+
+    /// Random number generator whose source is unavailable.
+    struct FailingRng;
+
+    impl RngCore for FailingRng {
+        fn next_u32(&mut self) -> u32 {
+            unimplemented!()
+        }
+        fn next_u64(&mut self) -> u64 {
+            unimplemented!()
+        }
+        fn fill_bytes(&mut self, _dest: &mut [u8]) {
+            unimplemented!()
+        }
+        fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand::Error> {
+            Err(rand::Error::new(
+                rand::ErrorKind::NotReady,
+                "entropy pool isn't initialized",
+            ))
+        }
+    }
+
+    #[test]
+    fn generate_private_key_test() {
+        let key = generate_private_key(Network::Regtest).unwrap();
+        assert!(key.compressed);
+        assert_eq!(key.network, Network::Regtest);
+        assert_ne!(key, generate_private_key(Network::Regtest).unwrap());
+
+        match generate_private_key_with(&mut FailingRng, Network::Regtest) {
+            Err(Error::InsufficientEntropy(_)) => {}
+            r => panic!("Should be InsufficientEntropy, but {:?}", r),
+        }
+        // generator which isn't seeded returns a constant.
+        let mut constant = rand::rngs::mock::StepRng::new(0, 0);
+        match generate_private_key_with(&mut constant, Network::Regtest) {
+            Err(Error::InsufficientEntropy(_)) => {}
+            r => panic!("Should be InsufficientEntropy, but {:?}", r),
+        }
+    }
 }
@@ -2,32 +2,293 @@
 // Distributed under the MIT software license, see the accompanying
 // file COPYING or http://www.opensource.org/licenses/mit-license.php.
 
-use std::collections::HashMap;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use bitcoin::{Address, PrivateKey, PublicKey};
-use redis::ControlFlow;
+use rand::Rng;
+use serde::Serialize;
 
-use crate::blockdata::Block;
-use crate::net::{ConnectionManager, Message, MessageType, Signature, SignerID};
-use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
-use crate::sign::sign;
-use crate::timer::RoundTimeOutObserver;
+use crate::blockdata::{Block, BlockHash};
+use crate::election::{ElectionStrategy, LeaderElection};
+use crate::errors::Error;
+use crate::metrics::Metrics;
+use crate::net::{Message, MessageType, Signature, SignerID};
+use crate::policy::BlockPolicy;
+use crate::rpc::GetBlockchainInfoResult;
+use crate::sign::{sign_message, SighashVariant};
 
 /// Round interval.
 pub static ROUND_INTERVAL_DEFAULT_SECS: u64 = 60;
 /// Round time limit delta. Round timeout timer should be little longer than `ROUND_INTERVAL_DEFAULT_SECS`.
-static ROUND_TIMELIMIT_DELTA: u64 = 5;
+pub static ROUND_TIMELIMIT_DELTA: u64 = 5;
+/// Default interval(msec) of requesting candidate block again, when Tapyrus Core failed to create it.
+pub static POLL_INTERVAL_DEFAULT_MILLIS: u64 = 1000;
+/// Minimum of poll interval(msec), not to make too many requests to Tapyrus Core.
+pub static POLL_INTERVAL_MIN_MILLIS: u64 = 100;
+/// Default number of rounds tried for a block height before backing off.
+pub static MAX_ROUND_ATTEMPTS_DEFAULT: u32 = 10;
+/// Max multiplier of the round time limit, while the rounds for a block height keep failing.
+pub static MAX_ROUND_BACKOFF: u32 = 8;
+/// Default number of round time limits without progress, after which the watchdog resets the
+/// state machine.
+pub static WATCHDOG_ROUNDS_DEFAULT: u32 = 3;
+/// Number of blocks of the chain tip ahead of the last signed height, over which the node warns
+/// on startup that it may have missed blocks.
+static LAST_SIGNED_HEIGHT_GAP: u64 = 100;
+/// Rounds per signer searched for the round which the master is elected for.
+static ROUND_SYNC_LIMIT_PER_SIGNER: u64 = 16;
+/// Number of signatures from unknown public keys, over which the node warns that another
+/// federation may use the same channel.
+static UNKNOWN_SENDER_WARNING_COUNT: u64 = 10;
 
-pub struct SignerNode<T: TapyrusApi, C: ConnectionManager> {
-    connection_manager: C,
-    params: NodeParameters<T>,
+/// Signer node state machine.
+///
+/// The node does no I/O with the signer network and Tapyrus Core, and never sleeps. The `node`
+/// command and embedders drive it with their own I/O and clock:
+///
+/// 1. Wait for Tapyrus Core to finish IBD. Pass its `getblockchaininfo` to `chain_info`, and
+///    call `start` once.
+/// 2. Pass each message received from the signer network to `process_message`.
+/// 3. Call `tick` with the current time, at `NodeOutput::next_deadline` at the latest.
+/// 4. Pass new blocks notified by Tapyrus Core to `block_notified`, if they are subscribed.
+///
+/// Each of these methods returns `NodeOutput`. Broadcast its messages in order, and do its
+/// requests to Tapyrus Core and pass the results to `candidate_block`, `block_tested`,
+/// `block_submitted` and `chain_info`. They return `NodeOutput` too, so repeat it until the node
+/// requests nothing. `current_state` is the state after the call.
+pub struct SignerNode {
+    params: NodeParameters,
     current_state: NodeState,
-    stop_signal: Option<Receiver<u32>>,
+    /// Time passed to the current call of `start`, `process_message`, `tick` or the methods
+    /// which take results of the requests.
+    now: Instant,
+    /// Output of the current call.
+    output: NodeOutput,
+    /// The last result of `getblockchaininfo` which was passed to `chain_info`.
+    chain: Option<GetBlockchainInfoResult>,
     master_index: usize,
-    round_timer: RoundTimeOutObserver,
+    /// Round number for the leader election. See `sync_round` and `election_round`.
+    round: u64,
+    round_timelimit: Duration,
+    /// Time limit of the current round. `None` until the next `tick`.
+    round_deadline: Option<Instant>,
+    metrics: Arc<Metrics>,
+    /// Times when the round started and when the candidate block was broadcasted, on master.
+    round_started_at: Option<Instant>,
+    signature_wait_started_at: Option<Instant>,
+    /// Time when master starts the round after `round_duration`.
+    round_start_at: Option<Instant>,
+    /// Time when master requested candidate block, until `candidate_block` is called.
+    candidate_block_requested_at: Option<Instant>,
+    /// Master is waiting for Tapyrus Core to create candidate block.
+    waiting_candidate_block: bool,
+    next_poll: Option<Instant>,
+    /// Height of the block which the current round produces. Messages are sent with it and
+    /// `round_attempts`, and messages of other rounds are dropped. See `check_round`.
+    round_height: u64,
+    /// Count of failed rounds for the current block height, which is the round number for it.
+    round_attempts: u32,
+    /// Count of consecutive rounds as master, which ended without the signature of each signer.
+    /// Indexed by signer index.
+    missed_rounds: Vec<u32>,
+    /// Signer indices which master doesn't wait for until they send a signature again. See
+    /// `collection_quorum`.
+    excluded_signers: HashSet<usize>,
+    /// Time of the last progress of the state machine. `None` until the next `tick`.
+    last_progress: Option<Instant>,
+    /// Type and sender of the last received message, for the watchdog log.
+    last_message: Option<(&'static str, SignerID)>,
+    /// Masters whose candidate block this node signed in the current round, on member.
+    rounds_in_flight: Vec<SignerID>,
+    /// Members which are ready for the announced candidate block, on master.
+    readiness: Option<Readiness>,
+    /// Hash of the candidate block of each master in the current round, which this node and
+    /// the other members received. Keyed by signer index of the master.
+    observed_blocks: HashMap<usize, BlockHash>,
+    /// Masters which proposed different blocks in the current round.
+    equivocated_masters: HashSet<usize>,
+    /// Count of the round events.
+    round_events: u64,
+    /// The event of the current round is already written.
+    round_event_written: bool,
+    round_timings: RoundTimings,
+    /// Height of the block which this node signed in the current round.
+    signing_height: Option<u64>,
+    /// Best block hash of the chain when master proposed the candidate block. The round is
+    /// aborted when it changes, because the candidate block is stale.
+    round_tip: Option<String>,
+    /// Master requested the best block hash for `round_tip`.
+    waiting_round_tip: bool,
+    next_tip_check: Option<Instant>,
+    /// Hash of the block which Tapyrus Core notified last time. See `block_notified`.
+    last_notified_block: Option<String>,
+    /// Candidate block which member requested Tapyrus Core to test.
+    block_under_test: Option<BlockUnderTest>,
+    /// Block which master produces with the collected signatures.
+    submission: Option<Submission>,
+    admin_socket: Option<UnixListener>,
+    /// Signer indices which this node received any message from.
+    peers: HashSet<usize>,
+    /// Whether this node signed a candidate block.
+    contributed: bool,
+    /// Block of the last completed round, signers who signed it and the end of the grace period
+    /// for late signatures.
+    completed_round: Option<(Block, HashSet<SignerID>, Instant)>,
+    /// Block submitted by this node, which is polled until it becomes the best block. See
+    /// `check_submission`.
+    submitted_block: Option<SubmittedBlock>,
+}
+
+/// What the embedder does for `SignerNode` after each call. See `SignerNode`.
+#[derive(Debug, Default)]
+pub struct NodeOutput {
+    /// Messages to broadcast to the signer network, in order.
+    pub messages: Vec<Message>,
+    /// Get candidate block paying to the address with `getnewblock`, and pass it to
+    /// `candidate_block`.
+    pub fetch_candidate_block: Option<Address>,
+    /// Test candidate block with `testproposedblock`, and pass the result to `block_tested`.
+    pub block_to_test: Option<Block>,
+    /// Complete the block with `combineblocksigs` and submit it with `submitblock`, and pass the
+    /// completed block to `block_submitted`.
+    pub block_to_submit: Option<BlockToSubmit>,
+    /// Get `getblockchaininfo` and pass it to `chain_info`.
+    pub fetch_chain_info: bool,
+    /// Time when `tick` must be called at the latest.
+    pub next_deadline: Option<Instant>,
+}
+
+/// Candidate block and the signatures for it which the round collected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockToSubmit {
+    pub block: Block,
+    pub signatures: Vec<secp256k1::Signature>,
+}
+
+/// Candidate block which is tested by Tapyrus Core.
+struct BlockUnderTest {
+    sender_id: SignerID,
+    block: Block,
+    /// The block is announced for the readiness check, and isn't signed yet.
+    announced: bool,
+    requested_at: Instant,
+}
+
+/// Block of the round and the signatures for it.
+struct Submission {
+    block: Block,
+    signature_map: SignatureMap,
+    /// Time when master requested submitting the block. `None` while the best block is checked
+    /// before it.
+    requested_at: Option<Instant>,
+}
+
+/// Submitted block waiting for the confirmation.
+struct SubmittedBlock {
+    hash: String,
+    height: u64,
+    deadline: Instant,
+    /// Time of the next poll. `None` until the next `tick`.
+    next_poll: Option<Instant>,
+}
+
+/// Outcome of a round in the round event.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundOutcome {
+    /// The block is produced.
+    Signed,
+    /// Enough signatures weren't collected in the round time limit.
+    Timeout,
+    /// Tapyrus Core rejected the signatures or the completed block.
+    Invalid,
+}
+
+/// Status of the node, which is written to the client of the admin socket as JSON.
+#[derive(Debug, Serialize)]
+struct NodeStatus {
+    /// Round number for the leader election.
+    round: u64,
+    /// "joining", "member" or "master".
+    state: &'static str,
+    master_index: usize,
+    /// Count of signatures which master collected in the current round. 0 on member.
+    signatures: usize,
+    /// Signer indices which this node received any message from.
+    peers: Vec<usize>,
+}
+
+/// Event written to `events_path` as a JSON line at the end of each round.
+#[derive(Debug, Serialize)]
+struct RoundEvent {
+    /// Number of the round counted by this node.
+    round: u64,
+    /// Height of the block which the round produced or tried to produce.
+    height: Option<u64>,
+    master_index: usize,
+    /// Signer indices whose signature the master collected. Members don't know them.
+    participants: Vec<usize>,
+    outcome: RoundOutcome,
+    /// Time from round start to the end, on master.
+    latency_milliseconds: Option<u64>,
+}
+
+/// Time spent in each phase of the current round. Phases which the node didn't pass are `None`.
+#[derive(Debug, Default)]
+struct RoundTimings {
+    /// getnewblock on master.
+    block_fetch: Option<Duration>,
+    /// testproposedblock and the block policy check on member.
+    block_validation: Option<Duration>,
+    /// Collecting enough signatures on master.
+    signature_wait: Option<Duration>,
+    /// combineblocksigs and submitblock on master.
+    submit: Option<Duration>,
+}
+
+/// Warning about the height which this node signed last time, when the chain `tip` is behind it or
+/// far ahead of it.
+fn last_signed_height_warning(last_signed: u64, tip: u64) -> Option<String> {
+    if tip < last_signed {
+        Some(format!(
+            "Chain tip {} is behind the last signed height {}. The chain may be rolled back.",
+            tip, last_signed
+        ))
+    } else if tip - last_signed > LAST_SIGNED_HEIGHT_GAP {
+        Some(format!(
+            "Chain tip {} is {} blocks ahead of the last signed height {}. The node may have missed blocks.",
+            tip,
+            tip - last_signed,
+            last_signed
+        ))
+    } else {
+        None
+    }
+}
+
+/// Write `contents` to `path` readable only by the owner. It writes a temporary file and renames
+/// it, so that the file is never partially written.
+fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Readiness of members collected by master before broadcasting the candidate block.
+struct Readiness {
+    ready: HashSet<SignerID>,
+    /// End of the collection window. `None` until the next `tick`.
+    deadline: Option<Instant>,
 }
 
 /// Signature HashMap type alias.
@@ -43,51 +304,132 @@ pub enum NodeState {
     Member,
 }
 
-fn sender_index(sender_id: &SignerID, pubkey_list: &[PublicKey]) -> usize {
-    //Unknown sender is already ignored.
-    pubkey_list
+/// Sort public keys into the canonical order of the signer network.
+/// Signer index of each node is its position in this order, so every node must use this
+/// function to get the same index assignment.
+pub fn canonical_order(keys: &mut [PublicKey]) {
+    keys.sort();
+}
+
+/// Select the private key which is paired with one of the public keys of the signer network.
+/// Returns error if no key or several keys are paired.
+pub fn select_private_key(
+    private_keys: &[PrivateKey],
+    pubkey_list: &[PublicKey],
+) -> Result<PrivateKey, Error> {
+    let secp = secp256k1::Secp256k1::signing_only();
+    let paired: Vec<&PrivateKey> = private_keys
         .iter()
-        .position(|pk| pk == &sender_id.pubkey)
-        .unwrap()
+        .filter(|k| pubkey_list.contains(&k.public_key(&secp)))
+        .collect();
+    match paired.len() {
+        0 => Err(Error::InvalidArgs(
+            "Private key is not pair of any one of Public key list.".to_string(),
+        )),
+        1 => Ok(*paired[0]),
+        n => Err(Error::InvalidArgs(format!(
+            "{} private keys are paired with Public key list. Only one is allowed.",
+            n
+        ))),
+    }
+}
+
+/// Signer index of the sender. `None` if the sender isn't in the federation.
+fn sender_index(sender_id: &SignerID, pubkey_list: &[PublicKey]) -> Option<usize> {
+    pubkey_list.iter().position(|pk| pk == &sender_id.pubkey)
 }
 
-impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
-    pub fn new(connection_manager: C, params: NodeParameters<T>) -> Self
+impl SignerNode {
+    pub fn new(params: NodeParameters) -> Self
     where
         Self: Sized,
     {
         let timer_limit = params.round_duration + ROUND_TIMELIMIT_DELTA;
+        let metrics = Metrics::new(Duration::from_secs(timer_limit), params.pubkey_list.len());
+        let signers = params.pubkey_list.len();
         SignerNode {
-            connection_manager,
             params,
             current_state: NodeState::Joining,
-            stop_signal: None,
+            now: Instant::now(),
+            output: NodeOutput::default(),
+            chain: None,
             master_index: 0,
-            round_timer: RoundTimeOutObserver::new(timer_limit),
+            round: 0,
+            round_timelimit: Duration::from_secs(timer_limit),
+            round_deadline: None,
+            metrics: Arc::new(metrics),
+            round_started_at: None,
+            signature_wait_started_at: None,
+            round_start_at: None,
+            candidate_block_requested_at: None,
+            waiting_candidate_block: false,
+            next_poll: None,
+            round_height: 0,
+            round_attempts: 0,
+            missed_rounds: vec![0; signers],
+            excluded_signers: HashSet::new(),
+            last_progress: None,
+            last_message: None,
+            rounds_in_flight: Vec::new(),
+            readiness: None,
+            observed_blocks: HashMap::new(),
+            equivocated_masters: HashSet::new(),
+            round_events: 0,
+            round_event_written: false,
+            round_timings: RoundTimings::default(),
+            signing_height: None,
+            round_tip: None,
+            waiting_round_tip: false,
+            next_tip_check: None,
+            last_notified_block: None,
+            block_under_test: None,
+            submission: None,
+            admin_socket: None,
+            peers: HashSet::new(),
+            contributed: false,
+            completed_round: None,
+            submitted_block: None,
         }
     }
 
-    pub fn stop_handler(&mut self, receiver: Receiver<u32>) {
-        self.stop_signal = Some(receiver);
+    /// Whether this node signed a candidate block. With `sign_once`, the node exits when it
+    /// signed one.
+    pub fn contributed(&self) -> bool {
+        self.contributed
     }
 
-    pub fn start(&mut self) {
-        if !self.params.skip_waiting_ibd {
-            self.wait_for_ibd_finish(std::time::Duration::from_secs(10));
-        }
+    /// With `sign_once`, the node exits when it signed a candidate block or the round time
+    /// limit passed without one.
+    pub fn sign_once_finished(&self, now: Instant) -> bool {
+        self.contributed || matches!(self.round_deadline, Some(deadline) if now >= deadline)
+    }
 
-        let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
-        let closure = move |message: Message| match sender.send(message) {
-            Ok(_) => ControlFlow::Continue,
-            Err(error) => {
-                log::warn!("Happened error!: {:?}", error);
-                ControlFlow::Break(())
+    /// Decide the role of the node at the first round. If the node is master, this starts the
+    /// round. Call this once, after the signer network is subscribed and `chain_info` was called.
+    pub fn start(&mut self, now: Instant) -> NodeOutput {
+        self.now = now;
+        self.check_last_signed_height();
+        self.round_height = self.current_height().map_or(0, |height| height + 1);
+        let is_master = match self.params.fixed_master {
+            Some(index) => {
+                self.master_index = index;
+                self.params.self_node_index == index
+            }
+            None if self.params.election == ElectionStrategy::HashBased => {
+                // all signers agree on the height, so the first round is round 0 of the next
+                // height.
+                self.round = self.election_round();
+                self.master_index = self
+                    .params
+                    .election
+                    .master_index(self.round, &self.params.pubkey_list);
+                self.params.self_node_index == self.master_index
             }
+            None => self.params.master_flag,
         };
-
-        // redisとの通信を行うthreadを開始
-        let _handler = self.connection_manager.start(closure);
-        self.current_state = if self.params.master_flag {
+        // the node exits after signing, so it must not be master of the round.
+        let is_master = is_master && !self.params.sign_once;
+        self.current_state = if is_master {
             self.start_new_round()
         } else {
             NodeState::Member
@@ -98,116 +440,388 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
             &self.params.self_node_index,
             &self.master_index
         );
+        self.take_output()
+    }
 
-        // Roundのtimeoutを監視するthreadを開始
-        self.round_timer.start().unwrap();
-        // get error_handler that is for catch error within connection_manager.
-        let connection_manager_error_handler = self.connection_manager.error_handler();
-        loop {
-            // After process when received message. Get message from receiver,
-            // then change that state in main thread side.
-            // messageを受け取った後の処理。receiverからmessageを受け取り、
-            // stateの変更はmain thread側で行う。
-            match &self.stop_signal {
-                Some(ref r) => match r.try_recv() {
-                    Ok(_) => {
-                        log::warn!("Stop by Terminate Signal.");
-                        self.round_timer.stop();
-                        break;
-                    }
-                    Err(std::sync::mpsc::TryRecvError::Empty) => {}
-                    Err(e) => {
-                        panic!("{:?}", e);
-                    }
-                },
-                None => {}
+    /// Advance the round timer to `now`.
+    /// When the round exceeds time limit, the master role passes to the next node.
+    /// The round time limit starts at the first call after `start` or after the round restarted.
+    pub fn tick(&mut self, now: Instant) -> NodeOutput {
+        self.now = now;
+        let prev_state = self.current_state.clone();
+        match self.round_deadline {
+            None => self.round_deadline = Some(now + self.round_timelimit()),
+            Some(deadline) if now >= deadline => {
+                self.current_state = self.process_round_timeout();
+                self.round_deadline = Some(now + self.round_timelimit());
+                self.last_progress = None;
             }
-            // Receiving message.
-            match receiver.try_recv() {
-                Ok(msg) => {
-                    let next = self.process_message(msg);
-                    self.current_state = next;
-                }
-                Err(_e) => {}
-            }
-            // Process for exceed time limit of Round.
-            match self.round_timer.receiver.try_recv() {
-                Ok(_) => {
-                    // Round timeout. force round robin master node.
-                    self.current_state = self.round_robin_master();
-                    self.round_timer.restart().unwrap();
+            Some(_) => {}
+        }
+        match self.round_start_at {
+            Some(round_start_at) if now >= round_start_at => {
+                self.current_state = self.begin_round();
+            }
+            _ => {}
+        }
+        if self.waiting_candidate_block {
+            match self.next_poll {
+                None => self.next_poll = Some(now + self.params.poll_interval),
+                Some(next_poll) if now >= next_poll => {
+                    self.next_poll = Some(now + self.params.poll_interval);
+                    self.current_state = self.propose_candidate_block();
                 }
-                Err(_e) => {} // nothing to do.
-            }
-            // Should be panic, if happened error in connection_manager.
-            match connection_manager_error_handler {
-                Some(ref receiver) => match receiver.try_recv() {
-                    Ok(e) => {
-                        self.round_timer.stop();
-                        panic!(e.to_string());
-                    }
-                    Err(_e) => {}
-                },
+                Some(_) => {}
+            }
+        }
+        let window = self.params.readiness_window;
+        let readiness_expired = match self.readiness {
+            Some(ref mut readiness) => match readiness.deadline {
                 None => {
-                    log::warn!("Failed to get error_handler of connection_manager!");
+                    readiness.deadline = Some(now + window);
+                    false
+                }
+                Some(deadline) => now >= deadline,
+            },
+            None => false,
+        };
+        if readiness_expired {
+            log::warn!(
+                "Only {} members are ready in {:?}. The candidate block isn't broadcasted and the round will time out.",
+                self.readiness.as_ref().map_or(0, |r| r.ready.len()),
+                window
+            );
+            self.readiness = None;
+        }
+        if self.round_tip.is_some() {
+            match self.next_tip_check {
+                None => self.next_tip_check = Some(now + self.params.poll_interval),
+                Some(next_tip_check) if now >= next_tip_check => {
+                    self.next_tip_check = Some(now + self.params.poll_interval);
+                    // `chain_info` aborts the round if the tip changed.
+                    self.output.fetch_chain_info = true;
                 }
+                Some(_) => {}
             }
-            // wait loop
-            std::thread::sleep(Duration::from_millis(300));
         }
+        self.check_submission(now);
+        if self.current_state != prev_state {
+            self.last_progress = None;
+        }
+        self.watchdog(now);
+        self.take_output()
     }
 
-    /// Signer Node waits for connected Tapyrus Core Node complete IBD(Initial Block Download).
-    fn wait_for_ibd_finish(&self, interval: Duration) {
-        log::info!("Waiting finish Initial Block Download ...");
-        log::info!("If you start right away, you can set `--skip-waiting-ibd` option. ");
+    /// Tell the node a new block which Tapyrus Core notified, like with `-zmqpubhashblock`.
+    /// While master is waiting for Tapyrus Core to create candidate block, the block triggers the
+    /// request without waiting for the next poll. Otherwise master checks the best block, and
+    /// starts the next round if its candidate block is stale.
+    /// Notifications of the same block are counted once, because Tapyrus Core may notify it again
+    /// on reconnect.
+    pub fn block_notified(&mut self, block_hash: String, now: Instant) -> NodeOutput {
+        self.now = now;
+        if self.last_notified_block.as_ref() == Some(&block_hash) {
+            return self.take_output();
+        }
+        log::debug!("New block is notified. hash: {}", block_hash);
+        self.last_notified_block = Some(block_hash);
+        if self.waiting_candidate_block {
+            self.next_poll = Some(now + self.params.poll_interval);
+            self.current_state = self.propose_candidate_block();
+        } else if self.round_tip.is_some() {
+            self.output.fetch_chain_info = true;
+        }
+        self.take_output()
+    }
 
-        loop {
-            match self
-                .params
-                .rpc
-                .getblockchaininfo()
-                .expect("RPC connection failed")
-            {
-                GetBlockchainInfoResult {
-                    initialblockdownload: false,
-                    ..
-                } => {
-                    break;
-                }
-                GetBlockchainInfoResult {
-                    initialblockdownload: true,
-                    blocks: height,
-                    bestblockhash: hash,
-                    ..
-                } => {
-                    log::info!("Waiting for finish Initial Block Download. Current block height: {}, current best hash: {}", height, hash);
-                }
+    /// Result of `getblockchaininfo` for `NodeOutput::fetch_chain_info`. Master aborts the round
+    /// when the best block changed after it proposed the candidate block, because the candidate
+    /// block is stale. It is checked before the block is submitted too.
+    pub fn chain_info(
+        &mut self,
+        info: Result<GetBlockchainInfoResult, Error>,
+        now: Instant,
+    ) -> NodeOutput {
+        self.now = now;
+        let info = match info {
+            Ok(info) => info,
+            Err(e) => {
+                log::warn!("Failed to get best block hash: {:?}", e);
+                self.waiting_round_tip = false;
+                self.submit_block();
+                return self.take_output();
             }
-            std::thread::sleep(interval);
+        };
+        if self.waiting_round_tip {
+            self.waiting_round_tip = false;
+            self.round_tip = Some(info.bestblockhash.clone());
+        }
+        self.confirm_submission(&info);
+        let stale = self.tip_changed(&info);
+        self.chain = Some(info);
+        if stale {
+            let state = self.abort_stale_round();
+            self.set_state(state);
+        } else {
+            self.submit_block();
         }
+        self.take_output()
     }
 
-    pub fn start_new_round(&self) -> NodeState {
-        std::thread::sleep(Duration::from_secs(self.params.round_duration));
+    /// Output of the current call. `next_deadline` is the earliest time when `tick` has
+    /// something to do.
+    fn take_output(&mut self) -> NodeOutput {
+        let mut output = std::mem::take(&mut self.output);
+        // `None` of these deadlines means that it is set on the next tick.
+        let mut deadlines = vec![self.round_deadline];
+        if let Some(round_start_at) = self.round_start_at {
+            deadlines.push(Some(round_start_at));
+        }
+        if self.waiting_candidate_block {
+            deadlines.push(self.next_poll);
+        }
+        if let Some(ref readiness) = self.readiness {
+            deadlines.push(readiness.deadline);
+        }
+        if self.round_tip.is_some() {
+            deadlines.push(self.next_tip_check);
+        }
+        if let Some(ref submitted) = self.submitted_block {
+            deadlines.push(submitted.next_poll);
+        }
+        if self.params.watchdog_rounds > 0 {
+            deadlines.push(self.last_progress.map(|last_progress| {
+                last_progress
+                    + self.round_timelimit * self.round_backoff() * self.params.watchdog_rounds
+            }));
+        }
+        output.next_deadline = deadlines
+            .into_iter()
+            .map(|deadline| deadline.unwrap_or(self.now))
+            .min();
+        output
+    }
 
-        let block = self.params.rpc.getnewblock(&self.params.address).unwrap();
-        self.connection_manager.broadcast_message(Message {
-            message_type: MessageType::Candidateblock(block.clone()),
-            sender_id: self.params.signer_id,
-        });
+    /// Change the state out of `process_message` and `tick`, which reset `last_progress` by
+    /// themselves.
+    fn set_state(&mut self, state: NodeState) {
+        if self.current_state != state {
+            self.last_progress = None;
+        }
+        self.current_state = state;
+    }
+
+    /// Reset the state machine, when it has neither changed its state nor timed out the round
+    /// for `watchdog_rounds` round time limits. It is a safety net for the state which never
+    /// completes nor times out.
+    fn watchdog(&mut self, now: Instant) {
+        let last_progress = match self.last_progress {
+            None => {
+                self.last_progress = Some(now);
+                return;
+            }
+            Some(last_progress) => last_progress,
+        };
+        if self.params.watchdog_rounds == 0
+            || now
+                < last_progress
+                    + self.round_timelimit * self.round_backoff() * self.params.watchdog_rounds
+        {
+            return;
+        }
+        log::warn!(
+            "No progress in {} rounds. Reset the state machine. last state: {:?}, last message: {:?}",
+            self.params.watchdog_rounds,
+            self.current_state,
+            self.last_message
+        );
+        self.metrics.watchdog_resets.fetch_add(1, Ordering::Relaxed);
+        self.round_start_at = None;
+        self.candidate_block_requested_at = None;
+        self.waiting_candidate_block = false;
+        self.next_poll = None;
+        self.round_started_at = None;
+        self.signature_wait_started_at = None;
+        self.rounds_in_flight.clear();
+        self.readiness = None;
+        self.observed_blocks.clear();
+        self.equivocated_masters.clear();
+        self.round_tip = None;
+        self.waiting_round_tip = false;
+        self.next_tip_check = None;
+        self.block_under_test = None;
+        self.submission = None;
+        self.current_state = NodeState::Member;
+        self.restart_round_timer();
+        self.last_progress = Some(now);
+    }
+
+    /// Time limit of a round. Random jitter up to `timeout_jitter` is added, so that nodes
+    /// don't time out in lockstep.
+    fn round_timelimit(&self) -> Duration {
+        let timelimit = self.round_timelimit * self.round_backoff();
+        let max_jitter = self.params.timeout_jitter.as_millis() as u64;
+        if max_jitter == 0 {
+            return timelimit;
+        }
+        let jitter = rand::thread_rng().gen_range(0, max_jitter + 1);
+        timelimit + Duration::from_millis(jitter)
+    }
+
+    /// Restart time limit of the round. Time limit is set again on next `tick`.
+    fn restart_round_timer(&mut self) {
+        self.round_deadline = None;
+    }
+
+    pub fn current_state(&self) -> &NodeState {
+        &self.current_state
+    }
 
-        let sig = sign(&self.params.private_key, &block.hash().unwrap());
+    pub fn master_index(&self) -> usize {
+        self.master_index
+    }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Start the round of this node as master after `round_duration`, on `tick`.
+    pub fn start_new_round(&mut self) -> NodeState {
+        if self.params.round_duration == 0 {
+            return self.begin_round();
+        }
+        self.round_start_at = Some(self.now + Duration::from_secs(self.params.round_duration));
+        NodeState::Member
+    }
+
+    fn begin_round(&mut self) -> NodeState {
+        self.round_start_at = None;
+        self.round_started_at = Some(self.now);
+        self.propose_candidate_block()
+    }
+
+    /// Request candidate block from Tapyrus Core. `candidate_block` broadcasts it.
+    fn propose_candidate_block(&mut self) -> NodeState {
+        self.output.fetch_candidate_block = Some(self.params.address.clone());
+        self.candidate_block_requested_at = Some(self.now);
+        NodeState::Member
+    }
+
+    /// Result of `getnewblock` for `NodeOutput::fetch_candidate_block`. Master broadcasts the
+    /// block. If Tapyrus Core failed to create the block, master requests it again after
+    /// `poll_interval` on `tick`.
+    pub fn candidate_block(&mut self, block: Result<Block, Error>, now: Instant) -> NodeOutput {
+        self.now = now;
+        let requested_at = match self.candidate_block_requested_at.take() {
+            Some(requested_at) => requested_at,
+            None => {
+                log::warn!("Candidate block isn't requested in the current round. Ignore it.");
+                return self.take_output();
+            }
+        };
+        let block = match block {
+            Ok(block) => {
+                let elapsed = now.saturating_duration_since(requested_at);
+                self.metrics.block_fetch.observe(elapsed);
+                self.round_timings.block_fetch = Some(elapsed);
+                // the round is aborted when the best block changes from this one.
+                self.round_tip = None;
+                self.waiting_round_tip = true;
+                self.output.fetch_chain_info = true;
+                block
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to get candidate block. Retry after {:?}. error: {:?}",
+                    self.params.poll_interval,
+                    e
+                );
+                self.waiting_candidate_block = true;
+                self.set_state(NodeState::Member);
+                return self.take_output();
+            }
+        };
+        self.waiting_candidate_block = false;
+        self.next_poll = None;
+        if self.params.readiness_window > Duration::from_millis(0) && self.params.threshold > 1 {
+            // broadcast the candidate block after enough members are ready.
+            self.broadcast(MessageType::Roundannounce(block.clone()));
+            self.readiness = Some(Readiness {
+                ready: HashSet::new(),
+                deadline: None,
+            });
+        } else {
+            self.broadcast_candidate_block(&block);
+        }
+
+        let sig = sign_message(&self.params.private_key, &self.block2message(&block));
         let mut signature_map: SignatureMap = HashMap::new();
         signature_map.insert(self.params.signer_id, sig);
-        NodeState::Master {
+        self.set_state(NodeState::Master {
             candidate_block: block,
             signature_map,
+        });
+        self.take_output()
+    }
+
+    fn broadcast_candidate_block(&mut self, block: &Block) {
+        self.broadcast(MessageType::Candidateblock(block.clone()));
+        self.signature_wait_started_at = Some(self.now);
+    }
+
+    /// Message of the current round from this node.
+    fn message(&self, message_type: MessageType) -> Message {
+        Message {
+            message_type,
+            sender_id: self.params.signer_id,
+            height: self.round_height,
+            round: self.round_attempts,
         }
     }
 
-    pub fn process_message(&mut self, message: Message) -> NodeState {
-        match message.message_type {
+    /// Broadcast the message of the current round to the signer network, by the embedder.
+    fn broadcast(&mut self, message_type: MessageType) {
+        let message = self.message(message_type);
+        self.output.messages.push(message);
+    }
+
+    /// Process a message received from the signer network. The new state is `current_state`.
+    pub fn process_message(&mut self, message: Message, now: Instant) -> NodeOutput {
+        self.now = now;
+        if sender_index(&message.sender_id, &self.params.pubkey_list).is_none() {
+            match (&message.message_type, &self.current_state) {
+                (MessageType::Signature(_), NodeState::Master { .. }) => {
+                    self.ignore_unknown_sender(&message.sender_id)
+                }
+                _ => log::warn!(
+                    "Ignore message from unknown signer: sender={:?}",
+                    message.sender_id
+                ),
+            }
+            return self.take_output();
+        }
+        self.record_response(&message.sender_id);
+        let prev_state = self.current_state.clone();
+        let message_type = match message.message_type {
+            MessageType::Candidateblock(_) => "Candidateblock",
+            MessageType::Signature(_) => "Signature",
+            MessageType::Completedblock(_) => "Completedblock",
+            MessageType::Roundfailure => "Roundfailure",
+            MessageType::Roundannounce(_) => "Roundannounce",
+            MessageType::Ready { .. } => "Ready",
+            MessageType::Blockhash { .. } => "Blockhash",
+        };
+        self.last_message = Some((message_type, message.sender_id));
+        if let Err(e) = self.check_round(&message) {
+            log::warn!(
+                "Drop the message of other round: type: {}, sender: {:?}, error: {:?}",
+                message_type,
+                message.sender_id,
+                e
+            );
+            return self.take_output();
+        }
+        self.current_state = match message.message_type {
             MessageType::Candidateblock(block) => {
                 self.process_candidateblock(&message.sender_id, &block)
             }
@@ -216,40 +830,285 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                 self.process_completedblock(&message.sender_id, &block)
             }
             MessageType::Roundfailure => self.process_roundfailure(&message.sender_id),
+            MessageType::Roundannounce(block) => {
+                self.process_roundannounce(&message.sender_id, &block)
+            }
+            MessageType::Ready {
+                signer_index,
+                block_hash,
+            } => self.process_ready(&message.sender_id, signer_index, &block_hash),
+            MessageType::Blockhash {
+                master_index,
+                block_hash,
+            } => self.process_blockhash(master_index, block_hash),
+        };
+        if self.current_state != prev_state || message_type == "Completedblock" {
+            self.last_progress = None;
+        }
+        self.take_output()
+    }
+
+    /// Check that the message is for the current round, so that messages of past rounds which
+    /// are replayed on the broker never change the state. Candidate block of later round moves
+    /// this node to the round, because this node may have missed the end of rounds. Late
+    /// signatures for the last completed round are checked by `record_late_signature`.
+    fn check_round(&mut self, message: &Message) -> Result<(), Error> {
+        let current = (self.round_height, self.round_attempts);
+        let round = (message.height, message.round);
+        if round == current {
+            return Ok(());
+        }
+        match message.message_type {
+            MessageType::Candidateblock(_) | MessageType::Roundannounce(_) if round > current => {
+                log::info!(
+                    "Follow the round of the candidate block. height: {}, round: {}",
+                    message.height,
+                    message.round
+                );
+                self.round_height = message.height;
+                self.round_attempts = message.round;
+                Ok(())
+            }
+            MessageType::Signature(_)
+                if self.completed_round.is_some() && message.height + 1 == self.round_height =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::UnexpectedRound {
+                height: message.height,
+                round: message.round,
+            }),
         }
     }
 
     fn process_candidateblock(&mut self, sender_id: &SignerID, block: &Block) -> NodeState {
-        match self.current_state {
-            NodeState::Member => {
-                match self.params.rpc.testproposedblock(&block) {
-                    Ok(_) => {
-                        self.master_index = sender_index(sender_id, &self.params.pubkey_list);
-                        let block_hash = block.hash().unwrap();
-                        let sig = sign(&self.params.private_key, &block_hash);
-                        self.connection_manager.broadcast_message(Message {
-                            message_type: MessageType::Signature(crate::net::Signature(sig)),
-                            sender_id: self.params.signer_id,
-                        });
-                        // TODO: Errorを処理する必要あるかな？
-                        self.round_timer.restart().unwrap();
-                    }
-                    Err(_e) => {
-                        log::warn!(
-                            "Received Invalid candidate block!!: sender: {:?}",
-                            sender_id
-                        );
-                    }
-                }
+        if let Some(index) = self.params.fixed_master {
+            if sender_index(sender_id, &self.params.pubkey_list) != Some(index) {
+                log::warn!(
+                    "Received candidate block from the signer which is not the fixed master: sender: {:?}",
+                    sender_id
+                );
+                return self.current_state.clone();
             }
+        }
+        let limit_reached = match self.params.max_concurrent_rounds {
+            Some(n) => self.rounds_in_flight.len() >= n as usize,
+            None => false,
+        };
+        if !self.rounds_in_flight.contains(sender_id) && limit_reached {
+            log::warn!(
+                "Dropped candidate block, because {} rounds are in flight: sender: {:?}, masters: {:?}",
+                self.rounds_in_flight.len(),
+                sender_id,
+                self.rounds_in_flight
+            );
+            return self.current_state.clone();
+        }
+        match self.current_state {
+            NodeState::Member => self.test_block(sender_id, block, false),
             _ => {}
         };
 
         self.current_state.clone()
     }
 
+    /// Request Tapyrus Core to test the candidate block. `block_tested` signs it, or tells master
+    /// that this node is ready to sign the announced block.
+    fn test_block(&mut self, sender_id: &SignerID, block: &Block, announced: bool) {
+        self.block_under_test = Some(BlockUnderTest {
+            sender_id: *sender_id,
+            block: block.clone(),
+            announced,
+            requested_at: self.now,
+        });
+        self.output.block_to_test = Some(block.clone());
+    }
+
+    /// Result of `testproposedblock` for `NodeOutput::block_to_test`. The block policy is
+    /// checked after Tapyrus Core accepted the block.
+    pub fn block_tested(&mut self, result: Result<(), Error>, now: Instant) -> NodeOutput {
+        self.now = now;
+        let tested = match self.block_under_test.take() {
+            Some(tested) => tested,
+            None => {
+                log::warn!("Candidate block isn't tested in the current round. Ignore it.");
+                return self.take_output();
+            }
+        };
+        let result = result.and_then(|_| self.params.policy.check(&tested.block));
+        let elapsed = now.saturating_duration_since(tested.requested_at);
+        self.metrics.block_validation.observe(elapsed);
+        self.round_timings.block_validation = Some(elapsed);
+        if self.current_state != NodeState::Member {
+            return self.take_output();
+        }
+        let state = if tested.announced {
+            self.ready_for_block(&tested.sender_id, &tested.block, result)
+        } else {
+            self.sign_candidate_block(&tested.sender_id, &tested.block, result)
+        };
+        self.set_state(state);
+        self.take_output()
+    }
+
+    fn sign_candidate_block(
+        &mut self,
+        sender_id: &SignerID,
+        block: &Block,
+        result: Result<(), Error>,
+    ) -> NodeState {
+        if let Err(e) = result {
+            log::warn!(
+                "Received Invalid candidate block!!: sender: {:?}, error: {:?}",
+                sender_id,
+                e
+            );
+            return self.current_state.clone();
+        }
+        let index = match sender_index(sender_id, &self.params.pubkey_list) {
+            Some(index) => index,
+            None => return self.current_state.clone(),
+        };
+        if self.params.detect_equivocation {
+            if let Err(e) = self.gossip_block_hash(index, block) {
+                log::error!("Abort the round. error: {:?}", e);
+                return self.current_state.clone();
+            }
+        }
+        self.master_index = index;
+        if !self.rounds_in_flight.contains(sender_id) {
+            self.rounds_in_flight.push(*sender_id);
+        }
+        if self.params.last_signed_height_path.is_some() {
+            // the candidate block is on the tip of the chain.
+            self.signing_height = Some(self.round_height);
+        }
+        let sig = sign_message(&self.params.private_key, &self.block2message(block));
+        self.broadcast(MessageType::Signature(crate::net::Signature(sig)));
+        self.contributed = true;
+        self.restart_round_timer();
+        self.current_state.clone()
+    }
+
+    /// Validate the announced candidate block and tell master that this node is ready to sign it.
+    fn process_roundannounce(&mut self, sender_id: &SignerID, block: &Block) -> NodeState {
+        if self.current_state != NodeState::Member {
+            return self.current_state.clone();
+        }
+        self.test_block(sender_id, block, true);
+        self.current_state.clone()
+    }
+
+    fn ready_for_block(
+        &mut self,
+        sender_id: &SignerID,
+        block: &Block,
+        result: Result<(), Error>,
+    ) -> NodeState {
+        match result.and_then(|_| block.hash()) {
+            Ok(block_hash) => {
+                self.broadcast(MessageType::Ready {
+                    signer_index: self.params.self_node_index,
+                    block_hash,
+                });
+            }
+            Err(e) => {
+                log::warn!(
+                    "Received Invalid announced block!!: sender: {:?}, error: {:?}",
+                    sender_id,
+                    e
+                );
+            }
+        }
+        self.current_state.clone()
+    }
+
+    /// Collect readiness of members. Master broadcasts the candidate block when threshold
+    /// signers including itself are ready.
+    fn process_ready(
+        &mut self,
+        sender_id: &SignerID,
+        signer_index: usize,
+        block_hash: &BlockHash,
+    ) -> NodeState {
+        let block = match self.current_state {
+            NodeState::Master {
+                ref candidate_block,
+                ..
+            } => candidate_block.clone(),
+            _ => return self.current_state.clone(),
+        };
+        let readiness = match self.readiness {
+            Some(ref mut readiness) => readiness,
+            None => return self.current_state.clone(),
+        };
+        if Some(signer_index) != sender_index(sender_id, &self.params.pubkey_list)
+            || block.hash().ok().as_ref() != Some(block_hash)
+        {
+            log::warn!(
+                "Received readiness for other block or index: sender: {:?}, signer_index: {}",
+                sender_id,
+                signer_index
+            );
+            return self.current_state.clone();
+        }
+        readiness.ready.insert(*sender_id);
+        // master itself is ready.
+        if readiness.ready.len() + 1 >= self.params.threshold as usize {
+            log::info!("{} members are ready.", readiness.ready.len());
+            self.readiness = None;
+            self.broadcast_candidate_block(&block);
+        }
+        self.current_state.clone()
+    }
+
+    /// Tell the other members the hash of the candidate block before signing it. Returns
+    /// `Error::MasterEquivocation` if the master proposed another block in the round.
+    fn gossip_block_hash(&mut self, master_index: usize, block: &Block) -> Result<(), Error> {
+        let block_hash = block.hash()?;
+        self.observe_block_hash(master_index, block_hash)?;
+        self.broadcast(MessageType::Blockhash {
+            master_index,
+            block_hash,
+        });
+        Ok(())
+    }
+
+    fn process_blockhash(&mut self, master_index: usize, block_hash: BlockHash) -> NodeState {
+        if !self.params.detect_equivocation
+            || self.current_state != NodeState::Member
+            || master_index >= self.params.pubkey_list.len()
+        {
+            return self.current_state.clone();
+        }
+        if let Err(e) = self.observe_block_hash(master_index, block_hash) {
+            log::error!("Abort the round. error: {:?}", e);
+        }
+        self.current_state.clone()
+    }
+
+    /// Record the hash of the block which the master proposed in the current round. Once the
+    /// master is found to propose two blocks, this node never signs its blocks in the round.
+    fn observe_block_hash(
+        &mut self,
+        master_index: usize,
+        block_hash: BlockHash,
+    ) -> Result<(), Error> {
+        let observed = *self
+            .observed_blocks
+            .entry(master_index)
+            .or_insert(block_hash);
+        if observed != block_hash || self.equivocated_masters.contains(&master_index) {
+            self.equivocated_masters.insert(master_index);
+            return Err(Error::MasterEquivocation(
+                self.params.pubkey_list[master_index],
+            ));
+        }
+        Ok(())
+    }
+
     fn block2message(&self, block: &Block) -> secp256k1::Message {
-        secp256k1::Message::from_slice(block.hash().unwrap().borrow_inner()).unwrap()
+        self.params.sighash_variant.sighash(block).unwrap()
     }
     fn verify_signature(
         &self,
@@ -269,7 +1128,14 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
         }
     }
     fn process_signature(&mut self, sender_id: &SignerID, signature: &Signature) -> NodeState {
+        if self.record_late_signature(sender_id, signature) {
+            return self.current_state.clone();
+        }
         match &self.current_state {
+            NodeState::Master { .. } if !self.params.pubkey_list.contains(&sender_id.pubkey) => {
+                self.ignore_unknown_sender(sender_id);
+                self.current_state.clone()
+            }
             NodeState::Master {
                 signature_map: ref sig_map,
                 candidate_block: ref block,
@@ -278,28 +1144,20 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
                 match self.verify_signature(&signature_map, &block, &signature.0, &sender_id) {
                     Ok(_) => {
                         signature_map.insert(*sender_id, signature.0.clone());
-                        if signature_map.len() as u8 >= self.params.threshold {
-                            // call combineblocksigs
-                            let sigs = signature_map.values().map(|v| *v).collect();
-                            let completed_block =
-                                self.params.rpc.combineblocksigs(&block, &sigs).unwrap();
-
-                            // call submitblock
-                            self.params.rpc.submitblock(&completed_block).unwrap();
-
-                            // send completeblock message
-                            let message = Message {
-                                message_type: MessageType::Completedblock(completed_block),
-                                sender_id: self.params.signer_id.clone(),
-                            };
-                            self.connection_manager.broadcast_message(message);
-
-                            // start round robin.
-                            self.round_robin_master()
+                        let block = block.clone();
+                        if let Some(index) = sender_index(sender_id, &self.params.pubkey_list) {
+                            self.metrics.record_signature(index);
+                            self.include_signer(index);
+                        }
+                        // the block is produced once, while it is submitted.
+                        if signature_map.len() as u8 >= self.collection_quorum()
+                            && self.submission.is_none()
+                        {
+                            self.produce_block(&block, &signature_map)
                         } else {
                             NodeState::Master {
                                 signature_map,
-                                candidate_block: block.clone(),
+                                candidate_block: block,
                             }
                         }
                     }
@@ -313,62 +1171,697 @@ impl<T: TapyrusApi, C: ConnectionManager> SignerNode<T, C> {
         }
     }
 
-    /// Master role pass to the node of next index.
-    fn round_robin_master(&mut self) -> NodeState {
-        let next_index = (self.master_index + 1) % self.params.pubkey_list.len();
-        self.master_index = next_index;
-        let next_state = if self.params.self_node_index == next_index {
-            // self node is master.
-            self.start_new_round()
-        } else {
-            NodeState::Member
-        };
-        log::info!(
-            "Round Robin: Next State {:?}, node_index: {}, master_inde: {}",
-            next_state,
-            self.params.self_node_index,
-            self.master_index
+    fn ignore_unknown_sender(&self, sender_id: &SignerID) {
+        let count = self
+            .metrics
+            .unknown_sender_shares
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        log::warn!(
+            "Ignore signature from unknown signer: sender={:?}, count={}",
+            sender_id,
+            count
         );
-        next_state
+        if count > UNKNOWN_SENDER_WARNING_COUNT {
+            log::warn!(
+                "Received {} signatures from unknown signers. The channel may be shared with another federation.",
+                count
+            );
+        }
     }
-    fn process_completedblock(&mut self, sender_id: &SignerID, _block: &Block) -> NodeState {
-        let index = sender_index(sender_id, &self.params.pubkey_list);
-        if index == self.master_index {
-            // authorization master.
-            // start round robin of master node.
-            return self.round_robin_master();
+
+    /// Combine the signatures to the candidate block after checking the best block, and submit
+    /// it. `block_submitted` starts the next round.
+    fn produce_block(&mut self, block: &Block, signature_map: &SignatureMap) -> NodeState {
+        if let Some(started_at) = self.signature_wait_started_at {
+            let elapsed = self.now.saturating_duration_since(started_at);
+            self.metrics.signature_wait.observe(elapsed);
+            self.round_timings.signature_wait = Some(elapsed);
+        }
+        let participants = self.signer_indices(signature_map);
+        self.record_missing_signers(&participants);
+
+        // `chain_info` aborts the round if the tip changed, or calls `submit_block`.
+        self.submission = Some(Submission {
+            block: block.clone(),
+            signature_map: signature_map.clone(),
+            requested_at: None,
+        });
+        self.output.fetch_chain_info = true;
+        NodeState::Master {
+            candidate_block: block.clone(),
+            signature_map: signature_map.clone(),
         }
-        self.current_state.clone()
     }
 
-    fn process_roundfailure(&self, _sender_id: &SignerID) -> NodeState {
-        self.current_state.clone()
+    /// Request combineblocksigs and submitblock of the block which is checked on the best block.
+    fn submit_block(&mut self) {
+        let submission = match self.submission {
+            Some(ref mut submission) if submission.requested_at.is_none() => submission,
+            _ => return,
+        };
+        submission.requested_at = Some(self.now);
+        self.output.block_to_submit = Some(BlockToSubmit {
+            block: submission.block.clone(),
+            signatures: submission.signature_map.values().cloned().collect(),
+        });
     }
-}
 
-pub struct NodeParameters<T: TapyrusApi> {
-    pub pubkey_list: Vec<PublicKey>,
-    pub threshold: u8,
-    pub private_key: PrivateKey,
-    pub rpc: std::sync::Arc<T>,
-    pub address: Address,
-    pub signer_id: SignerID,
-    pub master_flag: bool,
-    pub self_node_index: usize,
-    pub round_duration: u64,
-    pub skip_waiting_ibd: bool,
-}
+    /// Result of combineblocksigs and submitblock for `NodeOutput::block_to_submit`. `Ok` is the
+    /// completed block. Master broadcasts it and starts the next round.
+    pub fn block_submitted(&mut self, result: Result<Block, Error>, now: Instant) -> NodeOutput {
+        self.now = now;
+        let submission = match self.submission.take() {
+            Some(Submission {
+                block,
+                signature_map,
+                requested_at: Some(requested_at),
+            }) => (block, signature_map, requested_at),
+            _ => {
+                log::warn!("Block isn't submitted in the current round. Ignore it.");
+                return self.take_output();
+            }
+        };
+        let (block, signature_map, requested_at) = submission;
+        self.round_timings.submit = Some(now.saturating_duration_since(requested_at));
+        let participants = self.signer_indices(&signature_map);
+        let completed_block = match result {
+            Ok(completed_block) => completed_block,
+            Err(e) => {
+                log::error!("Failed to produce the block: {:?}", e);
+                self.write_round_event(RoundOutcome::Invalid, participants);
+                self.set_state(NodeState::Member);
+                return self.take_output();
+            }
+        };
+        if self.confirms_submission() {
+            if let Some(height) = self.current_height() {
+                self.wait_confirmation(&completed_block, height + 1);
+            }
+        }
+        if let Some(started_at) = self.round_started_at {
+            self.metrics
+                .round_latency
+                .observe(now.saturating_duration_since(started_at));
+        }
+        self.write_round_event(RoundOutcome::Signed, participants);
+        if self.params.last_signed_height_path.is_some() {
+            self.signing_height = Some(self.round_height);
+            self.write_last_signed_height();
+        }
 
-impl<T: TapyrusApi> NodeParameters<T> {
-    pub fn new(
+        // send completeblock message
+        self.broadcast(MessageType::Completedblock(completed_block));
+        self.metrics
+            .rounds_completed
+            .fetch_add(1, Ordering::Relaxed);
+        self.next_height();
+        if self.params.late_signature_grace > Duration::from_millis(0) {
+            self.completed_round = Some((
+                block,
+                signature_map.keys().cloned().collect(),
+                now + self.params.late_signature_grace,
+            ));
+        }
+
+        // start round robin.
+        let state = self.round_robin_master();
+        self.set_state(state);
+        self.last_progress = None;
+        self.take_output()
+    }
+
+    /// Count the signature for the block of the last completed round in participation, if it
+    /// arrived in the grace period. The completed block isn't changed. Returns whether the
+    /// signature is for the completed round.
+    fn record_late_signature(&mut self, sender_id: &SignerID, signature: &Signature) -> bool {
+        if !self.params.pubkey_list.contains(&sender_id.pubkey) {
+            return false;
+        }
+        let message = match self.completed_round {
+            Some((_, _, until)) if self.now >= until => {
+                self.completed_round = None;
+                return false;
+            }
+            Some((ref block, _, _)) => self.block2message(block),
+            None => return false,
+        };
+        let verifier = secp256k1::Secp256k1::verification_only();
+        if verifier
+            .verify(&message, &signature.0, &sender_id.pubkey.key)
+            .is_err()
+        {
+            return false;
+        }
+        let first = match self.completed_round {
+            Some((_, ref mut signers, _)) => signers.insert(*sender_id),
+            None => false,
+        };
+        if first {
+            log::info!(
+                "Received late signature for the completed round: sender={:?}",
+                sender_id
+            );
+            if let Some(index) = sender_index(sender_id, &self.params.pubkey_list) {
+                self.metrics.record_signature(index);
+                self.include_signer(index);
+            }
+        }
+        true
+    }
+
+    fn confirms_submission(&self) -> bool {
+        self.params.submit_confirmation_timeout > Duration::from_millis(0)
+    }
+
+    /// Start polling the submitted block in `tick`, to find the block which was rejected after
+    /// submitblock RPC succeeded.
+    fn wait_confirmation(&mut self, block: &Block, height: u64) {
+        let hash = match block.hash() {
+            Ok(hash) => hash.to_rpc_hex(),
+            Err(e) => {
+                log::error!("Failed to get hash of the submitted block: {:?}", e);
+                return;
+            }
+        };
+        self.submitted_block = Some(SubmittedBlock {
+            hash,
+            height,
+            deadline: self.now + self.params.submit_confirmation_timeout,
+            next_poll: None,
+        });
+    }
+
+    /// Poll Tapyrus Core at `poll_interval` until the submitted block becomes the best block at
+    /// its height, or `submit_confirmation_timeout` passes. `confirm_submission` checks it.
+    fn check_submission(&mut self, now: Instant) {
+        let poll_interval = self.params.poll_interval;
+        match self.submitted_block {
+            Some(ref mut submitted) => match submitted.next_poll {
+                Some(next_poll) if now < next_poll => {}
+                _ => {
+                    submitted.next_poll = Some(now + poll_interval);
+                    self.output.fetch_chain_info = true;
+                }
+            },
+            None => {}
+        }
+    }
+
+    fn confirm_submission(&mut self, info: &GetBlockchainInfoResult) {
+        let (hash, height, deadline) = match self.submitted_block {
+            Some(ref submitted) => (submitted.hash.clone(), submitted.height, submitted.deadline),
+            None => return,
+        };
+        if info.bestblockhash == hash && info.blocks == height {
+            log::info!("Block {} is accepted at height {}.", hash, height);
+            self.submitted_block = None;
+        } else if self.now >= deadline {
+            log::error!(
+                "Block {} didn't become the best block at height {} in {:?}. Tapyrus Core may have rejected it.",
+                hash,
+                height,
+                self.params.submit_confirmation_timeout
+            );
+            self.submitted_block = None;
+        }
+    }
+
+    /// Master role pass to the node elected for the next round.
+    fn round_robin_master(&mut self) -> NodeState {
+        let next_index = match self.params.fixed_master {
+            Some(index) => index,
+            None if self.params.election == ElectionStrategy::HashBased => {
+                self.round = self.election_round();
+                self.params
+                    .election
+                    .master_index(self.round, &self.params.pubkey_list)
+            }
+            None => {
+                self.sync_round(self.master_index);
+                self.round += 1;
+                self.params
+                    .election
+                    .master_index(self.round, &self.params.pubkey_list)
+            }
+        };
+        log::debug!("Round timings: {:?}", self.round_timings);
+        self.round_timings = RoundTimings::default();
+        self.signing_height = None;
+        self.round_tip = None;
+        self.waiting_round_tip = false;
+        self.next_tip_check = None;
+        self.block_under_test = None;
+        self.submission = None;
+        self.round_start_at = None;
+        self.candidate_block_requested_at = None;
+        self.master_index = next_index;
+        self.round_event_written = false;
+        self.rounds_in_flight.clear();
+        self.readiness = None;
+        self.observed_blocks.clear();
+        self.equivocated_masters.clear();
+        self.waiting_candidate_block = false;
+        self.next_poll = None;
+        let next_state = if self.params.self_node_index == next_index {
+            // self node is master.
+            self.start_new_round()
+        } else {
+            NodeState::Member
+        };
+        log::info!(
+            "Round Robin: Next State {:?}, node_index: {}, master_inde: {}",
+            next_state,
+            self.params.self_node_index,
+            self.master_index
+        );
+        self.dump_metrics();
+        next_state
+    }
+
+    /// Round number for the hash based election. It is keyed on the height and the round for it,
+    /// which signers send with every message, so a node which joined after some rounds timed out
+    /// elects the same masters once it follows the round of a candidate block.
+    fn election_round(&self) -> u64 {
+        (self.round_height << 32) | u64::from(self.round_attempts)
+    }
+
+    /// Advance the round to the first round which `master_index` is elected for on round robin,
+    /// if it isn't elected for the current round. It follows the master who actually started the
+    /// round, like when this node joined in the middle.
+    fn sync_round(&mut self, master_index: usize) {
+        let signers = &self.params.pubkey_list;
+        let election = self.params.election;
+        let limit = self.round + signers.len() as u64 * ROUND_SYNC_LIMIT_PER_SIGNER;
+        if let Some(round) =
+            (self.round..limit).find(|&round| election.master_index(round, signers) == master_index)
+        {
+            self.round = round;
+        }
+    }
+
+    fn signer_indices(&self, signature_map: &SignatureMap) -> Vec<usize> {
+        let mut indices: Vec<usize> = signature_map
+            .keys()
+            .filter_map(|signer_id| sender_index(signer_id, &self.params.pubkey_list))
+            .collect();
+        indices.sort();
+        indices
+    }
+
+    /// Write the event of the current round as a JSON line to `events_path`, if it is set.
+    /// Only the first event of each round is written.
+    fn write_round_event(&mut self, outcome: RoundOutcome, participants: Vec<usize>) {
+        let path = match self.params.events_path {
+            Some(ref path) => path.clone(),
+            None => return,
+        };
+        if self.round_event_written {
+            return;
+        }
+        self.round_event_written = true;
+        self.round_events += 1;
+        let event = RoundEvent {
+            round: self.round_events,
+            height: Some(self.round_height),
+            master_index: self.master_index,
+            participants,
+            outcome,
+            latency_milliseconds: self
+                .round_started_at
+                .filter(|_| self.master_index == self.params.self_node_index)
+                .map(|started_at| {
+                    self.now.saturating_duration_since(started_at).as_millis() as u64
+                }),
+        };
+        let line = format!("{}\n", serde_json::to_string(&event).unwrap());
+        let result = if path == Path::new("-") {
+            std::io::stderr().write_all(line.as_bytes())
+        } else {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to write round event to {:?}: {:?}", path, e);
+        }
+    }
+
+    /// Write the metrics snapshot as JSON to `metrics_dump_path`, if it is set.
+    fn dump_metrics(&self) {
+        let path = match self.params.metrics_dump_path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let mut snapshot = self.metrics.to_json();
+        snapshot["master_index"] = serde_json::json!(self.master_index);
+        snapshot["height"] = serde_json::json!(self.current_height());
+        // write to temporary file and rename it, so that readers never see a partial file.
+        let tmp = path.with_extension("tmp");
+        if let Err(e) =
+            std::fs::write(&tmp, snapshot.to_string()).and_then(|_| std::fs::rename(&tmp, path))
+        {
+            log::warn!("Failed to dump metrics to {:?}: {:?}", path, e);
+        }
+    }
+
+    /// Whether the best block of the chain changed after master proposed the candidate block.
+    fn tip_changed(&self, info: &GetBlockchainInfoResult) -> bool {
+        match self.round_tip {
+            Some(ref round_tip) if info.bestblockhash != *round_tip => {
+                log::warn!(
+                    "Best block changed from {} to {} during the round. The candidate block is stale.",
+                    round_tip,
+                    info.bestblockhash
+                );
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Abort the round of the stale candidate block without submitting it, and go next round.
+    fn abort_stale_round(&mut self) -> NodeState {
+        log::warn!("Abort the round, because the chain was reorganized or advanced.");
+        self.metrics.rounds_failed.fetch_add(1, Ordering::Relaxed);
+        let participants = match self.current_state {
+            NodeState::Master {
+                ref signature_map, ..
+            } => self.signer_indices(signature_map),
+            _ => vec![],
+        };
+        self.write_round_event(RoundOutcome::Invalid, participants);
+        self.round_attempts += 1;
+        self.round_robin_master()
+    }
+
+    /// Write the height of the block which this node signed in the completed round to
+    /// `last_signed_height_path`, if it is set.
+    fn write_last_signed_height(&mut self) {
+        let path = match self.params.last_signed_height_path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let height = match self.signing_height.take() {
+            Some(height) => height,
+            None => return,
+        };
+        if let Err(e) = write_private_file(path, &height.to_string()) {
+            log::warn!("Failed to write last signed height to {:?}: {:?}", path, e);
+        }
+    }
+
+    /// Log the height which this node signed last time, and warn if the chain is far from it.
+    fn check_last_signed_height(&self) {
+        let path = match self.params.last_signed_height_path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let last_signed = match std::fs::read_to_string(path) {
+            Ok(s) => match s.trim().parse::<u64>() {
+                Ok(height) => height,
+                Err(e) => {
+                    log::warn!("Invalid last signed height in {:?}: {:?}", path, e);
+                    return;
+                }
+            },
+            // the node hasn't signed any block yet.
+            Err(_) => return,
+        };
+        log::info!("Last signed height: {}", last_signed);
+        if let Some(tip) = self.current_height() {
+            if let Some(warning) = last_signed_height_warning(last_signed, tip) {
+                log::warn!("{}", warning);
+            }
+        }
+    }
+
+    /// Round timeout. Force round robin master node. After the rounds for current block height
+    /// reach `max_round_attempts`, the round time limit is extended. See `round_backoff`.
+    fn process_round_timeout(&mut self) -> NodeState {
+        if let NodeState::Master {
+            ref signature_map,
+            ref candidate_block,
+        } = self.current_state
+        {
+            if signature_map.len() as u8 >= self.params.threshold && self.submission.is_none() {
+                log::info!(
+                    "Round time limit is reached with {} signatures, less than the collection quorum {}. Produce the block with them.",
+                    signature_map.len(),
+                    self.params.collection_quorum
+                );
+                let (block, signature_map) = (candidate_block.clone(), signature_map.clone());
+                return self.produce_block(&block, &signature_map);
+            }
+        }
+        let participants = match self.current_state {
+            NodeState::Master {
+                ref signature_map, ..
+            } => self.signer_indices(signature_map),
+            _ => vec![],
+        };
+        if let NodeState::Master { .. } = self.current_state {
+            self.record_missing_signers(&participants);
+        }
+        self.write_round_event(RoundOutcome::Timeout, participants);
+        self.rounds_in_flight.clear();
+        self.observed_blocks.clear();
+        self.equivocated_masters.clear();
+
+        if let Some(index) = self.params.fixed_master {
+            log::warn!(
+                "Round timed out. Master is fixed to signer {}, so the next round is also started by it.",
+                index
+            );
+        }
+        self.round_attempts += 1;
+        self.metrics.rounds_failed.fetch_add(1, Ordering::Relaxed);
+        if self.round_attempts >= self.params.max_round_attempts {
+            log::error!(
+                "Failed to produce the block on height {} in {} rounds. Round time limit is extended to {:?}.",
+                self.round_height,
+                self.round_attempts,
+                self.round_timelimit * self.round_backoff()
+            );
+            self.dump_metrics();
+        }
+        self.round_robin_master()
+    }
+
+    /// Multiplier of the round time limit. It is 1 until the rounds for current block height
+    /// reach `max_round_attempts`, and then doubles on each failed round up to
+    /// `MAX_ROUND_BACKOFF`. Slow signers get more time, and rounds never stop.
+    fn round_backoff(&self) -> u32 {
+        if self.round_attempts < self.params.max_round_attempts {
+            return 1;
+        }
+        let exponent = self.round_attempts - self.params.max_round_attempts + 1;
+        if exponent >= 32 {
+            return MAX_ROUND_BACKOFF;
+        }
+        std::cmp::min(1u32 << exponent, MAX_ROUND_BACKOFF)
+    }
+
+    /// Number of signatures master waits for in the current round. It is `collection_quorum`,
+    /// lowered by the excluded signers, but never less than threshold.
+    fn collection_quorum(&self) -> u8 {
+        let active = self.params.pubkey_list.len() - self.excluded_signers.len();
+        let quorum = std::cmp::min(self.params.collection_quorum as usize, active) as u8;
+        std::cmp::max(quorum, self.params.threshold)
+    }
+
+    /// Count the round as master for the signers which didn't send a signature for it. When a
+    /// signer missed `max_missed_rounds` rounds in a row, master stops waiting for it. Signers
+    /// which master waits for never become less than threshold.
+    fn record_missing_signers(&mut self, participants: &[usize]) {
+        if self.params.max_missed_rounds == 0 {
+            return;
+        }
+        let max_excluded = self.params.pubkey_list.len() - self.params.threshold as usize;
+        for index in 0..self.params.pubkey_list.len() {
+            if index == self.params.self_node_index || participants.contains(&index) {
+                continue;
+            }
+            self.missed_rounds[index] += 1;
+            if self.missed_rounds[index] >= self.params.max_missed_rounds
+                && self.excluded_signers.len() < max_excluded
+                && self.excluded_signers.insert(index)
+            {
+                log::warn!(
+                    "Signer {} is excluded from the collection quorum. It missed {} rounds in a row.",
+                    index,
+                    self.missed_rounds[index]
+                );
+            }
+        }
+    }
+
+    /// The signer sent a signature, so master waits for it again.
+    fn include_signer(&mut self, index: usize) {
+        self.missed_rounds[index] = 0;
+        if self.excluded_signers.remove(&index) {
+            log::info!(
+                "Signer {} is included in the collection quorum again.",
+                index
+            );
+        }
+    }
+
+    /// The signer which sent a message is alive.
+    fn record_response(&mut self, sender_id: &SignerID) {
+        if let Some(index) = sender_index(sender_id, &self.params.pubkey_list) {
+            self.peers.insert(index);
+        }
+    }
+
+    /// Listen on the admin socket at `path`. The socket is readable only by the owner, because
+    /// the status tells which signers are alive.
+    pub fn open_admin_socket(&mut self, path: &Path) -> Result<(), Error> {
+        // the socket file of the last run remains if the node didn't stop gracefully.
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+        self.admin_socket = Some(listener);
+        Ok(())
+    }
+
+    /// Write the status to each client connected to the admin socket, and close the connection.
+    /// The socket is read-only, so anything the client sends is ignored.
+    pub fn serve_admin_requests(&mut self) {
+        let listener = match self.admin_socket {
+            Some(ref listener) => listener,
+            None => return,
+        };
+        let mut status = serde_json::to_string(&self.status()).unwrap();
+        status.push('\n');
+        while let Ok((mut stream, _)) = listener.accept() {
+            let result = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(Duration::from_secs(1))))
+                .and_then(|_| stream.write_all(status.as_bytes()));
+            if let Err(e) = result {
+                log::warn!("Failed to write status to admin socket client: {:?}", e);
+            }
+        }
+    }
+
+    fn status(&self) -> NodeStatus {
+        let (state, signatures) = match self.current_state {
+            NodeState::Joining => ("joining", 0),
+            NodeState::Member => ("member", 0),
+            NodeState::Master {
+                ref signature_map, ..
+            } => ("master", signature_map.len()),
+        };
+        let mut peers: Vec<usize> = self.peers.iter().cloned().collect();
+        peers.sort();
+        NodeStatus {
+            round: self.round,
+            state,
+            master_index: self.master_index,
+            signatures,
+            peers,
+        }
+    }
+
+    /// The block of the current round is produced, so the next round is the first one for the
+    /// next height.
+    fn next_height(&mut self) {
+        self.round_height += 1;
+        self.round_attempts = 0;
+    }
+
+    /// Height of the best block on the last `chain_info`.
+    fn current_height(&self) -> Option<u64> {
+        self.chain.as_ref().map(|info| info.blocks)
+    }
+
+    fn process_completedblock(&mut self, sender_id: &SignerID, _block: &Block) -> NodeState {
+        if sender_index(sender_id, &self.params.pubkey_list) == Some(self.master_index) {
+            // authorization master.
+            // start round robin of master node.
+            self.metrics
+                .rounds_completed
+                .fetch_add(1, Ordering::Relaxed);
+            self.next_height();
+            self.write_last_signed_height();
+            return self.round_robin_master();
+        }
+        self.current_state.clone()
+    }
+
+    fn process_roundfailure(&self, _sender_id: &SignerID) -> NodeState {
+        self.current_state.clone()
+    }
+}
+
+pub struct NodeParameters {
+    pub pubkey_list: Vec<PublicKey>,
+    pub threshold: u8,
+    pub private_key: PrivateKey,
+    pub address: Address,
+    pub signer_id: SignerID,
+    pub master_flag: bool,
+    pub self_node_index: usize,
+    pub round_duration: u64,
+    pub max_round_attempts: u32,
+    pub policy: BlockPolicy,
+    /// Max random jitter added to the round time limit.
+    pub timeout_jitter: Duration,
+    /// Interval of requesting candidate block again on master.
+    pub poll_interval: Duration,
+    /// File which the metrics snapshot is written to at the end of each round.
+    pub metrics_dump_path: Option<PathBuf>,
+    pub sighash_variant: SighashVariant,
+    /// Number of signatures master waits for before combining them. It is threshold or more.
+    pub collection_quorum: u8,
+    /// Rule to elect master of each round.
+    pub election: ElectionStrategy,
+    /// Count of consecutive rounds as master without the signature of a signer, after which
+    /// master doesn't wait for it to meet `collection_quorum`. 0 disables it.
+    pub max_missed_rounds: u32,
+    /// Number of round time limits without progress, after which the state machine is reset.
+    /// 0 disables the watchdog.
+    pub watchdog_rounds: u32,
+    /// Signer index of the master of all rounds. Master doesn't rotate when it is set.
+    pub fixed_master: Option<usize>,
+    /// Number of rounds which a member signs at the same time. Candidate blocks from other
+    /// masters are dropped while this number of rounds are in flight. No limit when it is `None`.
+    pub max_concurrent_rounds: Option<u32>,
+    /// Time master waits for members to be ready before broadcasting the candidate block. Zero
+    /// disables the readiness check.
+    pub readiness_window: Duration,
+    /// Time master waits for the submitted block to become the best block of Tapyrus Core. Zero
+    /// disables the confirmation.
+    pub submit_confirmation_timeout: Duration,
+    /// Members tell each other the hash of the candidate block, and don't sign the blocks of the
+    /// master which proposed different blocks in a round.
+    pub detect_equivocation: bool,
+    /// Sign one candidate block as a member and exit, see `sign_once_finished`. The node never
+    /// becomes master.
+    pub sign_once: bool,
+    /// Time after the block is produced, in which master counts late signatures for it in
+    /// participation metrics. Zero disables it.
+    pub late_signature_grace: Duration,
+    /// File which the event of each round is appended to as a JSON line. `-` means stderr.
+    pub events_path: Option<PathBuf>,
+    /// File which the height of the block this node signed last time is written to.
+    pub last_signed_height_path: Option<PathBuf>,
+}
+
+impl NodeParameters {
+    pub fn new(
         pubkey_list: Vec<PublicKey>,
         private_key: PrivateKey,
         threshold: u8,
-        rpc: T,
         master_flag: bool,
         round_duration: u64,
-        skip_waiting_ibd: bool,
-    ) -> NodeParameters<T> {
+    ) -> NodeParameters {
         let secp = secp256k1::Secp256k1::new();
         let self_pubkey = private_key.public_key(&secp);
         let address = Address::p2pkh(&self_pubkey, private_key.network);
@@ -378,168 +1871,292 @@ impl<T: TapyrusApi> NodeParameters<T> {
         let master_flag = master_flag;
 
         let mut pubkey_list = pubkey_list;
-        &pubkey_list.sort();
-        let self_node_index = sender_index(&signer_id, &pubkey_list);
+        canonical_order(&mut pubkey_list);
+        let self_node_index = sender_index(&signer_id, &pubkey_list)
+            .expect("The private key isn't paired with any of the public keys.");
         NodeParameters {
             pubkey_list,
             threshold,
             private_key,
-            rpc: Arc::new(rpc),
             address,
             signer_id,
             master_flag,
             self_node_index,
             round_duration,
-            skip_waiting_ibd,
+            max_round_attempts: MAX_ROUND_ATTEMPTS_DEFAULT,
+            policy: BlockPolicy::default(),
+            timeout_jitter: Duration::from_millis(0),
+            poll_interval: Duration::from_millis(POLL_INTERVAL_DEFAULT_MILLIS),
+            metrics_dump_path: None,
+            sighash_variant: SighashVariant::default(),
+            collection_quorum: threshold,
+            election: ElectionStrategy::default(),
+            max_missed_rounds: 0,
+            watchdog_rounds: WATCHDOG_ROUNDS_DEFAULT,
+            fixed_master: None,
+            max_concurrent_rounds: None,
+            readiness_window: Duration::from_millis(0),
+            submit_confirmation_timeout: Duration::from_millis(0),
+            detect_equivocation: false,
+            sign_once: false,
+            late_signature_grace: Duration::from_millis(0),
+            events_path: None,
+            last_signed_height_path: None,
         }
     }
+
+    /// Signer indices and public keys of the signer network, in canonical order.
+    pub fn signers(&self) -> Vec<(usize, PublicKey)> {
+        self.pubkey_list.iter().cloned().enumerate().collect()
+    }
+}
+
+/// Private key is redacted so that the parameters can be logged safely.
+impl std::fmt::Debug for NodeParameters {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NodeParameters")
+            .field("pubkey_list", &self.pubkey_list)
+            .field("threshold", &self.threshold)
+            .field("private_key", &"PrivateKey(***)")
+            .field("address", &self.address)
+            .field("signer_id", &self.signer_id)
+            .field("master_flag", &self.master_flag)
+            .field("self_node_index", &self.self_node_index)
+            .field("round_duration", &self.round_duration)
+            .field("max_round_attempts", &self.max_round_attempts)
+            .field("policy", &self.policy)
+            .field("timeout_jitter", &self.timeout_jitter)
+            .field("poll_interval", &self.poll_interval)
+            .field("metrics_dump_path", &self.metrics_dump_path)
+            .field("sighash_variant", &self.sighash_variant)
+            .field("collection_quorum", &self.collection_quorum)
+            .field("election", &self.election)
+            .field("max_missed_rounds", &self.max_missed_rounds)
+            .field("watchdog_rounds", &self.watchdog_rounds)
+            .field("fixed_master", &self.fixed_master)
+            .field("max_concurrent_rounds", &self.max_concurrent_rounds)
+            .field("readiness_window", &self.readiness_window)
+            .field(
+                "submit_confirmation_timeout",
+                &self.submit_confirmation_timeout,
+            )
+            .field("detect_equivocation", &self.detect_equivocation)
+            .field("sign_once", &self.sign_once)
+            .field("late_signature_grace", &self.late_signature_grace)
+            .field("events_path", &self.events_path)
+            .field("last_signed_height_path", &self.last_signed_height_path)
+            .finish()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, VecDeque};
+    use std::ops::{Deref, DerefMut};
+    use std::sync::atomic::Ordering;
     use std::sync::mpsc::{channel, Receiver, Sender};
     use std::sync::{Arc, Mutex};
-    use std::thread;
-    use std::thread::JoinHandle;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
-    use redis::ControlFlow;
-
-    use crate::net::{ConnectionManager, ConnectionManagerError, Message, Signature, SignerID};
+    use crate::blockdata::Block;
+    use crate::election::{ElectionStrategy, HashBased, LeaderElection};
+    use crate::net::{Message, MessageType, Signature, SignerID};
     use crate::rpc::tests::{safety, safety_error, MockRpc, SafetyBlock};
     use crate::rpc::TapyrusApi;
     use crate::sign::sign;
-    use crate::signer_node::{NodeParameters, NodeState, SignerNode};
+    use crate::signer_node::{
+        canonical_order, select_private_key, BlockToSubmit, NodeOutput, NodeParameters, NodeState,
+        SignerNode,
+    };
     use crate::test_helper::{get_block, TestKeys};
 
     type SpyMethod = Box<dyn Fn(Arc<Message>) -> () + Send + 'static>;
 
-    /// ConnectionManager for testing.
-    pub struct TestConnectionManager {
-        /// This is count of messages. TestConnectionManager waits for receiving the number of message.
-        pub receive_count: u32,
-        /// sender of message
-        pub sender: Sender<Message>,
-        /// receiver of message
-        pub receiver: Receiver<Message>,
-        /// A function which is called when the node try to broadcast messages.
-        pub broadcast_assert: SpyMethod,
-    }
-
-    impl TestConnectionManager {
-        pub fn new(receive_count: u32, broadcast_assert: SpyMethod) -> Self {
-            let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
-            TestConnectionManager {
-                receive_count,
-                sender,
-                receiver,
-                broadcast_assert,
-            }
-        }
-    }
-
-    impl ConnectionManager for TestConnectionManager {
-        type ERROR = crate::errors::Error;
-        fn broadcast_message(&self, message: Message) {
-            let rc_message = Arc::new(message);
-            (self.broadcast_assert)(rc_message.clone());
-        }
-
-        fn start(
-            &self,
-            mut message_processor: impl FnMut(Message) -> ControlFlow<()> + Send + 'static,
-        ) -> JoinHandle<()> {
-            for _count in 0..self.receive_count {
-                match self.receiver.recv() {
-                    Ok(message) => {
-                        log::debug!("Test message receiving!! {:?}", message.message_type);
-                        message_processor(message);
-                    }
-                    Err(e) => log::warn!("happend receiver error: {:?}", e),
+    /// Signer node which does the requests in its outputs with `rpc`, and passes the broadcasted
+    /// messages to `spy`, like the `node` command.
+    pub struct TestNode<T: TapyrusApi> {
+        pub node: SignerNode,
+        pub rpc: T,
+        pub spy: SpyMethod,
+    }
+
+    impl<T: TapyrusApi> Deref for TestNode<T> {
+        type Target = SignerNode;
+        fn deref(&self) -> &SignerNode {
+            &self.node
+        }
+    }
+
+    impl<T: TapyrusApi> DerefMut for TestNode<T> {
+        fn deref_mut(&mut self) -> &mut SignerNode {
+            &mut self.node
+        }
+    }
+
+    impl<T: TapyrusApi> TestNode<T> {
+        /// Time of the next call. It doesn't go back from the time given to the last `tick`.
+        fn now(&self) -> Instant {
+            std::cmp::max(self.node.now, Instant::now())
+        }
+
+        /// Do the requests in `output` and the outputs of their results.
+        fn drive(&mut self, output: NodeOutput) {
+            let mut outputs = VecDeque::new();
+            outputs.push_back(output);
+            while let Some(output) = outputs.pop_front() {
+                let now = self.node.now;
+                for message in output.messages {
+                    (self.spy)(Arc::new(message));
+                }
+                if output.fetch_chain_info {
+                    let info = self.rpc.getblockchaininfo();
+                    outputs.push_back(self.node.chain_info(info, now));
+                }
+                if let Some(address) = output.fetch_candidate_block {
+                    let block = self.rpc.getnewblock(&address);
+                    outputs.push_back(self.node.candidate_block(block, now));
+                }
+                if let Some(block) = output.block_to_test {
+                    let result = self.rpc.testproposedblock(&block);
+                    outputs.push_back(self.node.block_tested(result, now));
+                }
+                if let Some(BlockToSubmit { block, signatures }) = output.block_to_submit {
+                    let rpc = &self.rpc;
+                    let result = rpc.combineblocksigs(&block, &signatures).and_then(|block| {
+                        rpc.submitblock(&block)?;
+                        Ok(block)
+                    });
+                    outputs.push_back(self.node.block_submitted(result, now));
                 }
             }
-            thread::Builder::new()
-                .name("TestConnectionManager start Thread".to_string())
-                .spawn(|| {
-                    thread::sleep(Duration::from_millis(300));
-                })
-                .unwrap()
         }
 
-        fn error_handler(
+        /// Drive the output of the internal call, which returned `state`.
+        fn drive_state(&mut self, state: NodeState) -> NodeState {
+            self.node.current_state = state;
+            let output = self.node.take_output();
+            self.drive(output);
+            self.node.current_state.clone()
+        }
+
+        pub fn start(&mut self) -> NodeState {
+            let now = self.now();
+            let info = self.rpc.getblockchaininfo();
+            let output = self.node.chain_info(info, now);
+            self.drive(output);
+            let output = self.node.start(now);
+            self.drive(output);
+            self.node.current_state.clone()
+        }
+
+        pub fn tick(&mut self, now: Instant) -> NodeState {
+            let output = self.node.tick(now);
+            self.drive(output);
+            self.node.current_state.clone()
+        }
+
+        pub fn process_message(&mut self, message: Message) -> NodeState {
+            let now = self.now();
+            let output = self.node.process_message(message, now);
+            self.drive(output);
+            self.node.current_state.clone()
+        }
+
+        pub fn block_notified(&mut self, block_hash: &str, now: Instant) -> NodeState {
+            let output = self.node.block_notified(block_hash.to_string(), now);
+            self.drive(output);
+            self.node.current_state.clone()
+        }
+
+        pub fn start_new_round(&mut self) -> NodeState {
+            self.node.now = self.now();
+            let state = self.node.start_new_round();
+            self.drive_state(state)
+        }
+
+        pub fn propose_candidate_block(&mut self) -> NodeState {
+            self.node.now = self.now();
+            let state = self.node.propose_candidate_block();
+            self.drive_state(state)
+        }
+
+        pub fn process_candidateblock(&mut self, sender_id: &SignerID, block: &Block) -> NodeState {
+            self.node.now = self.now();
+            let state = self.node.process_candidateblock(sender_id, block);
+            self.drive_state(state)
+        }
+
+        pub fn process_signature(
             &mut self,
-        ) -> Option<Receiver<ConnectionManagerError<crate::errors::Error>>> {
-            None::<Receiver<ConnectionManagerError<crate::errors::Error>>>
+            sender_id: &SignerID,
+            signature: &Signature,
+        ) -> NodeState {
+            self.node.now = self.now();
+            let state = self.node.process_signature(sender_id, signature);
+            self.drive_state(state)
+        }
+
+        pub fn process_completedblock(&mut self, sender_id: &SignerID, block: &Block) -> NodeState {
+            self.node.now = self.now();
+            let state = self.node.process_completedblock(sender_id, block);
+            self.drive_state(state)
+        }
+
+        pub fn process_round_timeout(&mut self) -> NodeState {
+            self.node.now = self.now();
+            let state = self.node.process_round_timeout();
+            self.drive_state(state)
+        }
+
+        pub fn round_robin_master(&mut self) -> NodeState {
+            self.node.now = self.now();
+            let state = self.node.round_robin_master();
+            self.drive_state(state)
         }
     }
 
-    fn create_node<T: TapyrusApi>(
-        current_state: NodeState,
-        rpc: T,
-    ) -> SignerNode<T, TestConnectionManager> {
-        let closure: SpyMethod = Box::new(move |_message: Arc<Message>| {});
-        let (node, _) = create_node_with_closure_and_publish_count(current_state, rpc, closure, 1);
-        node
+    fn create_node<T: TapyrusApi>(current_state: NodeState, rpc: T) -> TestNode<T> {
+        let spy: SpyMethod = Box::new(move |_message: Arc<Message>| {});
+        create_node_with_spy(current_state, rpc, spy)
     }
 
-    fn create_node_with_closure_and_publish_count<T: TapyrusApi>(
+    fn create_node_with_spy<T: TapyrusApi>(
         current_state: NodeState,
         rpc: T,
         spy: SpyMethod,
-        publish_count: u32,
-    ) -> (SignerNode<T, TestConnectionManager>, Sender<Message>) {
+    ) -> TestNode<T> {
         let testkeys = TestKeys::new();
         let pubkey_list = testkeys.pubkeys();
         let threshold = 3;
         let private_key = testkeys.key[0];
 
-        let mut params =
-            NodeParameters::new(pubkey_list, private_key, threshold, rpc, true, 0, true);
+        let mut params = NodeParameters::new(pubkey_list, private_key, threshold, true, 0);
         params.round_duration = 0;
-        let con = TestConnectionManager::new(publish_count, spy);
-        let broadcaster = con.sender.clone();
-        let mut node = SignerNode::new(con, params);
+        let mut node = SignerNode::new(params);
         node.current_state = current_state;
-        (node, broadcaster)
+        node.chain = rpc.getblockchaininfo().ok();
+        TestNode { node, rpc, spy }
     }
 
-    /// Run node on other thread.
-    pub fn setup_node(
-        spy: SpyMethod,
-        arc_block: SafetyBlock,
-    ) -> (
-        Arc<Mutex<SignerNode<MockRpc, TestConnectionManager>>>,
-        Sender<u32>,
-        Sender<Message>,
-    ) {
+    /// Member node of 2 of 5 signers, which is started.
+    pub fn setup_node(spy: SpyMethod, arc_block: SafetyBlock) -> TestNode<MockRpc> {
         let testkeys = TestKeys::new();
         let pubkey_list = testkeys.pubkeys();
         let threshold = 2;
         let private_key = testkeys.key[0];
 
-        let con = TestConnectionManager::new(1, spy);
         let rpc = MockRpc {
             return_block: arc_block.clone(),
         };
-        let broadcaster = con.sender.clone();
-
-        let (stop_signal, stop_handler): (Sender<u32>, Receiver<u32>) = channel();
-        let mut params =
-            NodeParameters::new(pubkey_list, private_key, threshold, rpc, false, 0, true);
+        let mut params = NodeParameters::new(pubkey_list, private_key, threshold, false, 0);
         params.round_duration = 0;
-        let arc_node = Arc::new(Mutex::new(SignerNode::new(con, params)));
-        let node = arc_node.clone();
-        let _handle = thread::Builder::new()
-            .name("NodeMainThread".to_string())
-            .spawn(move || {
-                let mut node = node.lock().unwrap();
-                node.stop_handler(stop_handler);
-                node.start();
-            })
-            .unwrap();
-
-        (arc_node, stop_signal, broadcaster)
+        let mut node = TestNode {
+            node: SignerNode::new(params),
+            rpc,
+            spy,
+        };
+        node.start();
+        node
     }
 
     pub fn get_initial_master_state() -> NodeState {
@@ -587,113 +2204,1252 @@ mod tests {
         ];
         let threshold = 3;
         let private_key = testkeys.key[0];
-        let params = NodeParameters::new(
-            pubkey_list.clone(),
-            private_key,
-            threshold,
+        let params = NodeParameters::new(pubkey_list.clone(), private_key, threshold, true, 0);
+
+        assert_ne!(params.pubkey_list[0], pubkey_list[0]);
+        assert_eq!(params.pubkey_list[1], pubkey_list[4]);
+    }
+
+    #[test]
+    fn test_canonical_order_keys_differ_only_in_last_byte() {
+        use bitcoin::util::key::PublicKey;
+
+        let mut base = Vec::new();
+        TestKeys::new().pubkeys()[0].write_into(&mut base);
+        let keys: Vec<PublicKey> = (0..=255u8)
+            .filter_map(|b| {
+                let mut bytes = base.clone();
+                bytes[32] = b;
+                PublicKey::from_slice(&bytes).ok()
+            })
+            .take(3)
+            .collect();
+        assert_eq!(keys.len(), 3);
+
+        let mut sorted = keys.clone();
+        canonical_order(&mut sorted);
+        for permutation in &[[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+            let mut input: Vec<PublicKey> = permutation.iter().map(|&i| keys[i]).collect();
+            canonical_order(&mut input);
+            assert_eq!(input, sorted);
+        }
+    }
+
+    #[test]
+    fn test_canonical_order_parity_prefix() {
+        use bitcoin::util::key::PublicKey;
+        use std::str::FromStr;
+
+        // Same x coordinate, differ only in y parity.
+        let even = PublicKey::from_str(
+            "02ce7edc292d7b747fab2f23584bbafaffde5c8ff17cf689969614441e0527b900",
+        )
+        .unwrap();
+        let odd = PublicKey::from_str(
+            "03ce7edc292d7b747fab2f23584bbafaffde5c8ff17cf689969614441e0527b900",
+        )
+        .unwrap();
+
+        let mut keys1 = vec![even, odd];
+        let mut keys2 = vec![odd, even];
+        canonical_order(&mut keys1);
+        canonical_order(&mut keys2);
+        assert_eq!(keys1, keys2);
+        assert_ne!(keys1[0], keys1[1]);
+    }
+
+    #[test]
+    fn test_signers() {
+        let node = create_node(
+            NodeState::Member,
             MockRpc {
-                return_block: safety_error("Not set block.".to_string()),
+                return_block: safety(get_block(0)),
             },
-            true,
-            0,
-            true,
         );
+        let signers = node.params.signers();
+        let pubkeys = TestKeys::new().pubkeys();
+
+        // pubkeys sorted index map;
+        // 0 -> 4
+        // 1 -> 0
+        // 2 -> 3
+        // 3 -> 2
+        // 4 -> 1
+        assert_eq!(
+            signers,
+            vec![
+                (0, pubkeys[1]),
+                (1, pubkeys[4]),
+                (2, pubkeys[3]),
+                (3, pubkeys[2]),
+                (4, pubkeys[0]),
+            ]
+        );
+        assert_eq!(
+            signers[node.params.self_node_index].1,
+            node.params.signer_id.pubkey
+        );
+    }
+
+    #[test]
+    fn test_select_private_key() {
+        let testkeys = TestKeys::new();
+        // key[0] is not a member of the signer network.
+        let pubkey_list = testkeys.pubkeys()[1..].to_vec();
+
+        let selected =
+            select_private_key(&[testkeys.key[0], testkeys.key[2]], &pubkey_list).unwrap();
+        assert_eq!(selected, testkeys.key[2]);
+
+        let result = select_private_key(&[testkeys.key[0]], &pubkey_list);
+        assert!(result.is_err(), "no key is paired.");
+
+        let result = select_private_key(&[testkeys.key[1], testkeys.key[2]], &pubkey_list);
+        assert!(result.is_err(), "several keys are paired.");
+    }
+
+    #[test]
+    fn test_candidate_process() {
+        let (broadcast_s, broadcast_r): (Sender<Arc<Message>>, Receiver<Arc<Message>>) = channel();
+        let assertion = Box::new(move |message: Arc<Message>| {
+            broadcast_s.send(message).unwrap();
+        });
+        let arc_block = safety(get_block(0));
+        let mut node = setup_node(assertion, arc_block);
+        let message_str = r#"{"message_type": {"Candidateblock": [0, 0, 0, 32, 237, 101, 140, 196, 6, 112, 204, 237, 162, 59, 176, 182, 20, 130, 31, 230, 212, 138, 65, 209, 7, 209, 159, 63, 58, 86, 8, 173, 61, 72, 48, 146, 177, 81, 22, 10, 183, 17, 51, 180, 40, 225, 246, 46, 174, 181, 152, 174, 133, 143, 246, 96, 23, 201, 150, 1, 242, 144, 136, 183, 198, 74, 72, 29, 98, 132, 225, 69, 210, 155, 112, 191, 84, 57, 45, 41, 112, 16, 49, 210, 175, 159, 237, 95, 155, 178, 31, 187, 40, 79, 167, 28, 235, 35, 143, 105, 166, 212, 9, 93, 0, 1, 2, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 12, 0, 0, 0, 3, 92, 1, 1, 255, 255, 255, 255, 2, 0, 242, 5, 42, 1, 0, 0, 0, 25, 118, 169, 20, 207, 18, 219, 192, 75, 176, 222, 111, 182, 168, 122, 90, 235, 75, 46, 116, 201, 112, 6, 178, 136, 172, 0, 0, 0, 0, 0, 0, 0, 0, 38, 106, 36, 170, 33, 169, 237, 226, 246, 28, 63, 113, 209, 222, 253, 63, 169, 153, 223, 163, 105, 83, 117, 92, 105, 6, 137, 121, 153, 98, 180, 139, 235, 216, 54, 151, 78, 140, 249, 1, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] },"sender_id": [3, 131, 26, 105, 184, 0, 152, 51, 171, 91, 3, 38, 1, 46, 175, 72, 155, 254, 163, 90, 115, 33, 177, 202, 21, 177, 29, 136, 19, 20, 35, 250, 252], "height": 1, "round": 0}"#;
+        let message = serde_json::from_str::<Message>(message_str).unwrap();
+
+        node.process_message(message);
+        let broadcast_message = broadcast_r.try_recv().unwrap();
+        let actual = format!("{:?}", &broadcast_message.message_type);
+        assert!(actual.starts_with("Signature(Signature"));
+    }
+
+    #[test]
+    fn test_candidate_process_invalid_block() {
+        let (broadcast_s, broadcast_r): (Sender<Arc<Message>>, Receiver<Arc<Message>>) = channel();
+        let spy = Box::new(move |message: Arc<Message>| {
+            broadcast_s.send(message).unwrap();
+        });
+        let arc_block = safety_error("invalid block!".to_string());
+        let mut node = setup_node(spy, arc_block);
+        let message_str = r#"{"message_type": {"Candidateblock": [0, 0, 0, 32, 237, 101, 140, 196, 6, 112, 204, 237, 162, 59, 176, 182, 20, 130, 31, 230, 212, 138, 65, 209, 7, 209, 159, 63, 58, 86, 8, 173, 61, 72, 48, 146, 177, 81, 22, 10, 183, 17, 51, 180, 40, 225, 246, 46, 174, 181, 152, 174, 133, 143, 246, 96, 23, 201, 150, 1, 242, 144, 136, 183, 198, 74, 72, 29, 98, 132, 225, 69, 210, 155, 112, 191, 84, 57, 45, 41, 112, 16, 49, 210, 175, 159, 237, 95, 155, 178, 31, 187, 40, 79, 167, 28, 235, 35, 143, 105, 166, 212, 9, 93, 0, 1, 2, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 12, 0, 0, 0, 3, 92, 1, 1, 255, 255, 255, 255, 2, 0, 242, 5, 42, 1, 0, 0, 0, 25, 118, 169, 20, 207, 18, 219, 192, 75, 176, 222, 111, 182, 168, 122, 90, 235, 75, 46, 116, 201, 112, 6, 178, 136, 172, 0, 0, 0, 0, 0, 0, 0, 0, 38, 106, 36, 170, 33, 169, 237, 226, 246, 28, 63, 113, 209, 222, 253, 63, 169, 153, 223, 163, 105, 83, 117, 92, 105, 6, 137, 121, 153, 98, 180, 139, 235, 216, 54, 151, 78, 140, 249, 1, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] },"sender_id": [3, 131, 26, 105, 184, 0, 152, 51, 171, 91, 3, 38, 1, 46, 175, 72, 155, 254, 163, 90, 115, 33, 177, 202, 21, 177, 29, 136, 19, 20, 35, 250, 252], "height": 1, "round": 0}"#;
+        let message = serde_json::from_str::<Message>(message_str).unwrap();
+
+        node.process_message(message);
+        match broadcast_r.try_recv() {
+            Ok(m) => panic!("Should not broadcast Signature message: {:?}", m),
+            Err(_e) => assert!(true),
+        }
+    }
+
+    #[test]
+    fn test_modify_master_index() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+
+        // pubkeys sorted index map;
+        // 0 -> 4
+        // 1 -> 0
+        // 2 -> 3
+        // 3 -> 2
+        // 4 -> 1
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[1]);
+        assert_eq!(node.master_index, 0); // in begin, master_index is 0.
+        let _next_state = node.process_candidateblock(&sender_id, &get_block(0));
+        assert_eq!(node.master_index, 0);
+
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[0]);
+        let _next_state = node.process_candidateblock(&sender_id, &get_block(0));
+        assert_eq!(node.master_index, 4);
+    }
+
+    #[test]
+    fn test_timeout_roundrobin() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        node.params.master_flag = false;
+        node.start();
+
+        assert_eq!(node.master_index, 0 as usize);
+        let now = Instant::now();
+        node.tick(now);
+        node.tick(now + Duration::from_secs(5));
+
+        assert_eq!(node.master_index, 1 as usize);
+    }
+
+    #[test]
+    fn test_backoff_after_max_round_attempts() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        node.params.max_round_attempts = 3;
+
+        node.current_state = node.process_round_timeout();
+        assert_eq!(node.master_index, 1);
+        node.current_state = node.process_round_timeout();
+        assert_eq!(node.master_index, 2);
+        assert_eq!(node.round_timelimit(), Duration::from_secs(5));
+
+        // reached max attempts, but rounds go on with longer time limit.
+        node.current_state = node.process_round_timeout();
+        assert_eq!(node.master_index, 3);
+        assert_eq!(node.round_timelimit(), Duration::from_secs(10));
+        node.current_state = node.process_round_timeout();
+        assert_eq!(node.master_index, 4);
+        assert_eq!(node.round_timelimit(), Duration::from_secs(20));
+
+        // time limit is capped.
+        for _ in 0..5 {
+            node.current_state = node.process_round_timeout();
+        }
+        assert_eq!(node.round_attempts, 9);
+        assert_eq!(node.round_timelimit(), Duration::from_secs(40));
+
+        // the block is produced, so the next height starts with the normal time limit.
+        node.master_index = 1;
+        node.process_completedblock(&SignerID::new(node.params.pubkey_list[1]), &get_block(0));
+        assert_eq!(node.round_attempts, 0);
+        assert_eq!(node.round_timelimit(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_resume_after_all_nodes_reached_max_round_attempts() {
+        // all signers of the federation, with queues of the messages which they broadcasted.
+        let testkeys = TestKeys::new();
+        let mut federation = vec![];
+        for private_key in testkeys.key.iter() {
+            let (sender, receiver): (Sender<Arc<Message>>, Receiver<Arc<Message>>) = channel();
+            let spy: SpyMethod = Box::new(move |message: Arc<Message>| {
+                sender.send(message).unwrap();
+            });
+            let rpc = MockRpc {
+                return_block: safety(get_block(0)),
+            };
+            let mut params = NodeParameters::new(testkeys.pubkeys(), *private_key, 3, false, 0);
+            params.max_round_attempts = 2;
+            let mut node = TestNode {
+                node: SignerNode::new(params),
+                rpc,
+                spy,
+            };
+            node.current_state = NodeState::Member;
+            node.chain = node.rpc.getblockchaininfo().ok();
+            federation.push((node, receiver));
+        }
+
+        // the network is down, so all rounds time out and every message is lost.
+        for _ in 0..3 {
+            for (node, receiver) in federation.iter_mut() {
+                node.current_state = node.process_round_timeout();
+                while receiver.try_recv().is_ok() {}
+            }
+        }
+        for (node, _) in federation.iter() {
+            assert_eq!(node.round_attempts, 3);
+            assert_eq!(node.round_height, 0);
+        }
+
+        // the network recovers. the next round produces the block.
+        for (node, _) in federation.iter_mut() {
+            node.current_state = node.process_round_timeout();
+        }
+        let mut completed = false;
+        for _ in 0..10 {
+            let messages: Vec<Arc<Message>> = federation
+                .iter()
+                .flat_map(|(_, receiver)| receiver.try_iter().collect::<Vec<_>>())
+                .collect();
+            for message in messages {
+                if let MessageType::Completedblock(_) = message.message_type {
+                    completed = true;
+                }
+                let payload = serde_json::to_string(&*message).unwrap();
+                for (node, _) in federation.iter_mut() {
+                    if node.params.signer_id != message.sender_id {
+                        node.process_message(serde_json::from_str(&payload).unwrap());
+                    }
+                }
+            }
+            if completed {
+                break;
+            }
+        }
+        assert!(completed, "Rounds should resume.");
+        for (node, _) in federation.iter() {
+            assert_eq!(node.round_height, 1);
+        }
+    }
+
+    #[test]
+    fn test_tick_round_timeout() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        let now = Instant::now();
+
+        // first tick sets time limit of the round.
+        node.tick(now);
+        node.tick(now + Duration::from_secs(4));
+        assert_eq!(node.master_index(), 0);
+
+        // round timeout. round robin master.
+        let next_state = node.tick(now + Duration::from_secs(5));
+        assert_eq!(node.master_index(), 1);
+        assert_eq!(next_state, NodeState::Member);
+
+        // receiving candidate block restarts the round.
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[4]);
+        node.process_candidateblock(&sender_id, &get_block(0));
+        node.tick(now + Duration::from_secs(9));
+        node.tick(now + Duration::from_secs(13));
+        assert_eq!(node.master_index(), 1);
+    }
+
+    #[test]
+    fn test_sign_once() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        // the node is master by the flag, but it doesn't propose in sign once mode.
+        let mut node = create_node(NodeState::Joining, rpc);
+        node.params.sign_once = true;
+        assert_eq!(node.start(), NodeState::Member);
+
+        let now = Instant::now();
+        node.tick(now);
+        assert!(!node.sign_once_finished(now + Duration::from_secs(1)));
+
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[1]);
+        node.process_candidateblock(&sender_id, &get_block(0));
+        assert!(node.contributed());
+        assert!(node.sign_once_finished(now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_sign_once_without_candidate_block() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(NodeState::Joining, rpc);
+        node.params.sign_once = true;
+        node.start();
+
+        // round time limit is 5 sec.
+        let now = Instant::now();
+        node.tick(now);
+        assert!(!node.sign_once_finished(now + Duration::from_secs(4)));
+        assert!(node.sign_once_finished(now + Duration::from_secs(5)));
+        assert!(!node.contributed());
+    }
+
+    #[test]
+    fn test_round_timings() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(NodeState::Member, rpc);
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[1]);
+
+        node.process_candidateblock(&sender_id, &get_block(0));
+        assert!(node.round_timings.block_validation.is_some());
+        assert_eq!(node.metrics.block_validation.snapshot().count, 1);
+        assert_eq!(node.metrics.block_fetch.snapshot().count, 0);
+
+        // timings are reset for the next round.
+        node.round_robin_master();
+        assert!(node.round_timings.block_validation.is_none());
+    }
+
+    #[test]
+    fn test_last_signed_height() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(NodeState::Member, rpc);
+        let path = std::env::temp_dir().join(format!(
+            "tapyrus-signer-last-signed-height-{}",
+            std::process::id()
+        ));
+        node.params.last_signed_height_path = Some(path.clone());
+        let master_id = SignerID::new(node.params.pubkey_list[node.master_index]);
+
+        // the chain tip is 0, so the round is for the block at height 1.
+        node.round_height = 1;
+        node.process_candidateblock(&master_id, &get_block(0));
+        node.process_completedblock(&master_id, &get_block(0));
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, "1");
+    }
+
+    #[test]
+    fn test_admin_socket() {
+        use std::io::Read;
+        use std::os::unix::net::UnixStream;
+
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(NodeState::Member, rpc);
+        let path =
+            std::env::temp_dir().join(format!("tapyrus-signer-admin-{}.sock", std::process::id()));
+        node.open_admin_socket(&path).unwrap();
+
+        // master collected signatures of itself and a member.
+        node.master_index = node.params.self_node_index;
+        node.current_state = node.propose_candidate_block();
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[1]);
+        let sig = sign(&TestKeys::new().key[1], &get_block(0).hash().unwrap());
+        node.process_message(Message {
+            message_type: MessageType::Signature(Signature(sig)),
+            sender_id,
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        node.serve_admin_requests();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let status: serde_json::Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(status["state"], "master");
+        assert_eq!(status["master_index"], node.params.self_node_index);
+        assert_eq!(status["signatures"], 2);
+        assert_eq!(status["round"], 0);
+        let index = super::sender_index(&sender_id, &node.params.pubkey_list).unwrap();
+        assert_eq!(status["peers"], serde_json::json!([index]));
+    }
+
+    #[test]
+    fn test_last_signed_height_warning() {
+        assert_eq!(super::last_signed_height_warning(10, 10), None);
+        assert_eq!(super::last_signed_height_warning(10, 110), None);
+        // marker ahead of the tip.
+        assert!(super::last_signed_height_warning(11, 10)
+            .unwrap()
+            .contains("rolled back"));
+        assert!(super::last_signed_height_warning(10, 111)
+            .unwrap()
+            .contains("missed blocks"));
+    }
+
+    #[test]
+    fn test_debug_does_not_leak_private_key() {
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let node = create_node(NodeState::Member, rpc);
+        let private_key = node.params.private_key;
+        let debug = format!("{:?} {:?}", node.params, node.current_state);
+
+        assert!(debug.contains("PrivateKey(***)"));
+        assert!(!debug.contains(&private_key.to_wif()));
+        assert!(!debug.contains(&hex::encode(&private_key.key[..])));
+    }
+
+    #[test]
+    fn test_dump_metrics() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        let path = std::env::temp_dir().join(format!(
+            "tapyrus-signer-metrics-{}.json",
+            std::process::id()
+        ));
+        node.params.metrics_dump_path = Some(path.clone());
+        let now = Instant::now();
+
+        node.tick(now);
+        node.tick(now + Duration::from_secs(5));
+
+        let dumped: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dumped["rounds_failed"], 1);
+        assert_eq!(dumped["rounds_completed"], 0);
+        assert_eq!(dumped["master_index"], 1);
+        assert_eq!(dumped["height"], 0);
+        assert_eq!(dumped["participation"], serde_json::json!([0, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_round_event() {
+        let initial_state = get_initial_master_state();
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        node.master_index = node.params.self_node_index;
+        node.round_started_at = Some(Instant::now());
+        let path = std::env::temp_dir().join(format!(
+            "tapyrus-signer-events-{}.jsonl",
+            std::process::id()
+        ));
+        node.params.events_path = Some(path.clone());
+
+        let block_hash = get_block(0).hash().unwrap();
+        let mut participants = vec![node.params.self_node_index];
+        for i in 1..3 {
+            let sender_id = SignerID::new(TestKeys::new().pubkeys()[i]);
+            participants.push(super::sender_index(&sender_id, &node.params.pubkey_list).unwrap());
+            let sig = sign(&TestKeys::new().key[i], &block_hash);
+            node.current_state = node.process_signature(&sender_id, &Signature(sig));
+        }
+        participants.sort();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        let mut keys: Vec<&String> = event.as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "height",
+                "latency_milliseconds",
+                "master_index",
+                "outcome",
+                "participants",
+                "round"
+            ]
+        );
+        assert_eq!(event["round"], 1);
+        assert_eq!(event["height"], 0);
+        assert_eq!(event["master_index"], 4);
+        assert_eq!(event["participants"], serde_json::json!(participants));
+        assert_eq!(event["outcome"], "signed");
+        assert!(event["latency_milliseconds"].is_u64());
+    }
+
+    #[test]
+    fn test_watchdog_resets_wedged_state() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        let master_id = SignerID::new(TestKeys::new().pubkeys()[1]);
+        let now = Instant::now();
+
+        // candidate blocks keep restarting the round timer, but the round never completes.
+        node.tick(now);
+        for secs in &[4, 8, 12] {
+            node.process_message(Message {
+                message_type: MessageType::Candidateblock(get_block(0)),
+                sender_id: master_id,
+                height: node.round_height,
+                round: node.round_attempts,
+            });
+            node.tick(now + Duration::from_secs(*secs));
+        }
+        assert_eq!(node.metrics.rounds_failed.load(Ordering::Relaxed), 0);
+        assert_eq!(node.metrics.watchdog_resets.load(Ordering::Relaxed), 0);
+
+        // no progress in 3 round time limits.
+        node.tick(now + Duration::from_secs(15));
+        assert_eq!(node.metrics.watchdog_resets.load(Ordering::Relaxed), 1);
+        assert_eq!(node.current_state, NodeState::Member);
+        assert_eq!(node.round_deadline, None);
+    }
+
+    #[test]
+    fn test_watchdog_disabled() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        node.params.watchdog_rounds = 0;
+        let master_id = SignerID::new(TestKeys::new().pubkeys()[1]);
+        let now = Instant::now();
+
+        node.tick(now);
+        for secs in &[4, 8, 12, 16] {
+            node.process_message(Message {
+                message_type: MessageType::Candidateblock(get_block(0)),
+                sender_id: master_id,
+                height: node.round_height,
+                round: node.round_attempts,
+            });
+            node.tick(now + Duration::from_secs(*secs));
+        }
+        assert_eq!(node.metrics.watchdog_resets.load(Ordering::Relaxed), 0);
+    }
+
+    fn create_readiness_master(
+        readiness_window: Duration,
+    ) -> (TestNode<MockRpc>, Arc<Mutex<Vec<MessageType>>>) {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let broadcasted = Arc::new(Mutex::new(Vec::new()));
+        let messages = broadcasted.clone();
+        let closure: SpyMethod = Box::new(move |message: Arc<Message>| {
+            let message_type = match message.message_type {
+                MessageType::Candidateblock(ref block) => {
+                    MessageType::Candidateblock(block.clone())
+                }
+                MessageType::Roundannounce(ref block) => MessageType::Roundannounce(block.clone()),
+                _ => MessageType::Roundfailure,
+            };
+            messages.lock().unwrap().push(message_type);
+        });
+        let mut node = create_node_with_spy(NodeState::Member, rpc, closure);
+        node.params.readiness_window = readiness_window;
+        node.current_state = node.start_new_round();
+        (node, broadcasted)
+    }
+
+    fn ready_message(node: &TestNode<MockRpc>, index: usize) -> Message {
+        Message {
+            message_type: MessageType::Ready {
+                signer_index: index,
+                block_hash: get_block(0).hash().unwrap(),
+            },
+            sender_id: SignerID::new(node.params.pubkey_list[index]),
+            height: node.round_height,
+            round: node.round_attempts,
+        }
+    }
+
+    #[test]
+    fn test_readiness_check() {
+        let (mut node, broadcasted) = create_readiness_master(Duration::from_secs(1));
+        assert_eq!(
+            *broadcasted.lock().unwrap(),
+            vec![MessageType::Roundannounce(get_block(0))]
+        );
+        let now = Instant::now();
+        node.tick(now);
+
+        // threshold is 3, so master waits for 2 members and doesn't wait for the others.
+        let message = ready_message(&node, 0);
+        node.process_message(message);
+        assert_eq!(broadcasted.lock().unwrap().len(), 1);
+        let message = ready_message(&node, 1);
+        node.process_message(message);
+        assert_eq!(
+            broadcasted.lock().unwrap()[1],
+            MessageType::Candidateblock(get_block(0))
+        );
+        match node.current_state {
+            NodeState::Master { .. } => {}
+            ref state => panic!("Should be Master, but {:?}", state),
+        }
+    }
+
+    #[test]
+    fn test_member_sends_ready() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let (broadcast_s, broadcast_r): (Sender<Message>, Receiver<Message>) = channel();
+        let closure: SpyMethod = Box::new(move |message: Arc<Message>| {
+            let message = Message {
+                message_type: match message.message_type {
+                    MessageType::Ready {
+                        signer_index,
+                        block_hash,
+                    } => MessageType::Ready {
+                        signer_index,
+                        block_hash,
+                    },
+                    _ => MessageType::Roundfailure,
+                },
+                sender_id: message.sender_id,
+                height: message.height,
+                round: message.round,
+            };
+            broadcast_s.send(message).unwrap();
+        });
+        let mut node = create_node_with_spy(NodeState::Member, rpc, closure);
+        let master_id = SignerID::new(node.params.pubkey_list[1]);
+
+        node.process_message(Message {
+            message_type: MessageType::Roundannounce(get_block(0)),
+            sender_id: master_id,
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        let message = broadcast_r.try_recv().unwrap();
+        assert_eq!(
+            message.message_type,
+            MessageType::Ready {
+                signer_index: 4,
+                block_hash: get_block(0).hash().unwrap(),
+            }
+        );
+        assert_eq!(message.sender_id, node.params.signer_id);
+        // the member doesn't sign until the candidate block is broadcasted.
+        assert!(broadcast_r.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_readiness_window_expired() {
+        let (mut node, broadcasted) = create_readiness_master(Duration::from_secs(1));
+        let now = Instant::now();
+        node.tick(now);
+
+        let message = ready_message(&node, 0);
+        node.process_message(message);
+        // readiness for the index of other signer is ignored.
+        let mut message = ready_message(&node, 1);
+        message.sender_id = SignerID::new(node.params.pubkey_list[2]);
+        node.process_message(message);
+
+        // the other member doesn't send ready in the window.
+        node.tick(now + Duration::from_secs(1));
+        let message = ready_message(&node, 3);
+        node.process_message(message);
+        assert_eq!(
+            *broadcasted.lock().unwrap(),
+            vec![MessageType::Roundannounce(get_block(0))]
+        );
+    }
+
+    #[test]
+    fn test_detect_equivocation() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let broadcasted = Arc::new(Mutex::new(Vec::new()));
+        let messages = broadcasted.clone();
+        let closure: SpyMethod = Box::new(move |message: Arc<Message>| {
+            let name = match message.message_type {
+                MessageType::Blockhash { .. } => "Blockhash",
+                MessageType::Signature(_) => "Signature",
+                _ => "Other",
+            };
+            messages.lock().unwrap().push(name);
+        });
+        let mut node = create_node_with_spy(NodeState::Member, rpc, closure);
+        node.params.detect_equivocation = true;
+        let pubkeys = node.params.pubkey_list.clone();
+
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[1]),
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert_eq!(*broadcasted.lock().unwrap(), vec!["Blockhash", "Signature"]);
+
+        // other member received another block from the same master.
+        node.process_message(Message {
+            message_type: MessageType::Blockhash {
+                master_index: 1,
+                block_hash: get_block(1).hash().unwrap(),
+            },
+            sender_id: SignerID::new(pubkeys[2]),
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert!(node.equivocated_masters.contains(&1));
+
+        // the member doesn't sign the blocks of the master anymore in this round.
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[1]),
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert_eq!(broadcasted.lock().unwrap().len(), 2);
+
+        // the blocks of other master can be signed.
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[2]),
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert_eq!(broadcasted.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_observe_block_hash() {
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(NodeState::Member, rpc);
+        let block_hash = get_block(0).hash().unwrap();
+        assert!(node.observe_block_hash(1, block_hash).is_ok());
+        assert!(node.observe_block_hash(1, block_hash).is_ok());
+        match node.observe_block_hash(1, get_block(1).hash().unwrap()) {
+            Err(crate::errors::Error::MasterEquivocation(pubkey)) => {
+                assert_eq!(pubkey, node.params.pubkey_list[1]);
+            }
+            r => panic!("Should be MasterEquivocation, but {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_fixed_master() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+        node.params.fixed_master = Some(2);
+        node.start();
+        assert_eq!(node.master_index(), 2);
+        assert_eq!(node.current_state, NodeState::Member);
+
+        // timeout doesn't rotate master.
+        let now = Instant::now();
+        node.tick(now);
+        node.tick(now + Duration::from_secs(5));
+        assert_eq!(node.master_index(), 2);
+        assert_eq!(node.current_state, NodeState::Member);
+        node.tick(now + Duration::from_secs(10));
+        assert_eq!(node.master_index(), 2);
+    }
+
+    #[test]
+    fn test_fixed_master_ignores_other_candidate_block() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let broadcast_count = Arc::new(Mutex::new(0));
+        let count = broadcast_count.clone();
+        let closure: SpyMethod = Box::new(move |_message: Arc<Message>| {
+            *count.lock().unwrap() += 1;
+        });
+        let mut node = create_node_with_spy(initial_state, rpc, closure);
+        node.params.fixed_master = Some(2);
+        let pubkeys = node.params.pubkey_list.clone();
+
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[1]),
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert_eq!(*broadcast_count.lock().unwrap(), 0);
+
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[2]),
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert_eq!(*broadcast_count.lock().unwrap(), 1);
+        assert_eq!(node.master_index(), 2);
+    }
+
+    #[test]
+    fn test_refuse_oversized_candidate_block() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let broadcast_count = Arc::new(Mutex::new(0));
+        let count = broadcast_count.clone();
+        let closure: SpyMethod = Box::new(move |_message: Arc<Message>| {
+            *count.lock().unwrap() += 1;
+        });
+        let mut node = create_node_with_spy(initial_state, rpc, closure);
+        let size = get_block(0).payload().len() as u64;
+        node.params.policy.max_block_size = Some(size - 1);
+        let master = SignerID::new(node.params.pubkey_list[0]);
+
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: master,
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert_eq!(*broadcast_count.lock().unwrap(), 0);
+
+        node.params.policy.max_block_size = Some(size);
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: master,
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        assert_eq!(*broadcast_count.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_drop_candidate_block_while_round_in_flight() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let broadcast_count = Arc::new(Mutex::new(0));
+        let count = broadcast_count.clone();
+        let closure: SpyMethod = Box::new(move |_message: Arc<Message>| {
+            *count.lock().unwrap() += 1;
+        });
+        let mut node = create_node_with_spy(initial_state, rpc, closure);
+        node.params.max_concurrent_rounds = Some(1);
+        let pubkeys = node.params.pubkey_list.clone();
+        let candidate = |index: usize, height: u64| Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[index]),
+            height,
+            round: 0,
+        };
+
+        node.process_message(candidate(1, 0));
+        assert_eq!(*broadcast_count.lock().unwrap(), 1);
+
+        // other master starts round while the round of signer 1 is in flight.
+        node.process_message(candidate(2, 0));
+        assert_eq!(*broadcast_count.lock().unwrap(), 1);
+        assert_eq!(node.master_index(), 1);
+
+        // the master of the round in flight can propose again.
+        node.process_message(candidate(1, 0));
+        assert_eq!(*broadcast_count.lock().unwrap(), 2);
+
+        // next round starts after the round completed.
+        node.process_message(Message {
+            message_type: MessageType::Completedblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[1]),
+            height: node.round_height,
+            round: node.round_attempts,
+        });
+        node.process_message(candidate(2, 1));
+        assert_eq!(*broadcast_count.lock().unwrap(), 3);
+        assert_eq!(node.master_index(), 2);
+    }
+
+    #[test]
+    fn test_max_concurrent_rounds() {
+        let initial_state = NodeState::Member;
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let broadcast_count = Arc::new(Mutex::new(0));
+        let count = broadcast_count.clone();
+        let closure: SpyMethod = Box::new(move |_message: Arc<Message>| {
+            *count.lock().unwrap() += 1;
+        });
+        let mut node = create_node_with_spy(initial_state, rpc, closure);
+        node.params.max_concurrent_rounds = Some(2);
+        let pubkeys = node.params.pubkey_list.clone();
 
-        assert_ne!(params.pubkey_list[0], pubkey_list[0]);
-        assert_eq!(params.pubkey_list[1], pubkey_list[4]);
+        for pubkey in &pubkeys[1..=3] {
+            node.process_message(Message {
+                message_type: MessageType::Candidateblock(get_block(0)),
+                sender_id: SignerID::new(*pubkey),
+                height: node.round_height,
+                round: node.round_attempts,
+            });
+        }
+        assert_eq!(*broadcast_count.lock().unwrap(), 2);
+        assert_eq!(node.rounds_in_flight.len(), 2);
     }
 
     #[test]
-    fn test_candidate_process() {
-        let (broadcast_s, broadcast_r): (Sender<Arc<Message>>, Receiver<Arc<Message>>) = channel();
-        let assertion = Box::new(move |message: Arc<Message>| {
-            broadcast_s.send(message).unwrap();
-        });
-        let arc_block = safety(get_block(0));
-        let (_node, stop_signal, broadcaster) = setup_node(assertion, arc_block);
-        let message_str = r#"{"message_type": {"Candidateblock": [0, 0, 0, 32, 237, 101, 140, 196, 6, 112, 204, 237, 162, 59, 176, 182, 20, 130, 31, 230, 212, 138, 65, 209, 7, 209, 159, 63, 58, 86, 8, 173, 61, 72, 48, 146, 177, 81, 22, 10, 183, 17, 51, 180, 40, 225, 246, 46, 174, 181, 152, 174, 133, 143, 246, 96, 23, 201, 150, 1, 242, 144, 136, 183, 198, 74, 72, 29, 98, 132, 225, 69, 210, 155, 112, 191, 84, 57, 45, 41, 112, 16, 49, 210, 175, 159, 237, 95, 155, 178, 31, 187, 40, 79, 167, 28, 235, 35, 143, 105, 166, 212, 9, 93, 0, 1, 2, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 12, 0, 0, 0, 3, 92, 1, 1, 255, 255, 255, 255, 2, 0, 242, 5, 42, 1, 0, 0, 0, 25, 118, 169, 20, 207, 18, 219, 192, 75, 176, 222, 111, 182, 168, 122, 90, 235, 75, 46, 116, 201, 112, 6, 178, 136, 172, 0, 0, 0, 0, 0, 0, 0, 0, 38, 106, 36, 170, 33, 169, 237, 226, 246, 28, 63, 113, 209, 222, 253, 63, 169, 153, 223, 163, 105, 83, 117, 92, 105, 6, 137, 121, 153, 98, 180, 139, 235, 216, 54, 151, 78, 140, 249, 1, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] },"sender_id": [3, 131, 26, 105, 184, 0, 152, 51, 171, 91, 3, 38, 1, 46, 175, 72, 155, 254, 163, 90, 115, 33, 177, 202, 21, 177, 29, 136, 19, 20, 35, 250, 252]}"#;
-        let message = serde_json::from_str::<Message>(message_str).unwrap();
+    fn test_exclude_late_signer() {
+        let block_hash = get_block(0).hash().unwrap();
+        let sign_by = |node: &mut TestNode<MockRpc>, i: usize| {
+            let sender_id = SignerID::new(TestKeys::new().pubkeys()[i]);
+            let sig = sign(&TestKeys::new().key[i], &block_hash);
+            node.current_state = node.process_signature(&sender_id, &Signature(sig));
+        };
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(get_initial_master_state(), rpc);
+        node.params.collection_quorum = 5;
+        node.params.max_missed_rounds = 2;
+        let late = super::sender_index(
+            &SignerID::new(TestKeys::new().pubkeys()[4]),
+            &node.params.pubkey_list,
+        )
+        .unwrap();
 
-        broadcaster.send(message).unwrap();
-        let broadcast_message = broadcast_r.recv().unwrap();
-        let actual = format!("{:?}", &broadcast_message.message_type);
-        assert!(actual.starts_with("Signature(Signature"));
-        stop_signal.send(1).unwrap(); // this line not necessary, but for manners.
+        // signer 4 doesn't sign in 2 rounds, which reach the round time limit.
+        for _ in 0..2 {
+            node.current_state = get_initial_master_state();
+            for i in 1..4 {
+                sign_by(&mut node, i);
+            }
+            assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 0);
+            assert!(node.excluded_signers.is_empty());
+            node.current_state = node.process_round_timeout();
+            node.metrics.rounds_completed.store(0, Ordering::Relaxed);
+        }
+        assert!(node.excluded_signers.contains(&late));
+        assert_eq!(node.collection_quorum(), 4);
+
+        // master doesn't wait for signer 4.
+        node.current_state = get_initial_master_state();
+        for i in 1..4 {
+            sign_by(&mut node, i);
+        }
+        assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 1);
     }
 
     #[test]
-    fn test_candidate_process_invalid_block() {
-        let (broadcast_s, broadcast_r): (Sender<Arc<Message>>, Receiver<Arc<Message>>) = channel();
-        let spy = Box::new(move |message: Arc<Message>| {
-            broadcast_s.send(message).unwrap();
-        });
-        let arc_block = safety_error("invalid block!".to_string());
-        let (_node, stop_signal, bloadcaster) = setup_node(spy, arc_block);
-        let message_str = r#"{"message_type": {"Candidateblock": [0, 0, 0, 32, 237, 101, 140, 196, 6, 112, 204, 237, 162, 59, 176, 182, 20, 130, 31, 230, 212, 138, 65, 209, 7, 209, 159, 63, 58, 86, 8, 173, 61, 72, 48, 146, 177, 81, 22, 10, 183, 17, 51, 180, 40, 225, 246, 46, 174, 181, 152, 174, 133, 143, 246, 96, 23, 201, 150, 1, 242, 144, 136, 183, 198, 74, 72, 29, 98, 132, 225, 69, 210, 155, 112, 191, 84, 57, 45, 41, 112, 16, 49, 210, 175, 159, 237, 95, 155, 178, 31, 187, 40, 79, 167, 28, 235, 35, 143, 105, 166, 212, 9, 93, 0, 1, 2, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 12, 0, 0, 0, 3, 92, 1, 1, 255, 255, 255, 255, 2, 0, 242, 5, 42, 1, 0, 0, 0, 25, 118, 169, 20, 207, 18, 219, 192, 75, 176, 222, 111, 182, 168, 122, 90, 235, 75, 46, 116, 201, 112, 6, 178, 136, 172, 0, 0, 0, 0, 0, 0, 0, 0, 38, 106, 36, 170, 33, 169, 237, 226, 246, 28, 63, 113, 209, 222, 253, 63, 169, 153, 223, 163, 105, 83, 117, 92, 105, 6, 137, 121, 153, 98, 180, 139, 235, 216, 54, 151, 78, 140, 249, 1, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] },"sender_id": [3, 131, 26, 105, 184, 0, 152, 51, 171, 91, 3, 38, 1, 46, 175, 72, 155, 254, 163, 90, 115, 33, 177, 202, 21, 177, 29, 136, 19, 20, 35, 250, 252]}"#;
-        let message = serde_json::from_str::<Message>(message_str).unwrap();
+    fn test_exclusion_keeps_threshold() {
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(get_initial_master_state(), rpc);
+        node.params.collection_quorum = 5;
+        node.params.max_missed_rounds = 1;
 
-        bloadcaster.send(message).unwrap();
-        match broadcast_r.recv_timeout(Duration::from_millis(500)) {
-            Ok(m) => panic!("Should not broadcast Signature message: {:?}", m),
-            Err(_e) => assert!(true),
+        // 5 signers and threshold is 3, so only 2 signers can be excluded.
+        node.record_missing_signers(&[]);
+        assert_eq!(node.excluded_signers.len(), 2);
+        assert_eq!(node.collection_quorum(), 3);
+    }
+
+    #[test]
+    fn test_include_signing_signer() {
+        let block_hash = get_block(0).hash().unwrap();
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(get_initial_master_state(), rpc);
+        node.params.collection_quorum = 5;
+        node.params.max_missed_rounds = 1;
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[4]);
+        let index = super::sender_index(&sender_id, &node.params.pubkey_list).unwrap();
+        let participants: Vec<usize> = (0..5).filter(|i| *i != index).collect();
+        node.record_missing_signers(&participants);
+        assert!(node.excluded_signers.contains(&index));
+
+        let sig = sign(&TestKeys::new().key[4], &block_hash);
+        node.current_state = node.process_signature(&sender_id, &Signature(sig));
+        assert!(node.excluded_signers.is_empty());
+        assert_eq!(node.missed_rounds[index], 0);
+        assert_eq!(node.collection_quorum(), 5);
+    }
+
+    #[test]
+    fn test_hash_based_election() {
+        // public keys in signer index order.
+        let mut pubkeys = TestKeys::new().pubkeys();
+        canonical_order(&mut pubkeys);
+        // MockRpc returns height 0, so the rounds are of height 1.
+        let masters: Vec<usize> = (0..10)
+            .map(|round| HashBased.master_index((1 << 32) | round, &pubkeys))
+            .collect();
+
+        // each node elects the same masters, regardless of --master flag.
+        for &master_flag in &[true, false] {
+            let rpc = MockRpc {
+                return_block: safety(get_block(0)),
+            };
+            let mut node = create_node(NodeState::Joining, rpc);
+            assert_eq!(node.params.pubkey_list, pubkeys);
+            node.params.master_flag = master_flag;
+            node.params.election = ElectionStrategy::HashBased;
+            let state = node.start();
+            assert_eq!(node.master_index(), masters[0]);
+            assert_eq!(
+                matches!(state, NodeState::Master { .. }),
+                masters[0] == node.params.self_node_index
+            );
+            for master in &masters[1..] {
+                node.process_round_timeout();
+                assert_eq!(node.master_index(), *master);
+            }
         }
-        stop_signal.send(1).unwrap(); // this line not necessary, but for manners.
     }
 
     #[test]
-    fn test_modify_master_index() {
-        let initial_state = NodeState::Member;
-        let arc_block = safety(get_block(0));
+    fn test_hash_based_election_after_join() {
+        let mut pubkeys = TestKeys::new().pubkeys();
+        canonical_order(&mut pubkeys);
+        let master_of = |round: u64| HashBased.master_index((1 << 32) | round, &pubkeys);
         let rpc = MockRpc {
-            return_block: arc_block.clone(),
+            return_block: safety(get_block(0)),
         };
-        let mut node = create_node(initial_state, rpc);
+        let mut node = create_node(NodeState::Joining, rpc);
+        node.params.election = ElectionStrategy::HashBased;
+        // the other signers already timed out some rounds of height 1, and the master of the
+        // current round is another signer.
+        let round = (3..)
+            .find(|&round| master_of(round) != node.params.self_node_index)
+            .unwrap();
 
-        // pubkeys sorted index map;
-        // 0 -> 4
-        // 1 -> 0
-        // 2 -> 3
-        // 3 -> 2
-        // 4 -> 1
-        let sender_id = SignerID::new(TestKeys::new().pubkeys()[1]);
-        assert_eq!(node.master_index, 0); // in begin, master_index is 0.
-        let _next_state = node.process_candidateblock(&sender_id, &get_block(0));
-        assert_eq!(node.master_index, 0);
+        node.start();
+        assert_eq!(node.master_index(), master_of(0));
 
-        let sender_id = SignerID::new(TestKeys::new().pubkeys()[0]);
-        let _next_state = node.process_candidateblock(&sender_id, &get_block(0));
-        assert_eq!(node.master_index, 4);
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: SignerID::new(pubkeys[master_of(round)]),
+            height: 1,
+            round: round as u32,
+        });
+        assert_eq!(node.round_attempts, round as u32);
+        assert_eq!(node.master_index(), master_of(round));
 
-        node.round_timer.stop();
+        // the node elects the same master as the others on timeout.
+        let now = Instant::now();
+        node.tick(now);
+        node.tick(now + Duration::from_secs(5));
+        assert_eq!(node.round_attempts, round as u32 + 1);
+        assert_eq!(node.master_index(), master_of(round + 1));
     }
 
     #[test]
-    fn test_timeout_roundrobin() {
-        let closure: SpyMethod = Box::new(move |_message: Arc<Message>| {});
+    fn test_timeout_jitter() {
         let initial_state = NodeState::Member;
         let arc_block = safety(get_block(0));
         let rpc = MockRpc {
             return_block: arc_block.clone(),
         };
-        let (mut node, _broadcaster) =
-            create_node_with_closure_and_publish_count(initial_state, rpc, closure, 0);
-
-        let (stop_signal, stop_handler): (Sender<u32>, Receiver<u32>) = channel();
-        node.stop_handler(stop_handler);
-        node.params.master_flag = false;
+        let mut node = create_node(initial_state, rpc);
+        node.params.timeout_jitter = Duration::from_millis(1000);
 
-        assert_eq!(node.master_index, 0 as usize);
-        let ss = stop_signal.clone();
-        thread::spawn(move || {
-            thread::sleep(Duration::from_secs(6));
-            ss.send(1).unwrap();
-        });
-        node.start();
+        let limit = Duration::from_secs(5);
+        for _ in 0..100 {
+            let timelimit = node.round_timelimit();
+            assert!(limit <= timelimit && timelimit <= limit + Duration::from_millis(1000));
+        }
 
-        assert_eq!(node.master_index, 1 as usize);
+        // jitter doesn't change the next master.
+        let now = Instant::now();
+        node.tick(now);
+        node.tick(now + Duration::from_secs(6));
+        assert_eq!(node.master_index(), 1);
     }
 
     /// 3 of 5 multisig
+    #[test]
+    fn test_collection_quorum() {
+        let block_hash = get_block(0).hash().unwrap();
+        let sign_by = |node: &mut TestNode<MockRpc>, i: usize| {
+            let sender_id = SignerID::new(TestKeys::new().pubkeys()[i]);
+            let sig = sign(&TestKeys::new().key[i], &block_hash);
+            node.current_state = node.process_signature(&sender_id, &Signature(sig));
+        };
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(get_initial_master_state(), rpc);
+        node.params.collection_quorum = 4;
+
+        // master waits for the quorum, though threshold is met.
+        sign_by(&mut node, 1);
+        sign_by(&mut node, 2);
+        match node.current_state {
+            NodeState::Master {
+                ref signature_map, ..
+            } => assert_eq!(signature_map.len(), 3),
+            ref state => panic!("Should be Master node, but: {:?}", state),
+        }
+        assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 0);
+        sign_by(&mut node, 3);
+        assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 1);
+
+        // the round time limit is reached with threshold signatures.
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(get_initial_master_state(), rpc);
+        node.params.collection_quorum = 4;
+        sign_by(&mut node, 1);
+        sign_by(&mut node, 2);
+        let now = Instant::now();
+        node.tick(now);
+        node.tick(now + Duration::from_secs(5));
+        assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 1);
+        assert_eq!(node.metrics.rounds_failed.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_late_signature_grace() {
+        let block_hash = get_block(0).hash().unwrap();
+        let sign_by = |node: &mut TestNode<MockRpc>, i: usize| {
+            let sender_id = SignerID::new(TestKeys::new().pubkeys()[i]);
+            let sig = sign(&TestKeys::new().key[i], &block_hash);
+            node.current_state = node.process_signature(&sender_id, &Signature(sig));
+        };
+        let participation = |node: &TestNode<MockRpc>, i: usize| {
+            let index = super::sender_index(
+                &SignerID::new(TestKeys::new().pubkeys()[i]),
+                &node.params.pubkey_list,
+            )
+            .unwrap();
+            node.metrics.participation[index].load(Ordering::Relaxed)
+        };
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(get_initial_master_state(), rpc);
+        node.params.late_signature_grace = Duration::from_millis(1000);
+        sign_by(&mut node, 1);
+        sign_by(&mut node, 2);
+        assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 1);
+        let state = node.current_state.clone();
+
+        // the signature after the block was produced is counted, but the round is not changed.
+        sign_by(&mut node, 3);
+        assert_eq!(participation(&node, 3), 1);
+        assert_eq!(node.current_state, state);
+        assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 1);
+        // each signer is counted once.
+        sign_by(&mut node, 3);
+        sign_by(&mut node, 1);
+        assert_eq!(participation(&node, 3), 1);
+        assert_eq!(participation(&node, 1), 1);
+
+        // late signature is ignored without the grace period.
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(get_initial_master_state(), rpc);
+        sign_by(&mut node, 1);
+        sign_by(&mut node, 2);
+        sign_by(&mut node, 3);
+        assert_eq!(participation(&node, 3), 0);
+    }
+
     /// Round owner will collect signatures.
     #[test]
     fn process_signature_test() {
@@ -783,6 +3539,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_signature_from_unknown_signer() {
+        let initial_state = get_initial_master_state();
+
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let mut node = create_node(initial_state, rpc);
+
+        // valid signature, but the key isn't in the federation.
+        let private_key = crate::sign::generate_private_key(bitcoin::Network::Regtest).unwrap();
+        let sender_id = SignerID::new(private_key.public_key(&secp256k1::Secp256k1::new()));
+        let block_hash = get_block(0).hash().unwrap();
+        let sig = sign(&private_key, &block_hash);
+        let next_state = node.process_signature(&sender_id, &Signature(sig));
+
+        match next_state {
+            NodeState::Master {
+                signature_map: ref sigs,
+                ..
+            } => assert_eq!(sigs.len(), 1),
+            ref state => panic!("Should be Master node, but: {:?}", state),
+        }
+        assert_eq!(
+            node.metrics.unknown_sender_shares.load(Ordering::Relaxed),
+            1
+        );
+        assert_eq!(node.metrics.to_json()["unknown_sender_shares"], 1);
+    }
+
+    #[test]
+    fn test_message_from_unknown_signer() {
+        let rpc = MockRpc {
+            return_block: safety(get_block(0)),
+        };
+        let mut node = create_node(NodeState::Member, rpc);
+        let private_key = crate::sign::generate_private_key(bitcoin::Network::Regtest).unwrap();
+        let sender_id = SignerID::new(private_key.public_key(&secp256k1::Secp256k1::new()));
+
+        for message_type in [
+            MessageType::Candidateblock(get_block(0)),
+            MessageType::Completedblock(get_block(0)),
+        ] {
+            let message = Message {
+                message_type,
+                sender_id,
+                height: node.round_height,
+                round: node.round_attempts,
+            };
+            let output = node.node.process_message(message, Instant::now());
+            assert!(output.messages.is_empty());
+            assert!(output.block_to_test.is_none());
+            assert_eq!(node.current_state, NodeState::Member);
+        }
+    }
+
     #[test]
     fn test_ignore_duplicate_signature() {
         let initial_state = get_initial_master_state();
@@ -888,7 +3701,61 @@ mod tests {
         }
     }
 
-    mod test_for_waiting_ibd_finish {
+    #[test]
+    fn test_drop_replayed_message_of_past_round() {
+        let arc_block = safety(get_block(0));
+        let rpc = MockRpc {
+            return_block: arc_block.clone(),
+        };
+        let broadcast_count = Arc::new(Mutex::new(0));
+        let count = broadcast_count.clone();
+        let closure: SpyMethod = Box::new(move |_message: Arc<Message>| {
+            *count.lock().unwrap() += 1;
+        });
+        let mut node = create_node_with_spy(NodeState::Member, rpc, closure);
+        let master = SignerID::new(node.params.pubkey_list[1]);
+        let completed = Message {
+            message_type: MessageType::Completedblock(get_block(0)),
+            sender_id: master,
+            height: 0,
+            round: 0,
+        };
+        let replayed: Message =
+            serde_json::from_str(&serde_json::to_string(&completed).unwrap()).unwrap();
+
+        node.master_index = 1;
+        node.process_message(completed);
+        assert_eq!(node.master_index(), 2);
+        assert_eq!((node.round_height, node.round_attempts), (1, 0));
+
+        // signer 1 is master again, and its completed block of the past round is replayed.
+        node.master_index = 1;
+        node.process_message(replayed);
+        assert_eq!(node.master_index(), 1);
+        assert_eq!((node.round_height, node.round_attempts), (1, 0));
+
+        // candidate block of the timed out round isn't signed.
+        node.current_state = node.process_round_timeout();
+        node.master_index = 1;
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: master,
+            height: 1,
+            round: 0,
+        });
+        assert_eq!(*broadcast_count.lock().unwrap(), 0);
+
+        // candidate block of the current round is signed.
+        node.process_message(Message {
+            message_type: MessageType::Candidateblock(get_block(0)),
+            sender_id: master,
+            height: 1,
+            round: 1,
+        });
+        assert_eq!(*broadcast_count.lock().unwrap(), 1);
+    }
+
+    mod test_for_polling_candidate_block {
         use crate::blockdata::Block;
         use crate::errors::Error;
         use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
@@ -897,15 +3764,17 @@ mod tests {
         use bitcoin::Address;
         use secp256k1::Signature;
         use std::cell::Cell;
+        use std::time::{Duration, Instant};
 
+        /// Tapyrus Core which always fails to create candidate block.
         struct MockRpc {
-            pub results: [GetBlockchainInfoResult; 2],
             pub call_count: Cell<usize>,
         }
 
         impl TapyrusApi for MockRpc {
             fn getnewblock(&self, _address: &Address) -> Result<Block, Error> {
-                unimplemented!()
+                self.call_count.set(self.call_count.get() + 1);
+                Err(Error::InvalidArgs("can't create block".to_string()))
             }
             fn testproposedblock(&self, _block: &Block) -> Result<(), Error> {
                 unimplemented!()
@@ -920,35 +3789,315 @@ mod tests {
             fn submitblock(&self, _block: &Block) -> Result<(), Error> {
                 unimplemented!()
             }
-
             fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error> {
-                let result = self.results[self.call_count.get()].clone();
+                Err(Error::RpcTimeout)
+            }
+        }
 
-                self.call_count.set(self.call_count.get() + 1);
+        #[test]
+        fn test_poll_interval() {
+            let rpc = MockRpc {
+                call_count: Cell::new(0),
+            };
+            let mut node = create_node(NodeState::Joining, rpc);
+            node.params.poll_interval = Duration::from_millis(1000);
+
+            // master requests candidate block at start.
+            assert_eq!(node.start(), NodeState::Member);
+            assert_eq!(node.rpc.call_count.get(), 1);
 
-                Ok(result)
+            // tick every 100ms for 3.5 sec. round time limit is 5 sec.
+            let now = Instant::now();
+            for i in 0..=35 {
+                node.tick(now + Duration::from_millis(100) * i);
             }
+            assert_eq!(node.rpc.call_count.get(), 4);
         }
 
         #[test]
-        fn test_wait_for_ibd_finish() {
-            let json = serde_json::from_str("{\"chain\": \"test\", \"blocks\": 26826, \"headers\": 26826, \"bestblockhash\": \"7303687fb5d80781bd9fece466e76d97a94613d409d127030ff7f34081a899f7\", \"mediantime\": 1568103315, \"verificationprogress\": 1, \"initialblockdownload\": false, \"size_on_disk\": 11669126,  \"pruned\": false,  \"bip9_softforks\": {    \"csv\": {      \"status\": \"failed\",      \"startTime\": 1456790400, \"timeout\": 1493596800, \"since\": 2016 }, \"segwit\": { \"status\": \"failed\", \"startTime\": 1462060800, \"timeout\": 1493596800, \"since\": 2016 }},  \"warnings\": \"\"}").unwrap();
-            let mut result1 = serde_json::from_value::<GetBlockchainInfoResult>(json).unwrap();
-            result1.initialblockdownload = true;
-            let mut result2 = result1.clone();
-            result2.initialblockdownload = false;
-
+        fn test_block_notification() {
             let rpc = MockRpc {
-                results: [result1, result2],
                 call_count: Cell::new(0),
             };
+            let mut node = create_node(NodeState::Joining, rpc);
+            node.params.poll_interval = Duration::from_millis(1000);
+
+            assert_eq!(node.start(), NodeState::Member);
+            let now = Instant::now();
+            node.tick(now);
+            assert_eq!(node.rpc.call_count.get(), 1);
+
+            // new block triggers the request before the next poll.
+            node.block_notified("hash1", now + Duration::from_millis(100));
+            assert_eq!(node.rpc.call_count.get(), 2);
+
+            // duplicated notifications are ignored.
+            node.block_notified("hash1", now + Duration::from_millis(200));
+            node.block_notified("hash1", now + Duration::from_millis(200));
+            assert_eq!(node.rpc.call_count.get(), 2);
+
+            // polling continues from the last request.
+            node.tick(now + Duration::from_millis(1100));
+            assert_eq!(node.rpc.call_count.get(), 3);
+        }
+    }
+
+    mod test_for_reorg {
+        use crate::blockdata::Block;
+        use crate::errors::Error;
+        use crate::net::{Signature, SignerID};
+        use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
+        use crate::sign::sign;
+        use crate::signer_node::tests::create_node;
+        use crate::signer_node::NodeState;
+        use crate::test_helper::{get_block, TestKeys};
+        use bitcoin::Address;
+        use std::cell::RefCell;
+        use std::sync::atomic::Ordering;
+        use std::time::{Duration, Instant};
+
+        /// Tapyrus Core whose best block can be changed. It must not be asked to combine or
+        /// submit the stale block.
+        struct MockRpc {
+            pub tip: RefCell<String>,
+        }
+
+        impl MockRpc {
+            fn new() -> MockRpc {
+                MockRpc {
+                    tip: RefCell::new("tip0".to_string()),
+                }
+            }
+        }
+
+        impl TapyrusApi for MockRpc {
+            fn getnewblock(&self, _address: &Address) -> Result<Block, Error> {
+                Ok(get_block(0))
+            }
+            fn testproposedblock(&self, _block: &Block) -> Result<(), Error> {
+                Ok(())
+            }
+            fn combineblocksigs(
+                &self,
+                _block: &Block,
+                _signatures: &Vec<secp256k1::Signature>,
+            ) -> Result<Block, Error> {
+                panic!("stale block is combined.")
+            }
+            fn submitblock(&self, _block: &Block) -> Result<(), Error> {
+                panic!("stale block is submitted.")
+            }
+            fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error> {
+                Ok(GetBlockchainInfoResult {
+                    chain: "regtest".to_string(),
+                    blocks: 0,
+                    headers: 0,
+                    bestblockhash: self.tip.borrow().clone(),
+                    mediantime: 0,
+                    initialblockdownload: false,
+                })
+            }
+        }
+
+        #[test]
+        fn test_abort_round_when_tip_changed_before_submit() {
+            let mut node = create_node(NodeState::Member, MockRpc::new());
+            node.master_index = node.params.self_node_index;
+            node.current_state = node.propose_candidate_block();
+            assert_eq!(node.round_tip, Some("tip0".to_string()));
+
+            *node.rpc.tip.borrow_mut() = "tip1".to_string();
+            let block_hash = get_block(0).hash().unwrap();
+            for i in 1..3 {
+                let sender_id = SignerID::new(TestKeys::new().pubkeys()[i]);
+                let sig = sign(&TestKeys::new().key[i], &block_hash);
+                node.current_state = node.process_signature(&sender_id, &Signature(sig));
+            }
+            assert_eq!(node.current_state, NodeState::Member);
+            assert_eq!(node.round_tip, None);
+            assert_eq!(node.metrics.rounds_failed.load(Ordering::Relaxed), 1);
+            assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 0);
+        }
+
+        #[test]
+        fn test_abort_round_when_tip_changed_during_collection() {
+            let mut node = create_node(NodeState::Member, MockRpc::new());
+            node.master_index = node.params.self_node_index;
+            node.params.poll_interval = Duration::from_millis(1000);
+            node.current_state = node.propose_candidate_block();
+            let now = Instant::now();
+
+            // the tip doesn't change.
+            node.tick(now);
+            node.tick(now + Duration::from_millis(1000));
+            assert!(matches!(node.current_state, NodeState::Master { .. }));
+
+            *node.rpc.tip.borrow_mut() = "tip1".to_string();
+            node.tick(now + Duration::from_millis(1500));
+            assert!(matches!(node.current_state, NodeState::Master { .. }));
+            node.tick(now + Duration::from_millis(2000));
+            assert_eq!(node.current_state, NodeState::Member);
+            assert_eq!(node.metrics.rounds_failed.load(Ordering::Relaxed), 1);
+        }
+
+        #[test]
+        fn test_start_next_round_on_block_notification() {
+            let mut node = create_node(NodeState::Member, MockRpc::new());
+            node.master_index = node.params.self_node_index;
+            node.params.poll_interval = Duration::from_millis(1000);
+            node.current_state = node.propose_candidate_block();
+            let now = Instant::now();
+            node.tick(now);
+
+            // notification of the block which the candidate block is built on.
+            node.block_notified("tip0", now + Duration::from_millis(100));
+            assert!(matches!(node.current_state, NodeState::Master { .. }));
+
+            *node.rpc.tip.borrow_mut() = "tip1".to_string();
+            node.block_notified("tip1", now + Duration::from_millis(200));
+            assert_eq!(node.current_state, NodeState::Member);
+            assert_eq!(node.round_attempts, 1);
+            assert_eq!(node.metrics.rounds_failed.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    mod test_for_submit_confirmation {
+        use crate::blockdata::Block;
+        use crate::errors::Error;
+        use crate::net::{Signature, SignerID};
+        use crate::rpc::{GetBlockchainInfoResult, TapyrusApi};
+        use crate::sign::sign;
+        use crate::signer_node::tests::{create_node, get_initial_master_state, TestNode};
+        use crate::signer_node::NodeState;
+        use crate::test_helper::{get_block, TestKeys};
+        use bitcoin::Address;
+        use std::cell::Cell;
+        use std::sync::atomic::Ordering;
+        use std::time::{Duration, Instant};
+
+        /// Tapyrus Core which makes the submitted block the best block after `confirm_after`
+        /// polls. It never confirms the block if `confirm_after` is `None`.
+        struct MockRpc {
+            pub submitted: Cell<bool>,
+            pub polls: Cell<usize>,
+            pub confirm_after: Option<usize>,
+        }
+
+        impl MockRpc {
+            fn new(confirm_after: Option<usize>) -> MockRpc {
+                MockRpc {
+                    submitted: Cell::new(false),
+                    polls: Cell::new(0),
+                    confirm_after,
+                }
+            }
+        }
+
+        impl TapyrusApi for MockRpc {
+            fn getnewblock(&self, _address: &Address) -> Result<Block, Error> {
+                Ok(get_block(0))
+            }
+            fn testproposedblock(&self, _block: &Block) -> Result<(), Error> {
+                Ok(())
+            }
+            fn combineblocksigs(
+                &self,
+                block: &Block,
+                _signatures: &Vec<secp256k1::Signature>,
+            ) -> Result<Block, Error> {
+                Ok(block.clone())
+            }
+            fn submitblock(&self, _block: &Block) -> Result<(), Error> {
+                self.submitted.set(true);
+                Ok(())
+            }
+            fn getblockchaininfo(&self) -> Result<GetBlockchainInfoResult, Error> {
+                let mut info = GetBlockchainInfoResult {
+                    chain: "regtest".to_string(),
+                    blocks: 0,
+                    headers: 0,
+                    bestblockhash: "xxx".to_string(),
+                    mediantime: 0,
+                    initialblockdownload: false,
+                };
+                if self.submitted.get() {
+                    self.polls.set(self.polls.get() + 1);
+                    match self.confirm_after {
+                        Some(n) if self.polls.get() > n => {
+                            info.blocks = 1;
+                            info.bestblockhash = get_block(0).hash().unwrap().to_rpc_hex();
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(info)
+            }
+        }
+
+        fn complete_round(node: &mut TestNode<MockRpc>) {
+            let block_hash = get_block(0).hash().unwrap();
+            for i in 1..3 {
+                let sender_id = SignerID::new(TestKeys::new().pubkeys()[i]);
+                let sig = sign(&TestKeys::new().key[i], &block_hash);
+                node.current_state = node.process_signature(&sender_id, &Signature(sig));
+            }
+        }
+
+        #[test]
+        fn test_confirm_submission() {
+            let mut node = create_node(get_initial_master_state(), MockRpc::new(Some(1)));
+            node.params.submit_confirmation_timeout = Duration::from_millis(1000);
+            node.params.poll_interval = Duration::from_millis(100);
+            complete_round(&mut node);
+
+            // the round is completed without waiting for the confirmation.
+            assert_eq!(node.current_state, NodeState::Member);
+            assert!(node.rpc.submitted.get());
+            assert_eq!(node.rpc.polls.get(), 0);
+            assert_eq!(node.metrics.rounds_completed.load(Ordering::Relaxed), 1);
 
-            let node = create_node(NodeState::Member, rpc);
+            // not confirmed at the first poll, and confirmed at the second one.
+            let now = Instant::now();
+            node.tick(now);
+            assert_eq!(node.rpc.polls.get(), 1);
+            node.tick(now + Duration::from_millis(50));
+            assert_eq!(node.rpc.polls.get(), 1);
+            node.tick(now + Duration::from_millis(100));
+            assert_eq!(node.rpc.polls.get(), 2);
+            assert!(node.submitted_block.is_none());
 
-            node.wait_for_ibd_finish(std::time::Duration::from_millis(1));
+            node.tick(now + Duration::from_millis(200));
+            assert_eq!(node.rpc.polls.get(), 2);
+        }
+
+        #[test]
+        fn test_confirmation_timeout() {
+            let mut node = create_node(get_initial_master_state(), MockRpc::new(None));
+            node.params.submit_confirmation_timeout = Duration::from_millis(300);
+            node.params.poll_interval = Duration::from_millis(100);
+            complete_round(&mut node);
+            assert_eq!(node.current_state, NodeState::Member);
+
+            let now = Instant::now();
+            for i in 0..3 {
+                node.tick(now + Duration::from_millis(100 * i));
+                assert!(node.submitted_block.is_some());
+            }
+            // gives up at the deadline.
+            node.tick(now + Duration::from_millis(300));
+            assert_eq!(node.rpc.polls.get(), 4);
+            assert!(node.submitted_block.is_none());
+        }
+
+        #[test]
+        fn test_no_confirmation_by_default() {
+            let mut node = create_node(get_initial_master_state(), MockRpc::new(Some(0)));
+            complete_round(&mut node);
+            node.tick(Instant::now());
 
-            let rpc = node.params.rpc.clone();
-            assert_eq!(rpc.call_count.get(), 2);
+            assert_eq!(node.current_state, NodeState::Member);
+            assert_eq!(node.rpc.polls.get(), 0);
+            assert!(node.submitted_block.is_none());
         }
     }
 }
@@ -0,0 +1,191 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Exporter which pushes the metrics to StatsD, for environments which don't scrape Prometheus.
+//!
+//! Counters of `Metrics` are cumulative, so each push sends the increase since the previous
+//! push as StatsD counters. Histograms are sent as the counters of their count and sum(msec).
+
+use crate::errors::Error;
+use crate::metrics::{Histogram, Metrics};
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Default interval(msec) of pushing the metrics to StatsD.
+pub const STATSD_INTERVAL_DEFAULT_MILLIS: u64 = 10000;
+
+/// Prefix of the metric names.
+const PREFIX: &str = "tapyrus_signer";
+
+pub struct StatsdExporter {
+    socket: UdpSocket,
+    addr: SocketAddr,
+    metrics: Arc<Metrics>,
+    /// Values of the counters at the previous push.
+    last: HashMap<String, u64>,
+}
+
+impl StatsdExporter {
+    /// `addr` is `HOST:PORT` of the StatsD server.
+    pub fn new(addr: &str, metrics: Arc<Metrics>) -> Result<StatsdExporter, Error> {
+        let addr = addr
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| {
+                Error::InvalidArgs(format!("StatsD address must be HOST:PORT. addr: {}", addr))
+            })?;
+        let bind = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        Ok(StatsdExporter {
+            socket: UdpSocket::bind(bind)?,
+            addr,
+            metrics,
+            last: HashMap::new(),
+        })
+    }
+
+    /// Start the thread which pushes the metrics every `interval`.
+    pub fn spawn(mut self, interval: Duration) -> JoinHandle<()> {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            self.push();
+        })
+    }
+
+    /// Send the increase of the metrics in a packet. Failure to send is only logged, because
+    /// UDP endpoint may be unavailable for a while.
+    pub fn push(&mut self) {
+        let lines = self.lines();
+        if lines.is_empty() {
+            return;
+        }
+        if let Err(e) = self.socket.send_to(lines.join("\n").as_bytes(), self.addr) {
+            log::warn!("Failed to push metrics to StatsD {}: {:?}", self.addr, e);
+        }
+    }
+
+    /// Lines of the StatsD counters which increased since the previous call.
+    fn lines(&mut self) -> Vec<String> {
+        let metrics = self.metrics.clone();
+        let mut lines = Vec::new();
+        let mut counter = |name: String, value: u64| {
+            let last = self.last.insert(name.clone(), value).unwrap_or(0);
+            if value > last {
+                lines.push(format!("{}.{}:{}|c", PREFIX, name, value - last));
+            }
+        };
+        let counters = [
+            ("rounds_completed", &metrics.rounds_completed),
+            ("rounds_failed", &metrics.rounds_failed),
+            ("watchdog_resets", &metrics.watchdog_resets),
+            ("unknown_sender_shares", &metrics.unknown_sender_shares),
+        ];
+        for (name, value) in counters.iter() {
+            counter(name.to_string(), value.load(Ordering::Relaxed));
+        }
+        for (index, value) in metrics.participation.iter().enumerate() {
+            counter(
+                format!("participation.{}", index),
+                value.load(Ordering::Relaxed),
+            );
+        }
+        let histograms: [(&str, &Histogram); 4] = [
+            ("round_latency_milliseconds", &metrics.round_latency),
+            ("signature_wait_milliseconds", &metrics.signature_wait),
+            ("block_fetch_milliseconds", &metrics.block_fetch),
+            ("block_validation_milliseconds", &metrics.block_validation),
+        ];
+        for (name, histogram) in histograms.iter() {
+            let snapshot = histogram.snapshot();
+            counter(format!("{}.count", name), snapshot.count);
+            counter(format!("{}.sum", name), snapshot.sum);
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receive(receiver: &UdpSocket) -> Vec<String> {
+        let mut buf = [0u8; 4096];
+        let len = receiver.recv(&mut buf).unwrap();
+        String::from_utf8(buf[..len].to_vec())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_push() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let metrics = Arc::new(Metrics::new(Duration::from_secs(5), 3));
+        let addr = receiver.local_addr().unwrap().to_string();
+        let mut exporter = StatsdExporter::new(&addr, metrics.clone()).unwrap();
+
+        metrics.rounds_completed.fetch_add(2, Ordering::Relaxed);
+        metrics.record_signature(1);
+        metrics.round_latency.observe(Duration::from_millis(30));
+        exporter.push();
+        assert_eq!(
+            receive(&receiver),
+            vec![
+                "tapyrus_signer.rounds_completed:2|c",
+                "tapyrus_signer.participation.1:1|c",
+                "tapyrus_signer.round_latency_milliseconds.count:1|c",
+                "tapyrus_signer.round_latency_milliseconds.sum:30|c",
+            ]
+        );
+
+        // only the increase since the previous push is sent.
+        metrics.rounds_completed.fetch_add(1, Ordering::Relaxed);
+        metrics.rounds_failed.fetch_add(1, Ordering::Relaxed);
+        exporter.push();
+        assert_eq!(
+            receive(&receiver),
+            vec![
+                "tapyrus_signer.rounds_completed:1|c",
+                "tapyrus_signer.rounds_failed:1|c",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_endpoint() {
+        // nobody listens on the port.
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let metrics = Arc::new(Metrics::new(Duration::from_secs(5), 3));
+        let addr = format!("127.0.0.1:{}", port);
+        let mut exporter = StatsdExporter::new(&addr, metrics.clone()).unwrap();
+        metrics.rounds_completed.fetch_add(1, Ordering::Relaxed);
+        exporter.push();
+        exporter.push();
+    }
+
+    #[test]
+    fn test_invalid_address() {
+        let metrics = Arc::new(Metrics::new(Duration::from_secs(5), 3));
+        match StatsdExporter::new("localhost", metrics) {
+            Err(Error::InvalidArgs(_)) => {}
+            r => panic!("Should be InvalidArgs, but {:?}", r.map(|_| ())),
+        }
+    }
+}
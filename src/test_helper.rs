@@ -24,6 +24,8 @@ pub fn create_message() -> Message {
     Message {
         message_type: MessageType::Signature(net::Signature(sig)),
         sender_id: signer_id,
+        height: 1,
+        round: 0,
     }
 }
 
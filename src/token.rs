@@ -0,0 +1,172 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+use crate::errors::Error;
+use crate::signer_node::canonical_order;
+use bitcoin::util::base58;
+use bitcoin::PublicKey;
+use bitcoin_hashes::{sha256, Hash};
+use std::fmt;
+use std::str::FromStr;
+
+/// Version byte of the federation token.
+const TOKEN_VERSION: u8 = 0x01;
+/// Length of a compressed public key.
+const PUBKEY_LEN: usize = 33;
+
+/// Public keys and threshold of the federation encoded in one string, to configure other tools
+/// without copying each key. It is base58check of the version byte, threshold, count of public
+/// keys and the compressed public keys in the canonical order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FederationToken {
+    pub pubkey_list: Vec<PublicKey>,
+    pub threshold: u8,
+}
+
+impl FederationToken {
+    pub fn new(mut pubkey_list: Vec<PublicKey>, threshold: u8) -> Result<Self, Error> {
+        if threshold == 0 || pubkey_list.len() < threshold as usize || pubkey_list.len() > 255 {
+            return Err(Error::InvalidThreshold {
+                threshold,
+                signers: pubkey_list.len(),
+            });
+        }
+        if let Some(pk) = pubkey_list.iter().find(|pk| !pk.compressed) {
+            return Err(Error::InvalidPublicKeyFormat(pk.to_string()));
+        }
+        canonical_order(&mut pubkey_list);
+        Ok(FederationToken {
+            pubkey_list,
+            threshold,
+        })
+    }
+
+    /// Stable identifier of the federation, which is SHA256 of the token data. Federations of
+    /// the same public keys and threshold have the same id, in any order of the keys.
+    pub fn federation_id(&self) -> sha256::Hash {
+        sha256::Hash::hash(&self.data())
+    }
+
+    /// The version byte, threshold, count of public keys and the compressed public keys.
+    fn data(&self) -> Vec<u8> {
+        let mut data = vec![TOKEN_VERSION, self.threshold, self.pubkey_list.len() as u8];
+        for pk in &self.pubkey_list {
+            data.extend_from_slice(&pk.key.serialize());
+        }
+        data
+    }
+}
+
+impl fmt::Display for FederationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        base58::check_encode_slice_to_fmt(f, &self.data())
+    }
+}
+
+impl FromStr for FederationToken {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = base58::from_check(s.trim()).map_err(|e| Error::InvalidToken(e.to_string()))?;
+        if data.len() < 3 {
+            return Err(Error::InvalidToken("token is too short.".to_string()));
+        }
+        if data[0] != TOKEN_VERSION {
+            return Err(Error::InvalidToken(format!("unknown version: {}", data[0])));
+        }
+        let count = data[2] as usize;
+        if data.len() != 3 + count * PUBKEY_LEN {
+            return Err(Error::InvalidLength(3 + count * PUBKEY_LEN, data.len()));
+        }
+        let pubkey_list = data[3..]
+            .chunks(PUBKEY_LEN)
+            .map(|bytes| {
+                PublicKey::from_slice(bytes)
+                    .map_err(|_| Error::InvalidPublicKeyFormat(hex::encode(bytes)))
+            })
+            .collect::<Result<Vec<PublicKey>, Error>>()?;
+        FederationToken::new(pubkey_list, data[1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helper::TestKeys;
+
+    #[test]
+    fn test_round_trip() {
+        let token = FederationToken::new(TestKeys::new().pubkeys(), 3).unwrap();
+        let decoded = FederationToken::from_str(&token.to_string()).unwrap();
+        assert_eq!(decoded, token);
+        assert_eq!(decoded.threshold, 3);
+        assert_eq!(decoded.pubkey_list.len(), 5);
+    }
+
+    #[test]
+    fn test_corrupted_token() {
+        let encoded = FederationToken::new(TestKeys::new().pubkeys(), 3)
+            .unwrap()
+            .to_string();
+        // replace each character with another one of base58 alphabet.
+        for i in 0..encoded.len() {
+            let mut corrupted = encoded.clone().into_bytes();
+            corrupted[i] = if corrupted[i] == b'2' { b'3' } else { b'2' };
+            let corrupted = String::from_utf8(corrupted).unwrap();
+            assert!(
+                FederationToken::from_str(&corrupted).is_err(),
+                "corrupted token is accepted: {}",
+                corrupted
+            );
+        }
+    }
+
+    #[test]
+    fn test_federation_id() {
+        let mut pubkeys = TestKeys::new().pubkeys();
+        let id = FederationToken::new(pubkeys.clone(), 3)
+            .unwrap()
+            .federation_id();
+
+        // the id doesn't depend on the order of the keys.
+        pubkeys.reverse();
+        assert_eq!(
+            FederationToken::new(pubkeys.clone(), 3)
+                .unwrap()
+                .federation_id(),
+            id
+        );
+        pubkeys.swap(0, 2);
+        assert_eq!(
+            FederationToken::new(pubkeys.clone(), 3)
+                .unwrap()
+                .federation_id(),
+            id
+        );
+
+        assert_ne!(
+            FederationToken::new(pubkeys.clone(), 4)
+                .unwrap()
+                .federation_id(),
+            id
+        );
+        assert_ne!(
+            FederationToken::new(pubkeys[..4].to_vec(), 3)
+                .unwrap()
+                .federation_id(),
+            id
+        );
+    }
+
+    #[test]
+    fn test_invalid_threshold() {
+        match FederationToken::new(TestKeys::new().pubkeys(), 6) {
+            Err(Error::InvalidThreshold {
+                threshold: 6,
+                signers: 5,
+            }) => {}
+            r => panic!("Should be InvalidThreshold, but {:?}", r),
+        }
+    }
+}
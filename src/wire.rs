@@ -0,0 +1,259 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Wire format of the messages between signers.
+//!
+//! Each message published to the redis channel is a JSON object of the version and the body.
+//!
+//! ```text
+//! {"version":2,"body":<body>}
+//! ```
+//!
+//! Without the message authentication, `RedisManager` also puts `"timestamp":<msec since unix
+//! epoch>` of sending after the version, so that receivers can drop stale messages. Signed
+//! messages have the timestamp in the body instead, so that it is covered by the signature.
+//!
+//! Body is `net::SignedMessage`, or `net::Message` when the message authentication is disabled.
+//! Both are serialized by serde with these rules:
+//!
+//! * `Message` is `{"message_type":<message type>,"sender_id":<signer id>,"height":<height>,
+//!   "round":<round>}`. Height is of the block which the round produces, and round is the number
+//!   of the round for the height.
+//! * Message type is `{"Candidateblock":<block>}`, `{"Signature":<signature>}`,
+//!   `{"Completedblock":<block>}`, `"Roundfailure"`, `{"Roundannounce":<block>}` or
+//!   `{"Ready":{"signer_index":<signer index>,"block_hash":<block hash>}}` or
+//!   `{"Blockhash":{"master_index":<signer index>,"block_hash":<block hash>}}`.
+//! * Block is the array of the bytes of the block.
+//! * Block hash is the array of the 32 bytes of sha256d of the block header without proof.
+//! * Signer id is the array of the bytes of the compressed public key.
+//! * Signature is the array of the bytes of the DER encoded ECDSA signature.
+//! * `SignedMessage` is `{"message":<message>,"timestamp":<msec since unix epoch>,
+//!   "signature":<signature>}`. The signature is for sha256d of the serialized message followed
+//!   by the timestamp in 8 bytes big endian.
+//!
+//! Payload without the version is the format before the envelope, which is version 0.
+//! Decoders reject payloads of other versions, so all signers must be upgraded together.
+//!
+//! Increment `WIRE_VERSION` when this format changes.
+
+use crate::errors::Error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Version of the wire format.
+pub const WIRE_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct Envelope<'a, T: Serialize> {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+    body: &'a T,
+}
+
+#[derive(Deserialize)]
+struct VersionOnly {
+    #[serde(default)]
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct OwnedEnvelope<T> {
+    timestamp: Option<u64>,
+    body: T,
+}
+
+pub fn encode<T: Serialize>(body: &T) -> Result<String, Error> {
+    encode_with_timestamp(body, None)
+}
+
+/// Same as `encode`, with the time(msec since unix epoch) when the message is sent.
+pub fn encode_with_timestamp<T: Serialize>(
+    body: &T,
+    timestamp: Option<u64>,
+) -> Result<String, Error> {
+    let envelope = Envelope {
+        version: WIRE_VERSION,
+        timestamp,
+        body,
+    };
+    Ok(serde_json::to_string(&envelope)?)
+}
+
+/// Returns `Error::UnsupportedWireVersion` if the payload is encoded in another version.
+pub fn decode<T: DeserializeOwned>(payload: &str) -> Result<T, Error> {
+    decode_with_timestamp(payload).map(|(body, _)| body)
+}
+
+/// Same as `decode`, with the timestamp if the sender put it.
+pub fn decode_with_timestamp<T: DeserializeOwned>(
+    payload: &str,
+) -> Result<(T, Option<u64>), Error> {
+    let VersionOnly { version } = serde_json::from_str(payload)?;
+    if version != WIRE_VERSION {
+        return Err(Error::UnsupportedWireVersion(version));
+    }
+    let envelope: OwnedEnvelope<T> = serde_json::from_str(payload)?;
+    Ok((envelope.body, envelope.timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockdata::{Block, BlockHash};
+    use crate::net::{Message, MessageType, SignedMessage, SignerID};
+    use crate::test_helper::{create_message, TestKeys};
+
+    /// Encoded messages which must not change without incrementing `WIRE_VERSION`.
+    fn corpus() -> Vec<(Message, &'static str)> {
+        let sender_id = SignerID::new(TestKeys::new().pubkeys()[0]);
+        vec![
+            (
+                create_message(),
+                r#"{"version":2,"body":{"message_type":{"Signature":[48,69,2,33,0,209,78,75,40,108,63,135,236,126,58,248,69,201,134,198,123,9,100,136,101,202,168,134,119,114,0,86,36,17,238,152,190,2,32,91,12,234,133,10,255,32,122,215,249,21,62,10,88,133,223,155,69,205,171,31,105,114,13,174,21,159,118,161,43,58,137]},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}}"#,
+            ),
+            (
+                Message {
+                    message_type: MessageType::Candidateblock(Block::new(vec![0, 1, 255])),
+                    sender_id,
+                    height: 1,
+                    round: 0,
+                },
+                r#"{"version":2,"body":{"message_type":{"Candidateblock":[0,1,255]},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}}"#,
+            ),
+            (
+                Message {
+                    message_type: MessageType::Completedblock(Block::new(vec![0, 1, 255])),
+                    sender_id,
+                    height: 1,
+                    round: 0,
+                },
+                r#"{"version":2,"body":{"message_type":{"Completedblock":[0,1,255]},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}}"#,
+            ),
+            (
+                Message {
+                    message_type: MessageType::Roundfailure,
+                    sender_id,
+                    height: 1,
+                    round: 0,
+                },
+                r#"{"version":2,"body":{"message_type":"Roundfailure","sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}}"#,
+            ),
+            (
+                Message {
+                    message_type: MessageType::Roundannounce(Block::new(vec![0, 1, 255])),
+                    sender_id,
+                    height: 1,
+                    round: 0,
+                },
+                r#"{"version":2,"body":{"message_type":{"Roundannounce":[0,1,255]},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}}"#,
+            ),
+            (
+                Message {
+                    message_type: MessageType::Ready {
+                        signer_index: 1,
+                        block_hash: BlockHash::from_slice(&[1; 32]).unwrap(),
+                    },
+                    sender_id,
+                    height: 1,
+                    round: 0,
+                },
+                r#"{"version":2,"body":{"message_type":{"Ready":{"signer_index":1,"block_hash":[1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1]}},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}}"#,
+            ),
+            (
+                Message {
+                    message_type: MessageType::Blockhash {
+                        master_index: 2,
+                        block_hash: BlockHash::from_slice(&[2; 32]).unwrap(),
+                    },
+                    sender_id,
+                    height: 1,
+                    round: 0,
+                },
+                r#"{"version":2,"body":{"message_type":{"Blockhash":{"master_index":2,"block_hash":[2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2]}},"sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252],"height":1,"round":0}}"#,
+            ),
+        ]
+    }
+
+    /// Position of the message type in `MessageType`. Adding a message type fails to compile
+    /// here, so that it is added to the corpus too.
+    fn type_index(message_type: &MessageType) -> usize {
+        match message_type {
+            MessageType::Candidateblock(_) => 0,
+            MessageType::Signature(_) => 1,
+            MessageType::Completedblock(_) => 2,
+            MessageType::Roundfailure => 3,
+            MessageType::Roundannounce(_) => 4,
+            MessageType::Ready { .. } => 5,
+            MessageType::Blockhash { .. } => 6,
+        }
+    }
+
+    #[test]
+    fn test_corpus_covers_all_message_types() {
+        let mut indices: Vec<usize> = corpus()
+            .iter()
+            .map(|(message, _)| type_index(&message.message_type))
+            .collect();
+        indices.sort();
+        assert_eq!(indices, (0..7).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_corpus() {
+        for (message, encoded) in corpus() {
+            assert_eq!(encode(&message).unwrap(), encoded);
+
+            let decoded: Message = decode(encoded).unwrap();
+            assert_eq!(decoded.message_type, message.message_type);
+            assert_eq!(decoded.sender_id, message.sender_id);
+            assert_eq!(decoded.height, message.height);
+            assert_eq!(decoded.round, message.round);
+        }
+    }
+
+    #[test]
+    fn test_signed_message_round_trip() {
+        let testkeys = TestKeys::new();
+        let signed = SignedMessage::sign(create_message(), 0, &testkeys.key[0]).unwrap();
+        let encoded = encode(&signed).unwrap();
+        assert!(encoded.starts_with(r#"{"version":2,"body":{"message":{"message_type":"#));
+
+        let decoded: SignedMessage = decode(&encoded).unwrap();
+        assert_eq!(decoded.signature, signed.signature);
+        assert!(decoded.verify(&testkeys.pubkeys()).is_ok());
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let encoded = encode_with_timestamp(&create_message(), Some(1_564_000_000_000)).unwrap();
+        assert!(encoded.starts_with(r#"{"version":2,"timestamp":1564000000000,"body":"#));
+
+        let (decoded, timestamp): (Message, Option<u64>) = decode_with_timestamp(&encoded).unwrap();
+        assert_eq!(decoded.message_type, create_message().message_type);
+        assert_eq!(timestamp, Some(1_564_000_000_000));
+
+        // message from the node which doesn't put timestamp.
+        let (_, timestamp): (Message, Option<u64>) =
+            decode_with_timestamp(&encode(&create_message()).unwrap()).unwrap();
+        assert_eq!(timestamp, None);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        // version 1 message has neither the height nor the round.
+        let encoded = r#"{"version":1,"body":{"message_type":"Roundfailure","sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252]}}"#;
+        match decode::<Message>(encoded) {
+            Err(Error::UnsupportedWireVersion(1)) => {}
+            r => panic!("Should be UnsupportedWireVersion, but {:?}", r),
+        }
+
+        // message of the node before the envelope.
+        let encoded = r#"{"message_type":"Roundfailure","sender_id":[3,131,26,105,184,0,152,51,171,91,3,38,1,46,175,72,155,254,163,90,115,33,177,202,21,177,29,136,19,20,35,250,252]}"#;
+        match decode::<Message>(encoded) {
+            Err(Error::UnsupportedWireVersion(0)) => {}
+            r => panic!("Should be UnsupportedWireVersion, but {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright (c) 2019 Chaintope Inc.
+// Distributed under the MIT software license, see the accompanying
+// file COPYING or http://www.opensource.org/licenses/mit-license.php.
+
+//! Subscriber of block notifications which Tapyrus Core publishes with `-zmqpubhashblock`.
+//!
+//! This implements only what the subscriber needs of ZMTP 3.0: NULL security mechanism, SUB
+//! socket and receiving multipart messages over TCP.
+
+use crate::errors::Error;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Topic of the notification of new blocks.
+pub const TOPIC_HASHBLOCK: &[u8] = b"hashblock";
+
+/// Interval to connect again after the connection is lost.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// Start the thread which subscribes `hashblock` notifications at `endpoint`, like
+/// `tcp://127.0.0.1:28332`, and sends hash of each notified block to `sender`.
+/// When the connection fails, the thread connects again after a while, so the node keeps
+/// polling Tapyrus Core meanwhile. The thread exits when `sender` is disconnected.
+pub fn subscribe_block_hashes(
+    endpoint: &str,
+    sender: Sender<String>,
+) -> Result<JoinHandle<()>, Error> {
+    let address = tcp_address(endpoint)?.to_string();
+    let handle = std::thread::spawn(move || loop {
+        match receive_block_hashes(&address, &sender) {
+            Ok(()) => return,
+            Err(e) => log::warn!(
+                "ZMQ subscription to {} failed. Retry after {:?}. error: {:?}",
+                address,
+                RECONNECT_INTERVAL,
+                e
+            ),
+        }
+        std::thread::sleep(RECONNECT_INTERVAL);
+    });
+    Ok(handle)
+}
+
+fn tcp_address(endpoint: &str) -> Result<&str, Error> {
+    match endpoint.trim_start_matches("tcp://") {
+        address if address.len() < endpoint.len() && !address.is_empty() => Ok(address),
+        _ => Err(Error::InvalidArgs(format!(
+            "ZMQ endpoint must be tcp://HOST:PORT. endpoint: {}",
+            endpoint
+        ))),
+    }
+}
+
+/// Receive notifications until the connection is lost. Returns `Ok` when `sender` is disconnected.
+fn receive_block_hashes(address: &str, sender: &Sender<String>) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(address)?;
+    handshake(&mut stream)?;
+    let mut subscription = vec![0x01];
+    subscription.extend_from_slice(TOPIC_HASHBLOCK);
+    write_frame(&mut stream, 0, &subscription)?;
+    log::info!("Subscribed block notifications at {}", address);
+
+    loop {
+        let message = read_message(&mut stream)?;
+        // message is [topic, block hash, sequence number].
+        if message.len() < 2 || message[0] != TOPIC_HASHBLOCK || message[1].len() != 32 {
+            continue;
+        }
+        if sender.send(hex::encode(&message[1])).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Exchange greetings and READY commands as a SUB socket.
+fn handshake<S: Read + Write>(stream: &mut S) -> std::io::Result<()> {
+    stream.write_all(&greeting())?;
+    let mut peer = [0u8; 64];
+    stream.read_exact(&mut peer)?;
+    if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 || &peer[12..16] != b"NULL" {
+        return Err(invalid_data("peer isn't ZMTP 3 socket with NULL mechanism"));
+    }
+
+    write_frame(stream, FLAG_COMMAND, &ready_command(b"SUB"))?;
+    let (flags, body) = read_frame(stream)?;
+    if flags & FLAG_COMMAND == 0 || !body.starts_with(b"\x05READY") {
+        return Err(invalid_data("peer didn't send READY command"));
+    }
+    Ok(())
+}
+
+fn greeting() -> [u8; 64] {
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    // version 3.0
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting
+}
+
+fn ready_command(socket_type: &[u8]) -> Vec<u8> {
+    let mut body = b"\x05READY\x0bSocket-Type".to_vec();
+    body.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    body.extend_from_slice(socket_type);
+    body
+}
+
+fn write_frame<W: Write>(stream: &mut W, flags: u8, body: &[u8]) -> std::io::Result<()> {
+    if body.len() > 255 {
+        stream.write_all(&[flags | FLAG_LONG])?;
+        stream.write_all(&(body.len() as u64).to_be_bytes())?;
+    } else {
+        stream.write_all(&[flags, body.len() as u8])?;
+    }
+    stream.write_all(body)
+}
+
+fn read_frame<R: Read>(stream: &mut R) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut flags = [0u8; 1];
+    stream.read_exact(&mut flags)?;
+    let size = if flags[0] & FLAG_LONG != 0 {
+        let mut size = [0u8; 8];
+        stream.read_exact(&mut size)?;
+        u64::from_be_bytes(size)
+    } else {
+        let mut size = [0u8; 1];
+        stream.read_exact(&mut size)?;
+        u64::from(size[0])
+    };
+    // notifications are small. Long frame is an error of the peer.
+    if size > 1024 * 1024 {
+        return Err(invalid_data("frame is too large"));
+    }
+    let mut body = vec![0u8; size as usize];
+    stream.read_exact(&mut body)?;
+    Ok((flags[0], body))
+}
+
+/// Read frames of a message. Commands between messages are skipped.
+fn read_message<R: Read>(stream: &mut R) -> std::io::Result<Vec<Vec<u8>>> {
+    let mut frames = Vec::new();
+    loop {
+        let (flags, body) = read_frame(stream)?;
+        if flags & FLAG_COMMAND != 0 {
+            continue;
+        }
+        frames.push(body);
+        if flags & FLAG_MORE == 0 {
+            return Ok(frames);
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc::channel;
+
+    /// Publisher which sends the notifications as Tapyrus Core does.
+    fn fake_publisher(hashes: Vec<[u8; 32]>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("tcp://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&greeting()).unwrap();
+            let mut peer = [0u8; 64];
+            stream.read_exact(&mut peer).unwrap();
+            write_frame(&mut stream, FLAG_COMMAND, &ready_command(b"PUB")).unwrap();
+            let (_, ready) = read_frame(&mut stream).unwrap();
+            assert!(ready.ends_with(b"SUB"));
+            let (_, subscription) = read_frame(&mut stream).unwrap();
+            assert_eq!(&subscription[..], b"\x01hashblock");
+
+            for (sequence, hash) in hashes.iter().enumerate() {
+                write_frame(&mut stream, FLAG_MORE, TOPIC_HASHBLOCK).unwrap();
+                write_frame(&mut stream, FLAG_MORE, hash).unwrap();
+                write_frame(&mut stream, 0, &(sequence as u32).to_le_bytes()).unwrap();
+            }
+        });
+        endpoint
+    }
+
+    #[test]
+    fn test_subscribe_block_hashes() {
+        let endpoint = fake_publisher(vec![[0x01; 32], [0x02; 32]]);
+        let (sender, receiver) = channel();
+        subscribe_block_hashes(&endpoint, sender).unwrap();
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "01".repeat(32));
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "02".repeat(32));
+    }
+
+    #[test]
+    fn test_invalid_endpoint() {
+        let (sender, _receiver) = channel();
+        for endpoint in &["127.0.0.1:28332", "ipc:///tmp/tapyrus", "tcp://"] {
+            match subscribe_block_hashes(endpoint, sender.clone()) {
+                Err(Error::InvalidArgs(_)) => {}
+                _ => panic!("endpoint {} should be invalid", endpoint),
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_frame() {
+        let body = vec![0xab; 300];
+        let mut buf = Vec::new();
+        write_frame(&mut buf, FLAG_MORE, &body).unwrap();
+        assert_eq!(buf[0], FLAG_MORE | FLAG_LONG);
+        assert_eq!(
+            read_frame(&mut &buf[..]).unwrap(),
+            (FLAG_MORE | FLAG_LONG, body)
+        );
+    }
+}